@@ -0,0 +1,881 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use crate::parser::*;
+use crate::printer::print_argument;
+use crate::printer::print_case_pattern;
+use crate::printer::print_command;
+use crate::printer::print_item;
+use crate::printer::print_redirects;
+use crate::printer::print_sequence;
+use crate::visit::Visit;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatOptions {
+  /// Width at which boolean lists (ex. `cmd1 && cmd2`) are broken after
+  /// each operator onto indented lines, or `None` to never break them.
+  pub line_width: Option<usize>,
+  /// Number of spaces to indent the continued lines of a boolean list
+  /// and the commands within compound commands that have comments.
+  pub indent_width: usize,
+}
+
+impl Default for FormatOptions {
+  fn default() -> Self {
+    FormatOptions {
+      line_width: None,
+      indent_width: 2,
+    }
+  }
+}
+
+/// Formats the script with consistent whitespace.
+///
+/// Each command is printed in the canonical form of the printer.
+/// Comments are kept, so a compound command with comments inside it
+/// (ex. an `if` statement) is written with each of its commands on an
+/// indented line, and a comment following an operator (ex. `&&`) moves
+/// the next command to an indented line. Commands on separate lines
+/// stay on separate lines along with the comments between them, and
+/// blank lines are collapsed to a single one.
+///
+/// A command with a comment inside one of its words (ex. a command
+/// substitution) is kept as written.
+pub fn format(
+  input: &str,
+  options: &FormatOptions,
+) -> Result<String, ParseError> {
+  let list = match parse(input) {
+    Ok(list) => list,
+    // the input may only have comments
+    Err(err) if err.kind == ParseErrorKind::EmptyCommand => SequentialList {
+      items: Vec::new(),
+      span: Span::default(),
+    },
+    Err(err) => return Err(err),
+  };
+
+  let mut word_spans = WordSpanCollector::default();
+  word_spans.visit_sequential_list(&list);
+
+  let mut formatter = Formatter {
+    input,
+    options,
+    word_spans: word_spans.spans,
+    text: String::new(),
+    indent: 0,
+    here_doc_bodies: Vec::new(),
+    line_end: LineEnd::Item,
+  };
+  let position = formatter.write_list(&list, 0);
+  formatter.write_trivia(position, input.len());
+  formatter.write_here_doc_bodies();
+
+  let mut text = formatter.text;
+  if input.ends_with('\n') && !text.is_empty() {
+    text.push('\n');
+  }
+  Ok(text)
+}
+
+/// Formats an item that doesn't contain comments, also returning the
+/// bodies of the here-documents on its line.
+fn format_item(
+  item: &SequentialListItem,
+  line_width: usize,
+  indent: usize,
+  options: &FormatOptions,
+) -> (String, Vec<String>) {
+  let (mut text, here_doc_bodies) = print_item(item);
+  let is_long = match options.line_width {
+    Some(max_width) => line_width + text.chars().count() > max_width,
+    None => false,
+  };
+  // the bodies of here-documents must directly follow the line
+  if is_long && here_doc_bodies.is_empty() && !text.contains('\n') {
+    if let Sequence::BooleanList(list) = &item.sequence {
+      text = format_broken_boolean_list(list, indent, options);
+      if item.is_async {
+        text.push_str(" &");
+      }
+    }
+  }
+  (text, here_doc_bodies)
+}
+
+/// Formats the boolean list with each sequence after
+/// the first on its own indented line.
+fn format_broken_boolean_list(
+  list: &BooleanList,
+  indent: usize,
+  options: &FormatOptions,
+) -> String {
+  let separator = format!("\n{}", " ".repeat(indent + options.indent_width));
+  let mut text = String::new();
+  let mut list = list;
+  loop {
+    text.push_str(&print_sequence(&list.current).0);
+    text.push(' ');
+    text.push_str(list.op.as_str());
+    text.push_str(&separator);
+    match &list.next {
+      Sequence::BooleanList(next) => list = next,
+      next => {
+        text.push_str(&print_sequence(next).0);
+        return text;
+      }
+    }
+  }
+}
+
+/// What the text written so far ends with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineEnd {
+  /// An item, which a following item on the line is separated from
+  /// with a `;`.
+  Item,
+  /// An async item (ex. `cmd &`), which a following item on the line
+  /// is separated from with a space.
+  AsyncItem,
+  /// Text that's followed by an item after a space (ex. `if`).
+  Open,
+  /// Text after which the items start on a new line (ex. `then`).
+  Block,
+  /// A comment, so anything following must start on a new line.
+  Comment,
+}
+
+struct Formatter<'a> {
+  input: &'a str,
+  options: &'a FormatOptions,
+  /// Spans of the text where a `#` doesn't start a comment.
+  word_spans: Vec<Span>,
+  text: String,
+  /// Number of spaces at the start of new lines.
+  indent: usize,
+  /// Bodies of the here-documents on the current line, which are
+  /// written on the lines following it.
+  here_doc_bodies: Vec<String>,
+  line_end: LineEnd,
+}
+
+impl<'a> Formatter<'a> {
+  /// Gets the width of the current line.
+  fn line_width(&self) -> usize {
+    let line_start = self.text.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    self.text[line_start..].chars().count()
+  }
+
+  fn is_in_word(&self, position: usize) -> bool {
+    self
+      .word_spans
+      .iter()
+      .any(|span| span.start <= position && position < span.end)
+  }
+
+  /// Gets if there's a comment within the span, which is any `#`
+  /// outside of the words because the parser skips the rest of the line.
+  fn contains_comment(&self, span: Span) -> bool {
+    self.input[span.start..span.end]
+      .match_indices('#')
+      .any(|(index, _)| !self.is_in_word(span.start + index))
+  }
+
+  /// Finds a reserved word or operator (ex. `then` or `;;`) between
+  /// the positions, skipping any comments.
+  fn find_token(&self, start: usize, end: usize, token: &str) -> Option<usize> {
+    let mut is_comment = false;
+    for (index, c) in self.input[start..end].char_indices() {
+      let position = start + index;
+      if is_comment {
+        is_comment = c != '\n';
+      } else if self.is_in_word(position) {
+        continue;
+      } else if c == '#' {
+        is_comment = true;
+      } else if self.input[position..end].starts_with(token) {
+        return Some(position);
+      }
+    }
+    None
+  }
+
+  /// Finds the reserved word, which the parser ensured is between
+  /// the positions.
+  fn find_keyword(&self, start: usize, end: usize, keyword: &str) -> usize {
+    self.find_token(start, end, keyword).unwrap_or(end)
+  }
+
+  /// Writes the comments in the input between the positions, returning
+  /// the number of line breaks following the last comment or other text.
+  fn write_trivia(&mut self, start: usize, end: usize) -> usize {
+    if start >= end {
+      return 0;
+    }
+    let text = &self.input[start..end];
+    let mut newlines = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+      match c {
+        _ if self.is_in_word(start + index) => newlines = 0,
+        '\n' => newlines += 1,
+        '\\' if chars.peek().map(|(_, c)| *c) == Some('\n') => {
+          // line continuation
+          chars.next();
+        }
+        '#' => {
+          let comment = text[index..].lines().next().unwrap().trim_end();
+          if newlines == 0
+            && !self.text.is_empty()
+            && self.line_end != LineEnd::Comment
+          {
+            self.text.push(' ');
+          } else {
+            self.start_line(newlines);
+          }
+          self.text.push_str(comment);
+          self.line_end = LineEnd::Comment;
+          newlines = 0;
+          while chars.peek().map(|(_, c)| *c != '\n').unwrap_or(false) {
+            chars.next();
+          }
+        }
+        c if c.is_whitespace() => {}
+        // reserved words and operators
+        _ => newlines = 0,
+      }
+    }
+    newlines
+  }
+
+  /// Writes the items of the list along with the comments before and
+  /// between them, returning the position following the last item.
+  fn write_list(&mut self, list: &SequentialList, start: usize) -> usize {
+    let mut position = start;
+    for item in &list.items {
+      let newlines = self.write_trivia(position, item.span.start);
+      self.write_item(item, newlines);
+      // the item's span includes the bodies of the here-documents
+      // following its line, so only the rest of the line is trivia
+      let end = item.sequence.span().end;
+      position = match self.input[end..item.span.end].find('\n') {
+        Some(index) => {
+          self.write_trivia(end, end + index);
+          item.span.end
+        }
+        None => end,
+      };
+    }
+    position
+  }
+
+  fn write_item(&mut self, item: &SequentialListItem, newlines: usize) {
+    match self.line_end {
+      _ if self.text.is_empty() => {}
+      LineEnd::Item if newlines == 0 => self.text.push_str("; "),
+      LineEnd::AsyncItem if newlines == 0 => self.text.push(' '),
+      LineEnd::Open => self.text.push(' '),
+      _ => self.start_line(newlines),
+    }
+    if self.contains_comment(item.sequence.span()) {
+      self.write_sequence(&item.sequence);
+      if item.is_async {
+        self.text.push_str(" &");
+      }
+    } else {
+      let (text, here_doc_bodies) =
+        format_item(item, self.line_width(), self.indent, self.options);
+      self.write_text(&text, here_doc_bodies);
+    }
+    self.line_end = if item.is_async {
+      LineEnd::AsyncItem
+    } else {
+      LineEnd::Item
+    };
+  }
+
+  fn write_text(&mut self, text: &str, here_doc_bodies: Vec<String>) {
+    self.text.push_str(text);
+    self.here_doc_bodies.extend(here_doc_bodies);
+  }
+
+  fn write_sequence(&mut self, sequence: &Sequence) {
+    if !self.contains_comment(sequence.span()) {
+      let (text, here_doc_bodies) = print_sequence(sequence);
+      self.write_text(&text, here_doc_bodies);
+      return;
+    }
+    match sequence {
+      // the comment is within the value
+      Sequence::ShellVar(env_var) => {
+        self
+          .text
+          .push_str(&self.input[env_var.span.start..env_var.span.end]);
+      }
+      Sequence::Pipeline(pipeline) => {
+        if pipeline.negated {
+          self.text.push_str("! ");
+        }
+        self.write_pipeline_inner(&pipeline.inner);
+      }
+      Sequence::BooleanList(list) => {
+        let indent = self.indent;
+        let mut list = list.as_ref();
+        loop {
+          self.write_sequence(&list.current);
+          self.write_operator(
+            list.op.as_str(),
+            list.current.span().end,
+            list.next.span().start,
+            indent,
+          );
+          match &list.next {
+            Sequence::BooleanList(next) => list = next,
+            next => {
+              self.write_sequence(next);
+              break;
+            }
+          }
+        }
+        self.indent = indent;
+      }
+      Sequence::FunctionDefinition(definition) => {
+        self.text.push_str(&definition.name);
+        self.text.push_str("() {");
+        // comments before the `{` are written after it
+        self.write_block(
+          &definition.body,
+          definition.span.start + definition.name.len(),
+          definition.span.end - 1,
+        );
+        self.write_closer("}");
+      }
+    }
+  }
+
+  fn write_pipeline_inner(&mut self, inner: &PipelineInner) {
+    let indent = self.indent;
+    let mut inner = inner;
+    loop {
+      match inner {
+        PipelineInner::Command(command) => {
+          self.write_command(command);
+          break;
+        }
+        PipelineInner::PipeSequence(sequence) => {
+          self.write_command(&sequence.current);
+          self.write_operator(
+            match sequence.op {
+              PipeSequenceOperator::Stdout => "|",
+              PipeSequenceOperator::StdoutStderr => "|&",
+            },
+            sequence.current.span.end,
+            sequence.next.span().start,
+            indent,
+          );
+          inner = &sequence.next;
+        }
+      }
+    }
+    self.indent = indent;
+  }
+
+  /// Writes an operator between commands (ex. `&&`) along with the
+  /// comments following it, after which the next command starts on
+  /// an indented line.
+  fn write_operator(
+    &mut self,
+    operator: &str,
+    start: usize,
+    end: usize,
+    indent: usize,
+  ) {
+    self.text.push(' ');
+    self.text.push_str(operator);
+    self.line_end = LineEnd::Open;
+    self.write_trivia(start, end);
+    if self.line_end == LineEnd::Comment {
+      self.indent = indent + self.options.indent_width;
+      self.start_line(1);
+    } else {
+      self.text.push(' ');
+    }
+  }
+
+  fn write_command(&mut self, command: &Command) {
+    if !self.contains_comment(command.span) {
+      let (text, here_doc_bodies) = print_command(command);
+      self.write_text(&text, here_doc_bodies);
+      return;
+    }
+    let end = self.end_before_redirects(command);
+    match &command.inner {
+      // the comment is within a word (ex. a command substitution)
+      CommandInner::Simple(_) => {
+        self
+          .text
+          .push_str(&self.input[command.span.start..command.span.end]);
+        self.here_doc_bodies.extend(print_command(command).1);
+        return;
+      }
+      CommandInner::Subshell(list) => {
+        self.text.push('(');
+        self.write_block(list, command.span.start + 1, end - 1);
+        self.write_closer(")");
+      }
+      CommandInner::Group(list) => {
+        self.text.push('{');
+        self.write_block(list, command.span.start + 1, end - 1);
+        self.write_closer("}");
+      }
+      CommandInner::If(if_clause) => self.write_if_clause(if_clause),
+      CommandInner::For(for_clause) => {
+        self.text.push_str("for ");
+        self.text.push_str(&for_clause.var_name);
+        self.text.push_str(" in");
+        for word in &for_clause.wordlist {
+          self.text.push(' ');
+          self.text.push_str(&print_argument(word));
+        }
+        self.line_end = LineEnd::Item;
+        let words_end = match for_clause.wordlist.last() {
+          Some(word) => word.span().end,
+          None => for_clause.span.start + "for".len(),
+        };
+        self.write_loop_body(words_end, &for_clause.body, for_clause.span.end);
+      }
+      CommandInner::While(while_clause) => {
+        let keyword = if while_clause.is_until {
+          "until"
+        } else {
+          "while"
+        };
+        self.text.push_str(keyword);
+        self.line_end = LineEnd::Open;
+        let position = self.write_list(
+          &while_clause.condition,
+          while_clause.span.start + keyword.len(),
+        );
+        self.write_loop_body(
+          position,
+          &while_clause.body,
+          while_clause.span.end,
+        );
+      }
+      CommandInner::Case(case_clause) => self.write_case_clause(case_clause),
+    }
+    let (text, here_doc_bodies) = print_redirects(&command.redirects);
+    self.write_text(&text, here_doc_bodies);
+  }
+
+  /// Gets the end of a compound command's text before its redirects.
+  fn end_before_redirects(&self, command: &Command) -> usize {
+    let end = match command.redirects.first() {
+      Some(redirect) => redirect.span.start,
+      None => command.span.end,
+    };
+    self.input[..end]
+      .trim_end_matches(|c: char| c.is_whitespace() || c == '\\')
+      .len()
+  }
+
+  fn write_if_clause(&mut self, if_clause: &IfClause) {
+    let fi_start = if_clause.span.end - "fi".len();
+    let mut clause = if_clause;
+    let mut position = clause.span.start + "if".len();
+    self.text.push_str("if");
+    loop {
+      self.line_end = LineEnd::Open;
+      position = self.write_list(&clause.condition, position);
+      let then_start =
+        self.find_keyword(position, clause.body.span.start, "then");
+      self.write_trivia(position, then_start);
+      self.write_list_terminator("then");
+      let (next_start, keyword) = match &clause.else_part {
+        Some(ElsePart::Elif(next)) => (next.condition.span.start, "elif"),
+        Some(ElsePart::Else(list)) => (list.span.start, "else"),
+        None => (fi_start, "fi"),
+      };
+      let keyword_start =
+        self.find_keyword(clause.body.span.end, next_start, keyword);
+      self.write_block(&clause.body, then_start + "then".len(), keyword_start);
+      self.write_closer(keyword);
+      position = keyword_start + keyword.len();
+      match &clause.else_part {
+        Some(ElsePart::Elif(next)) => clause = next,
+        Some(ElsePart::Else(list)) => {
+          self.line_end = LineEnd::Block;
+          self.write_block(list, position, fi_start);
+          self.write_closer("fi");
+          return;
+        }
+        None => return,
+      }
+    }
+  }
+
+  /// Writes the `do` and body of a loop following the position.
+  fn write_loop_body(
+    &mut self,
+    position: usize,
+    body: &SequentialList,
+    end: usize,
+  ) {
+    let do_start = self.find_keyword(position, body.span.start, "do");
+    self.write_trivia(position, do_start);
+    self.write_list_terminator("do");
+    self.write_block(body, do_start + "do".len(), end - "done".len());
+    self.write_closer("done");
+  }
+
+  fn write_case_clause(&mut self, case_clause: &CaseClause) {
+    self.text.push_str("case ");
+    self.text.push_str(&print_argument(&case_clause.word));
+    self.text.push_str(" in");
+    self.line_end = LineEnd::Block;
+    let esac_start = case_clause.span.end - "esac".len();
+    let first_start = match case_clause.items.first() {
+      Some(item) => item.span.start,
+      None => esac_start,
+    };
+    let mut position =
+      self.find_keyword(case_clause.word.span().end, first_start, "in")
+        + "in".len();
+    self.indent += self.options.indent_width;
+    for (i, item) in case_clause.items.iter().enumerate() {
+      let newlines = self.write_trivia(position, item.span.start);
+      self.start_line(newlines);
+      for (i, pattern) in item.patterns.iter().enumerate() {
+        if i > 0 {
+          self.text.push('|');
+        }
+        self.text.push_str(&print_case_pattern(pattern));
+      }
+      self.text.push(')');
+      self.line_end = LineEnd::Block;
+      let next_start = match case_clause.items.get(i + 1) {
+        Some(item) => item.span.start,
+        None => esac_start,
+      };
+      // patterns always exist
+      let patterns_end = item.patterns.last().unwrap().span().end;
+      let body_start = match item.body.items.first() {
+        Some(first) => first.span.start,
+        None => next_start,
+      };
+      let paren_end = self.find_keyword(patterns_end, body_start, ")") + 1;
+      let body_end = match item.body.items.last() {
+        Some(last) => last.span.end,
+        None => paren_end,
+      };
+      // the terminator may be omitted on the last item
+      let terminator = self.find_token(body_end, next_start, ";;");
+      self.write_block(&item.body, paren_end, terminator.unwrap_or(next_start));
+      if self.line_end == LineEnd::Block {
+        // the body is empty
+        self.text.push_str(" ;;");
+      } else {
+        self.indent += self.options.indent_width;
+        self.write_closer(";;");
+        self.indent -= self.options.indent_width;
+      }
+      self.line_end = LineEnd::Block;
+      position = match terminator {
+        Some(terminator) => terminator + ";;".len(),
+        None => next_start,
+      };
+    }
+    self.write_trivia(position, esac_start);
+    self.indent -= self.options.indent_width;
+    self.write_closer("esac");
+  }
+
+  /// Writes a reserved word following a list (ex. `then`), after
+  /// which the next items start on a new line.
+  fn write_list_terminator(&mut self, keyword: &str) {
+    match self.line_end {
+      LineEnd::Item => self.text.push_str("; "),
+      LineEnd::AsyncItem => self.text.push(' '),
+      _ => self.start_line(1),
+    }
+    self.text.push_str(keyword);
+    self.line_end = LineEnd::Block;
+  }
+
+  /// Writes the list on indented lines along with the comments
+  /// up to the end position.
+  fn write_block(&mut self, list: &SequentialList, start: usize, end: usize) {
+    self.line_end = LineEnd::Block;
+    self.indent += self.options.indent_width;
+    let position = self.write_list(list, start);
+    self.write_trivia(position, end);
+    self.indent -= self.options.indent_width;
+  }
+
+  /// Writes text that closes a compound command (ex. `fi`) on a new line.
+  fn write_closer(&mut self, text: &str) {
+    self.start_line(1);
+    self.text.push_str(text);
+    self.line_end = LineEnd::Item;
+  }
+
+  /// Starts a new line, keeping a blank line before it if
+  /// there were blank lines in the input.
+  fn start_line(&mut self, newlines: usize) {
+    if self.text.is_empty() {
+      return;
+    }
+    self.write_here_doc_bodies();
+    self.text.push('\n');
+    if newlines > 1 {
+      self.text.push('\n');
+    }
+    self.text.push_str(&" ".repeat(self.indent));
+  }
+
+  fn write_here_doc_bodies(&mut self) {
+    for body in std::mem::take(&mut self.here_doc_bodies) {
+      self.text.push('\n');
+      self.text.push_str(&body);
+    }
+  }
+}
+
+/// Collects the spans of the text where a `#` doesn't start a comment,
+/// which are the words other than command substitutions and the
+/// bodies of here-documents.
+#[derive(Default)]
+struct WordSpanCollector {
+  spans: Vec<Span>,
+}
+
+impl Visit for WordSpanCollector {
+  fn visit_string_part(&mut self, part: &StringPart) {
+    match part {
+      StringPart::Command { list, .. } => self.visit_sequential_list(list),
+      part => self.spans.push(part.span()),
+    }
+  }
+
+  fn visit_here_doc(&mut self, here_doc: &HereDoc) {
+    self.spans.push(here_doc.span);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::printer::print;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_format() {
+    assert_format("", "");
+    assert_format(
+      "echo 1&&echo 2||  echo 3 ;echo 4|cat |&  cat",
+      "echo 1 && echo 2 || echo 3; echo 4 | cat |& cat",
+    );
+    assert_format(
+      "cmd>out.txt 2>&1 <  in.txt&echo 1",
+      "cmd > out.txt 2>&1 < in.txt & echo 1",
+    );
+    assert_format(
+      "( echo 1 ;echo 2 )&&{ echo 3;}",
+      "(echo 1; echo 2) && { echo 3; }",
+    );
+    assert_format("echo 1\n\n\n\necho 2\n", "echo 1\n\necho 2\n");
+    assert_format(
+      "if true\nthen\n  echo 1\nfi\nfor i in 1 2;do echo $i;done",
+      "if true; then echo 1; fi\nfor i in 1 2; do echo $i; done",
+    );
+    assert_format(
+      "cat  <<EOF\n  $A  body\nEOF\necho   1",
+      "cat <<EOF\n  $A  body\nEOF\necho 1",
+    );
+    assert_format(
+      "cat <<A;cat <<B &\na\nA\nb\nB\necho 1",
+      "cat <<A; cat <<B &\na\nA\nb\nB\necho 1",
+    );
+  }
+
+  #[test]
+  fn test_format_comments() {
+    assert_format("# only a comment\n", "# only a comment\n");
+    assert_format(
+      concat!(
+        "# leading comment\n",
+        "echo  1 ;  # trailing comment\n",
+        "\n",
+        "  # own line comment\n",
+        "echo 2&&  echo '#not a comment'   #end\n",
+      ),
+      concat!(
+        "# leading comment\n",
+        "echo 1 # trailing comment\n",
+        "\n",
+        "# own line comment\n",
+        "echo 2 && echo '#not a comment' #end\n",
+      ),
+    );
+    // compound commands with comments are written across lines
+    assert_format(
+      concat!(
+        "if  true; then\n",
+        "  # comment\n",
+        "  echo  1\n",
+        "fi\n",
+        "echo  $#  ${A#b}  \\#  $(echo  1)\n",
+      ),
+      concat!(
+        "if true; then\n",
+        "  # comment\n",
+        "  echo 1\n",
+        "fi\n",
+        "echo $# ${A#b} $'#' $(echo 1)\n",
+      ),
+    );
+    assert_format(
+      concat!(
+        "for f in a b;do # loop\n",
+        "    # inline\n",
+        "  echo $f&&  # first\n",
+        "  cat $f   # trailing\n",
+        "\n",
+        "  echo  2;echo 3 # same line\n",
+        "done>out.txt # end\n",
+        "case $x in # header\n",
+        "  a) # item\n",
+        "    echo a;; # after\n",
+        "  # before b\n",
+        "  b|c)  echo b\n",
+        "  ;;\n",
+        "  d);;\n",
+        "esac\n",
+      ),
+      concat!(
+        "for f in a b; do # loop\n",
+        "  # inline\n",
+        "  echo $f && # first\n",
+        "    cat $f # trailing\n",
+        "\n",
+        "  echo 2; echo 3 # same line\n",
+        "done > out.txt # end\n",
+        "case $x in # header\n",
+        "  a) # item\n",
+        "    echo a\n",
+        "    ;; # after\n",
+        "  # before b\n",
+        "  b|c)\n",
+        "    echo b\n",
+        "    ;;\n",
+        "  d) ;;\n",
+        "esac\n",
+      ),
+    );
+    assert_format(
+      concat!(
+        "f() # f\n",
+        "{\n",
+        "  echo 1\n",
+        "}\n",
+        "while true # condition\n",
+        "do { echo 1; # group\n",
+        "} | cat; done\n",
+        "if a; then b # b\n",
+        "elif c; then d\n",
+        "else # else\n",
+        "  ( cat <<EOF # here-doc\n",
+        "  body\n",
+        "EOF\n",
+        ") fi",
+      ),
+      concat!(
+        "f() { # f\n",
+        "  echo 1\n",
+        "}\n",
+        "while true # condition\n",
+        "do\n",
+        "  {\n",
+        "    echo 1 # group\n",
+        "  } | cat\n",
+        "done\n",
+        "if a; then\n",
+        "  b # b\n",
+        "elif c; then\n",
+        "  d\n",
+        "else # else\n",
+        "  (\n",
+        "    cat <<EOF # here-doc\n",
+        "  body\n",
+        "EOF\n",
+        "  )\n",
+        "fi",
+      ),
+    );
+    assert_format(
+      "echo 1 |# pipe\n cat&&! echo 2 ||  # or\necho 3 &",
+      "echo 1 | # pipe\n  cat && ! echo 2 || # or\n  echo 3 &",
+    );
+    assert_format(
+      "cat <<EOF  # comment\n# body\nEOF",
+      "cat <<EOF # comment\n# body\nEOF",
+    );
+    // comments within words are kept as written
+    assert_format("echo  $(echo 1 # comment\n)", "echo  $(echo 1 # comment\n)");
+  }
+
+  #[test]
+  fn test_format_line_width() {
+    let options = FormatOptions {
+      line_width: Some(20),
+      indent_width: 4,
+    };
+    assert_format_with_options(
+      "echo 1 && echo 2 || echo 3",
+      "echo 1 &&\n    echo 2 ||\n    echo 3",
+      &options,
+    );
+    assert_format_with_options(
+      "echo 1 && echo 2",
+      "echo 1 && echo 2",
+      &options,
+    );
+    assert_format_with_options(
+      "echo 1; echo 2 && echo 3 &",
+      "echo 1; echo 2 &&\n    echo 3 &",
+      &options,
+    );
+    // only boolean lists are broken
+    assert_format_with_options(
+      "echo 1 2 3 4 5 6 7 8 9 | cat",
+      "echo 1 2 3 4 5 6 7 8 9 | cat",
+      &options,
+    );
+  }
+
+  #[test]
+  fn test_format_error() {
+    let err = format("echo 1 &&", &FormatOptions::default()).unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::Expected);
+  }
+
+  #[track_caller]
+  fn assert_format(input: &str, expected: &str) {
+    assert_format_with_options(input, expected, &FormatOptions::default());
+  }
+
+  #[track_caller]
+  fn assert_format_with_options(
+    input: &str,
+    expected: &str,
+    options: &FormatOptions,
+  ) {
+    let text = format(input, options).unwrap();
+    assert_eq!(text, expected);
+    // formatting is idempotent
+    assert_eq!(format(&text, options).unwrap(), expected);
+    // and only changes the formatting
+    if let (Ok(input), Ok(text)) = (parse(input), parse(&text)) {
+      assert_eq!(print(&text), print(&input));
+    }
+  }
+}
@@ -1,6 +1,9 @@
 // Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
 
+pub mod formatter;
 pub mod parser;
+pub mod printer;
+pub mod visit;
 
 #[cfg(feature = "shell")]
 mod shell;
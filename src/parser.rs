@@ -1,17 +1,69 @@
 // Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
 
-use anyhow::bail;
-use anyhow::Result;
+use std::cell::Cell;
+
 use monch::*;
 
+use crate::visit::Visit;
+use crate::visit::VisitMut;
+
 // Shell grammar rules this is loosely based on:
 // https://pubs.opengroup.org/onlinepubs/009604499/utilities/xcu_chap02.html#tag_02_10_02
 
+/// Byte range of a node within the parsed text.
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  /// Byte offset of the start of the node.
+  pub start: usize,
+  /// Byte offset following the end of the node.
+  pub end: usize,
+}
+
+impl Span {
+  pub fn new(start: usize, end: usize) -> Self {
+    Span { start, end }
+  }
+}
+
+/// Serializes a variant as `{ "kind": ..., "value": ..., "span": ... }`,
+/// which keeps the payload under `value` like the tuple variants did
+/// before nodes had spans.
+#[cfg(feature = "serialization")]
+fn serialize_kind_value<S: serde::Serializer, T: serde::Serialize + ?Sized>(
+  serializer: S,
+  kind: &'static str,
+  value: Option<&T>,
+  span: Span,
+) -> Result<S::Ok, S::Error> {
+  use serde::ser::SerializeStruct;
+
+  let len = if value.is_some() { 3 } else { 2 };
+  let mut state = serializer.serialize_struct("Node", len)?;
+  state.serialize_field("kind", kind)?;
+  if let Some(value) = value {
+    state.serialize_field("value", value)?;
+  }
+  state.serialize_field("span", &span)?;
+  state.end()
+}
+
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SequentialList {
   pub items: Vec<SequentialListItem>,
+  pub span: Span,
+}
+
+impl SequentialList {
+  /// Resets the spans of every node in the list to the default.
+  ///
+  /// Spans are compared by `PartialEq`, so this allows comparing lists
+  /// that were formatted differently or constructed in code.
+  pub fn clear_spans(&mut self) {
+    SpanMapper(|_| Span::default()).visit_sequential_list_mut(self);
+  }
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -20,6 +72,7 @@ pub struct SequentialList {
 pub struct SequentialListItem {
   pub is_async: bool,
   pub sequence: Sequence,
+  pub span: Span,
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -35,6 +88,28 @@ pub enum Sequence {
   Pipeline(Pipeline),
   /// `cmd1 && cmd2 || cmd3`
   BooleanList(Box<BooleanList>),
+  /// `name() { list; }`
+  FunctionDefinition(FunctionDefinition),
+}
+
+impl Sequence {
+  pub fn span(&self) -> Span {
+    match self {
+      Sequence::ShellVar(env_var) => env_var.span,
+      Sequence::Pipeline(pipeline) => pipeline.span,
+      Sequence::BooleanList(list) => list.span,
+      Sequence::FunctionDefinition(definition) => definition.span,
+    }
+  }
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDefinition {
+  pub name: String,
+  pub body: SequentialList,
+  pub span: Span,
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -44,6 +119,7 @@ pub struct Pipeline {
   /// `! pipeline`
   pub negated: bool,
   pub inner: PipelineInner,
+  pub span: Span,
 }
 
 impl From<Pipeline> for Sequence {
@@ -65,6 +141,15 @@ pub enum PipelineInner {
   PipeSequence(Box<PipeSequence>),
 }
 
+impl PipelineInner {
+  pub fn span(&self) -> Span {
+    match self {
+      PipelineInner::Command(command) => command.span,
+      PipelineInner::PipeSequence(sequence) => sequence.span,
+    }
+  }
+}
+
 impl From<PipeSequence> for PipelineInner {
   fn from(p: PipeSequence) -> Self {
     PipelineInner::PipeSequence(Box::new(p))
@@ -102,6 +187,7 @@ pub struct BooleanList {
   pub current: Sequence,
   pub op: BooleanListOperator,
   pub next: Sequence,
+  pub span: Span,
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -121,12 +207,14 @@ pub struct PipeSequence {
   pub current: Command,
   pub op: PipeSequenceOperator,
   pub next: PipelineInner,
+  pub span: Span,
 }
 
 impl From<PipeSequence> for Sequence {
   fn from(p: PipeSequence) -> Self {
     Sequence::Pipeline(Pipeline {
       negated: false,
+      span: p.span,
       inner: p.into(),
     })
   }
@@ -137,7 +225,9 @@ impl From<PipeSequence> for Sequence {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Command {
   pub inner: CommandInner,
-  pub redirect: Option<Redirect>,
+  /// Redirects applied to the command in order from left to right.
+  pub redirects: Vec<Redirect>,
+  pub span: Span,
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -151,30 +241,106 @@ pub enum CommandInner {
   Simple(SimpleCommand),
   /// `(list)`
   Subshell(Box<SequentialList>),
+  /// `{ list; }`
+  Group(Box<SequentialList>),
+  /// `if list; then list; [elif list; then list;]... [else list;] fi`
+  If(IfClause),
+  /// `for name in words...; do list; done`
+  For(ForClause),
+  /// `while list; do list; done` or `until list; do list; done`
+  While(WhileClause),
+  /// `case word in [(]pattern[|pattern]...) list;; ... esac`
+  Case(CaseClause),
 }
 
 impl From<Command> for Sequence {
   fn from(c: Command) -> Self {
     Pipeline {
       negated: false,
+      span: c.span,
       inner: c.into(),
     }
     .into()
   }
 }
 
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfClause {
+  pub condition: SequentialList,
+  pub body: SequentialList,
+  pub else_part: Option<ElsePart>,
+  pub span: Span,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(
+  feature = "serialization",
+  serde(rename_all = "camelCase", tag = "kind")
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ElsePart {
+  /// `elif list; then list;`
+  Elif(Box<IfClause>),
+  /// `else list;`
+  Else(SequentialList),
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForClause {
+  pub var_name: String,
+  pub wordlist: Vec<StringOrWord>,
+  pub body: SequentialList,
+  pub span: Span,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhileClause {
+  /// `until` loops run the body until the condition succeeds
+  pub is_until: bool,
+  pub condition: SequentialList,
+  pub body: SequentialList,
+  pub span: Span,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseClause {
+  pub word: StringOrWord,
+  pub items: Vec<CaseItem>,
+  pub span: Span,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseItem {
+  /// Patterns separated by `|`, any of which may match the word
+  pub patterns: Vec<StringOrWord>,
+  pub body: SequentialList,
+  pub span: Span,
+}
+
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
 #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct SimpleCommand {
   pub env_vars: Vec<EnvVar>,
   pub args: Vec<StringOrWord>,
+  pub span: Span,
 }
 
 impl From<SimpleCommand> for Command {
   fn from(c: SimpleCommand) -> Self {
     Command {
-      redirect: None,
+      redirects: Vec::new(),
+      span: c.span,
       inner: CommandInner::Simple(c),
     }
   }
@@ -205,62 +371,376 @@ impl From<SimpleCommand> for Sequence {
 pub struct EnvVar {
   pub name: String,
   pub value: StringOrWord,
+  pub span: Span,
 }
 
 impl EnvVar {
   pub fn new(name: String, value: StringOrWord) -> Self {
-    EnvVar { name, value }
+    EnvVar {
+      name,
+      value,
+      span: Span::default(),
+    }
   }
 }
 
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-#[cfg_attr(
-  feature = "serialization",
-  serde(rename_all = "camelCase", tag = "kind", content = "value")
-)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum StringOrWord {
-  Word(Vec<StringPart>),
-  String(Vec<StringPart>),
+  Word { parts: Vec<StringPart>, span: Span },
+  String { parts: Vec<StringPart>, span: Span },
 }
 
 impl StringOrWord {
   pub fn new_string(text: &str) -> Self {
-    StringOrWord::String(vec![StringPart::Text(text.to_string())])
+    StringOrWord::String {
+      parts: vec![StringPart::new_text(text)],
+      span: Span::default(),
+    }
   }
 
   pub fn new_word(text: &str) -> Self {
-    StringOrWord::Word(vec![StringPart::Text(text.to_string())])
+    StringOrWord::Word {
+      parts: vec![StringPart::new_text(text)],
+      span: Span::default(),
+    }
   }
 
   pub fn parts(&self) -> &Vec<StringPart> {
     match self {
-      StringOrWord::String(parts) => parts,
-      StringOrWord::Word(parts) => parts,
+      StringOrWord::String { parts, .. } => parts,
+      StringOrWord::Word { parts, .. } => parts,
     }
   }
 
   pub fn into_parts(self) -> Vec<StringPart> {
     match self {
-      StringOrWord::String(parts) => parts,
-      StringOrWord::Word(parts) => parts,
+      StringOrWord::String { parts, .. } => parts,
+      StringOrWord::Word { parts, .. } => parts,
+    }
+  }
+
+  pub fn span(&self) -> Span {
+    match self {
+      StringOrWord::String { span, .. } => *span,
+      StringOrWord::Word { span, .. } => *span,
+    }
+  }
+}
+
+#[cfg(feature = "serialization")]
+impl serde::Serialize for StringOrWord {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    match self {
+      StringOrWord::Word { parts, span } => {
+        serialize_kind_value(serializer, "word", Some(parts), *span)
+      }
+      StringOrWord::String { parts, span } => {
+        serialize_kind_value(serializer, "string", Some(parts), *span)
+      }
     }
   }
 }
 
-#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
-#[cfg_attr(
-  feature = "serialization",
-  serde(rename_all = "camelCase", tag = "kind", content = "value")
-)]
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum StringPart {
   /// Text in the string (ex. `some text`)
-  Text(String),
+  Text { value: String, span: Span },
   /// Variable substitution (ex. `$MY_VAR`)
-  Variable(String),
+  Variable { name: String, span: Span },
   /// Command substitution (ex. `$(command)`)
-  Command(SequentialList),
+  Command { list: SequentialList, span: Span },
+  /// Unquoted pattern characters (ex. `*`, `?`, or `[a-z]`)
+  Glob { value: String, span: Span },
+  /// Home directory at the start of a word (ex. `~` in `~/projects`)
+  Tilde { span: Span },
+  /// Braced parameter expansion (ex. `${MY_VAR:-default}`)
+  ParameterExpansion(ParameterExpansion),
+  /// Arithmetic expansion (ex. `$((1 + 2))`)
+  Arithmetic { expr: ArithmeticExpr, span: Span },
+}
+
+impl StringPart {
+  pub fn new_text(text: &str) -> Self {
+    StringPart::Text {
+      value: text.to_string(),
+      span: Span::default(),
+    }
+  }
+
+  pub fn new_variable(name: &str) -> Self {
+    StringPart::Variable {
+      name: name.to_string(),
+      span: Span::default(),
+    }
+  }
+
+  pub fn new_glob(text: &str) -> Self {
+    StringPart::Glob {
+      value: text.to_string(),
+      span: Span::default(),
+    }
+  }
+
+  pub fn span(&self) -> Span {
+    match self {
+      StringPart::Text { span, .. }
+      | StringPart::Variable { span, .. }
+      | StringPart::Command { span, .. }
+      | StringPart::Glob { span, .. }
+      | StringPart::Tilde { span }
+      | StringPart::Arithmetic { span, .. } => *span,
+      StringPart::ParameterExpansion(expansion) => expansion.span,
+    }
+  }
+}
+
+#[cfg(feature = "serialization")]
+impl serde::Serialize for StringPart {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    match self {
+      StringPart::Text { value, span } => {
+        serialize_kind_value(serializer, "text", Some(value), *span)
+      }
+      StringPart::Variable { name, span } => {
+        serialize_kind_value(serializer, "variable", Some(name), *span)
+      }
+      StringPart::Command { list, span } => {
+        serialize_kind_value(serializer, "command", Some(list), *span)
+      }
+      StringPart::Glob { value, span } => {
+        serialize_kind_value(serializer, "glob", Some(value), *span)
+      }
+      StringPart::Tilde { span } => {
+        serialize_kind_value::<_, ()>(serializer, "tilde", None, *span)
+      }
+      StringPart::ParameterExpansion(expansion) => serialize_kind_value(
+        serializer,
+        "parameterExpansion",
+        Some(expansion),
+        expansion.span,
+      ),
+      StringPart::Arithmetic { expr, span } => {
+        serialize_kind_value(serializer, "arithmetic", Some(expr), *span)
+      }
+    }
+  }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ArithmeticExpr {
+  /// Integer (ex. `1`)
+  Number { value: i64, span: Span },
+  /// Variable, which may or may not start with a `$` (ex. `VAR` or `$VAR`)
+  Variable { name: String, span: Span },
+  /// Parenthesized expression (ex. `(1 + 2)`)
+  Parentheses {
+    expr: Box<ArithmeticExpr>,
+    span: Span,
+  },
+  /// Unary operation (ex. `-1`)
+  Unary {
+    op: UnaryArithmeticOp,
+    operand: Box<ArithmeticExpr>,
+    span: Span,
+  },
+  /// Binary operation (ex. `1 + 2`)
+  Binary {
+    left: Box<ArithmeticExpr>,
+    op: BinaryArithmeticOp,
+    right: Box<ArithmeticExpr>,
+    span: Span,
+  },
+}
+
+impl ArithmeticExpr {
+  pub fn span(&self) -> Span {
+    match self {
+      ArithmeticExpr::Number { span, .. }
+      | ArithmeticExpr::Variable { span, .. }
+      | ArithmeticExpr::Parentheses { span, .. }
+      | ArithmeticExpr::Unary { span, .. }
+      | ArithmeticExpr::Binary { span, .. } => *span,
+    }
+  }
+}
+
+#[cfg(feature = "serialization")]
+impl serde::Serialize for ArithmeticExpr {
+  fn serialize<S: serde::Serializer>(
+    &self,
+    serializer: S,
+  ) -> Result<S::Ok, S::Error> {
+    #[derive(serde::Serialize)]
+    struct UnaryValue<'a> {
+      op: &'a UnaryArithmeticOp,
+      operand: &'a ArithmeticExpr,
+    }
+
+    #[derive(serde::Serialize)]
+    struct BinaryValue<'a> {
+      left: &'a ArithmeticExpr,
+      op: &'a BinaryArithmeticOp,
+      right: &'a ArithmeticExpr,
+    }
+
+    match self {
+      ArithmeticExpr::Number { value, span } => {
+        serialize_kind_value(serializer, "number", Some(value), *span)
+      }
+      ArithmeticExpr::Variable { name, span } => {
+        serialize_kind_value(serializer, "variable", Some(name), *span)
+      }
+      ArithmeticExpr::Parentheses { expr, span } => {
+        serialize_kind_value(serializer, "parentheses", Some(expr), *span)
+      }
+      ArithmeticExpr::Unary { op, operand, span } => serialize_kind_value(
+        serializer,
+        "unary",
+        Some(&UnaryValue { op, operand }),
+        *span,
+      ),
+      ArithmeticExpr::Binary {
+        left,
+        op,
+        right,
+        span,
+      } => serialize_kind_value(
+        serializer,
+        "binary",
+        Some(&BinaryValue { left, op, right }),
+        *span,
+      ),
+    }
+  }
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum UnaryArithmeticOp {
+  /// +
+  Plus,
+  /// -
+  Minus,
+  /// !
+  Not,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryArithmeticOp {
+  /// +
+  Add,
+  /// -
+  Subtract,
+  /// *
+  Multiply,
+  /// /
+  Divide,
+  /// %
+  Modulo,
+  /// <
+  LessThan,
+  /// <=
+  LessThanOrEqual,
+  /// >
+  GreaterThan,
+  /// >=
+  GreaterThanOrEqual,
+  /// ==
+  Equal,
+  /// !=
+  NotEqual,
+  /// &&
+  And,
+  /// ||
+  Or,
+}
+
+impl BinaryArithmeticOp {
+  /// How tightly the operator binds, where operators with a higher
+  /// precedence are applied first.
+  pub(crate) fn precedence(self) -> usize {
+    use BinaryArithmeticOp::*;
+    match self {
+      Or => 0,
+      And => 1,
+      Equal | NotEqual => 2,
+      LessThan | LessThanOrEqual | GreaterThan | GreaterThanOrEqual => 3,
+      Add | Subtract => 4,
+      Multiply | Divide | Modulo => 5,
+    }
+  }
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParameterExpansion {
+  /// Name of the variable or special parameter (ex. `MY_VAR` or `#`)
+  pub name: String,
+  /// Operator applied to the value, which is `None` for `${MY_VAR}`
+  pub op: Option<ParameterExpansionOp>,
+  pub span: Span,
+}
+
+/// An operator in a braced parameter expansion.
+///
+/// For the operators that check if the variable is set, `check_null`
+/// is true for the form with a colon (ex. `:-`), which also treats an
+/// empty value as unset.
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(
+  feature = "serialization",
+  serde(rename_all = "camelCase", tag = "kind")
+)]
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ParameterExpansionOp {
+  /// `${#VAR}` - Length of the value
+  Length,
+  /// `${VAR:-word}` or `${VAR-word}` - Uses the word when unset
+  #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+  UseDefault {
+    check_null: bool,
+    word: Vec<StringPart>,
+  },
+  /// `${VAR:=word}` or `${VAR=word}` - Assigns the word when unset
+  #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+  AssignDefault {
+    check_null: bool,
+    word: Vec<StringPart>,
+  },
+  /// `${VAR:+word}` or `${VAR+word}` - Uses the word when set
+  #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+  UseAlternative {
+    check_null: bool,
+    word: Vec<StringPart>,
+  },
+  /// `${VAR:?word}` or `${VAR?word}` - Errors with the word when unset
+  #[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+  ErrorIfUnset {
+    check_null: bool,
+    word: Vec<StringPart>,
+  },
+  /// `${VAR#pattern}` or `${VAR##pattern}` - Removes the shortest or
+  /// longest matching prefix
+  RemovePrefix {
+    longest: bool,
+    pattern: Vec<StringPart>,
+  },
+  /// `${VAR%pattern}` or `${VAR%%pattern}` - Removes the shortest or
+  /// longest matching suffix
+  RemoveSuffix {
+    longest: bool,
+    pattern: Vec<StringPart>,
+  },
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -280,7 +760,45 @@ pub enum RedirectFd {
 pub struct Redirect {
   pub maybe_fd: Option<RedirectFd>,
   pub op: RedirectOp,
-  pub io_file: StringOrWord,
+  pub io_file: IoFile,
+  pub span: Span,
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(
+  feature = "serialization",
+  serde(rename_all = "camelCase", tag = "kind", content = "value")
+)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IoFile {
+  /// Path of the file (ex. `file.txt`)
+  Word(StringOrWord),
+  /// File descriptor to duplicate (ex. `&2`)
+  Fd(u32),
+  /// Closes the file descriptor (ex. `&-`)
+  Close,
+  /// Here-document (ex. `<<EOF`)
+  HereDoc(HereDoc),
+}
+
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HereDoc {
+  /// Line that ends the here-document with any quotes removed (ex. `EOF`)
+  pub delimiter: String,
+  /// If the delimiter was quoted, which disables expansion in the body.
+  pub is_quoted: bool,
+  /// If leading tabs are stripped from the lines (`<<-`).
+  pub strip_tabs: bool,
+  /// Text of the here-document with leading tabs already stripped.
+  ///
+  /// The body follows the end of the line, so this is `None` until it's
+  /// parsed. It's only `None` in a list from `parse_with_recovery` when
+  /// the line has an error.
+  pub body: Option<Vec<StringPart>>,
+  /// Span of the body and the line with the closing delimiter.
+  pub span: Span,
 }
 
 #[cfg_attr(feature = "serialization", derive(serde::Serialize))]
@@ -291,117 +809,520 @@ pub enum RedirectOp {
   Redirect,
   /// >>
   Append,
+  /// <
+  Input,
+  /// << or <<-
+  HereDoc,
+  /// <<<
+  HereString,
 }
 
-fn error_for_failure(e: ParseErrorFailure) -> Result<SequentialList> {
-  bail!(
-    "{}\n  {}\n  ~",
-    e.message,
-    // truncate the output to prevent wrapping in the console
-    e.input.chars().take(60).collect::<String>()
-  )
-}
+/// Updates every span within a node.
+///
+/// While parsing, the start and end of a span are stored as the length
+/// of the input remaining at those positions because the parsers only
+/// see the rest of the input. This is used to convert them to byte
+/// offsets once the length of the entire input is known.
+struct SpanMapper<F: Fn(Span) -> Span>(F);
 
-pub fn parse(input: &str) -> Result<SequentialList> {
-  match parse_sequential_list(input) {
-    Ok((input, expr)) => {
-      if input.trim().is_empty() {
-        if expr.items.is_empty() {
-          bail!("Empty command.")
-        } else {
-          Ok(expr)
-        }
-      } else {
-        error_for_failure(fail_for_trailing_input(input))
-      }
-    }
-    Err(ParseError::Backtrace) => {
-      error_for_failure(fail_for_trailing_input(input))
-    }
-    Err(ParseError::Failure(e)) => error_for_failure(e),
+impl<F: Fn(Span) -> Span> VisitMut for SpanMapper<F> {
+  fn visit_span_mut(&mut self, span: &mut Span) {
+    *span = (self.0)(*span);
   }
 }
 
-fn parse_sequential_list(input: &str) -> ParseResult<SequentialList> {
-  let (input, items) = separated_list(
-    terminated(parse_sequential_list_item, skip_whitespace),
-    terminated(
-      skip_whitespace,
-      or(
-        map(parse_sequential_list_op, |_| ()),
-        map(parse_async_list_op, |_| ()),
-      ),
-    ),
-  )(input)?;
-  Ok((input, SequentialList { items }))
-}
-
-fn parse_sequential_list_item(input: &str) -> ParseResult<SequentialListItem> {
-  let (input, sequence) = parse_sequence(input)?;
-  Ok((
-    input,
-    SequentialListItem {
-      is_async: maybe(parse_async_list_op)(input)?.1.is_some(),
-      sequence,
-    },
-  ))
+/// Gets the span of the text consumed between two parse inputs,
+/// which is stored as the lengths of the remaining input.
+fn span_between(start: &str, end: &str) -> Span {
+  Span::new(start.len(), end.len())
 }
 
-fn parse_sequence(input: &str) -> ParseResult<Sequence> {
-  let (input, current) = terminated(
-    or(
-      parse_shell_var_command,
-      map(parse_pipeline, Sequence::Pipeline),
-    ),
-    skip_whitespace,
-  )(input)?;
-
-  Ok(match parse_boolean_list_op(input) {
-    Ok((input, op)) => {
-      let (input, next_sequence) = assert_exists(
-        &parse_sequence,
-        "Expected command following boolean operator.",
-      )(input)?;
-      (
-        input,
-        Sequence::BooleanList(Box::new(BooleanList {
-          current,
-          op,
-          next: next_sequence,
-        })),
-      )
-    }
-    Err(ParseError::Backtrace) => (input, current),
-    Err(err) => return Err(err),
-  })
+/// Combines the spans of the first and last of a series of nodes.
+fn join_spans(first: Span, last: Span) -> Span {
+  Span::new(first.start, last.end)
 }
 
-fn parse_shell_var_command(input: &str) -> ParseResult<Sequence> {
-  let env_vars_input = input;
-  let (input, mut env_vars) = if_not_empty(parse_env_vars)(input)?;
-  let (input, args) = parse_command_args(input)?;
-  if !args.is_empty() {
-    return ParseError::backtrace();
-  }
-  if env_vars.len() > 1 {
-    ParseError::fail(env_vars_input, "Cannot set multiple environment variables when there is no following command.")
-  } else {
-    ParseResult::Ok((input, Sequence::ShellVar(env_vars.remove(0))))
+/// Parses with the provided combinator, also returning the span
+/// of the parsed text.
+fn with_span<'a, T>(
+  combinator: impl Fn(&'a str) -> ParseResult<'a, T>,
+) -> impl Fn(&'a str) -> ParseResult<'a, (T, Span)> {
+  move |input| {
+    let (rest, value) = combinator(input)?;
+    Ok((rest, (value, span_between(input, rest))))
   }
 }
 
-/// Parses a pipeline, which is a sequence of one or more commands.
-/// https://www.gnu.org/software/bash/manual/html_node/Pipelines.html
-fn parse_pipeline(input: &str) -> ParseResult<Pipeline> {
-  let (input, maybe_negated) = maybe(parse_negated_op)(input)?;
-  let (input, inner) = parse_pipeline_inner(input)?;
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+  /// The script can't be run.
+  Error,
+  /// The script can be run, but likely has a mistake.
+  Warning,
+}
 
-  let pipeline = Pipeline {
-    negated: maybe_negated.is_some(),
-    inner,
-  };
+/// A problem found while parsing.
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDiagnostic {
+  pub kind: ParseErrorKind,
+  pub message: String,
+  /// Byte offset in the input where the problem was found.
+  pub offset: usize,
+  /// Byte length of the text at the offset that has the problem.
+  pub length: usize,
+  pub severity: DiagnosticSeverity,
+}
 
-  Ok((input, pipeline))
+/// Result of parsing with `parse_with_recovery`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveredParse {
+  /// The commands that could be parsed.
+  pub list: SequentialList,
+  /// Problems found in the input in the order they appear.
+  pub diagnostics: Vec<ParseDiagnostic>,
+}
+
+/// Kind of problem that caused parsing to fail.
+#[cfg_attr(feature = "serialization", derive(serde::Serialize))]
+#[cfg_attr(feature = "serialization", serde(rename_all = "camelCase"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+  /// The input has no commands.
+  EmptyCommand,
+  /// A character that can't appear at this position (ex. `&& echo`).
+  UnexpectedChar,
+  /// A reserved word that can't appear at this position (ex. `fi`).
+  UnexpectedReservedWord,
+  /// A reserved word that isn't supported (ex. `select` or `[[`).
+  UnsupportedReservedWord,
+  /// A quoted string or back tick command substitution is never closed.
+  UnclosedQuote,
+  /// A compound command, expansion, or here-document is never closed.
+  Unclosed,
+  /// Something required is missing (ex. a command after `&&`).
+  Expected,
+  /// Valid shell syntax that isn't supported.
+  UnsupportedFeature,
+  /// A number in an arithmetic expansion is invalid.
+  InvalidNumber,
+}
+
+thread_local! {
+  /// Kind of the most recent failure created by `new_failure`.
+  ///
+  /// monch's `ParseErrorFailure` only has a message, so the kind is
+  /// carried alongside it and read once parsing has failed. A failure that
+  /// is given more context (ex. by `assert_exists`) keeps the kind of the
+  /// failure it was created from because it doesn't create a new kind.
+  static FAILURE_KIND: Cell<ParseErrorKind> =
+    const { Cell::new(ParseErrorKind::Expected) };
+}
+
+/// Creates a failure with the kind of problem.
+///
+/// Every failure is created by this function (or `fail`) so that the
+/// kind of the failure that stops parsing is always the one that was
+/// set for it.
+fn new_failure(
+  input: &str,
+  kind: ParseErrorKind,
+  message: impl AsRef<str>,
+) -> ParseErrorFailure {
+  FAILURE_KIND.with(|failure_kind| failure_kind.set(kind));
+  ParseErrorFailure::new(input, message)
+}
+
+/// Gets the kind of the failure that stopped parsing.
+fn failure_kind() -> ParseErrorKind {
+  FAILURE_KIND.with(Cell::get)
+}
+
+fn fail<'a, O>(
+  input: &'a str,
+  kind: ParseErrorKind,
+  message: impl AsRef<str>,
+) -> ParseResult<'a, O> {
+  Err(monch::ParseError::Failure(new_failure(
+    input, kind, message,
+  )))
+}
+
+/// Fails with the kind and message when the combinator backtracks.
+///
+/// A failure in the combinator keeps its own kind and is given
+/// the message as context.
+fn assert_exists<'a, O>(
+  kind: ParseErrorKind,
+  combinator: impl Fn(&'a str) -> ParseResult<'a, O>,
+  message: &'static str,
+) -> impl Fn(&'a str) -> ParseResult<'a, O> {
+  move |input| match combinator(input) {
+    Err(monch::ParseError::Backtrace) => fail(input, kind, message),
+    Err(monch::ParseError::Failure(err)) => monch::ParseError::fail(
+      err.input,
+      format!("{}\n\n{}", message, err.message),
+    ),
+    result => result,
+  }
+}
+
+/// Error returned by `parse` for the first problem in the input.
+///
+/// This is a struct with a `kind` rather than an enum with a variant per
+/// kind because every kind has the same message, offset, and snippet, so
+/// callers match on `kind` to tell the problems apart.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+  pub kind: ParseErrorKind,
+  pub message: String,
+  /// Byte offset in the input where the problem was found.
+  pub offset: usize,
+  /// Text of the input at the offset, truncated to prevent
+  /// wrapping in the console.
+  snippet: String,
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if self.kind == ParseErrorKind::EmptyCommand {
+      write!(f, "{}", self.message)
+    } else {
+      write!(f, "{}\n  {}\n  ~", self.message, self.snippet)
+    }
+  }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(input: &str) -> Result<SequentialList, ParseError> {
+  let result = parse_with_recovery(input);
+  // an empty command is only a warning when recovering, but can't be run
+  let error = result.diagnostics.into_iter().find(|diagnostic| {
+    diagnostic.severity == DiagnosticSeverity::Error
+      || diagnostic.kind == ParseErrorKind::EmptyCommand
+  });
+  match error {
+    Some(error) => Err(ParseError {
+      kind: error.kind,
+      snippet: input[error.offset..].chars().take(60).collect(),
+      message: error.message,
+      offset: error.offset,
+    }),
+    None => Ok(result.list),
+  }
+}
+
+/// Parses the input, continuing on the next line after any
+/// problems so that all of them are found.
+///
+/// The returned list contains all the commands that could be parsed.
+/// An input without any commands is reported as a warning.
+pub fn parse_with_recovery(input: &str) -> RecoveredParse {
+  let mut items = Vec::new();
+  let mut diagnostics = Vec::new();
+  let mut remaining = input;
+  // index of the first item on the current line
+  let mut line_start = 0;
+  loop {
+    let failure = match parse_sequential_list_next_item(
+      remaining, &mut items, line_start,
+    ) {
+      Ok((rest, Some(is_newline))) => {
+        if is_newline {
+          line_start = items.len();
+        }
+        remaining = rest;
+        continue;
+      }
+      Ok((rest, None)) if has_pending_here_docs(&items[line_start..]) => {
+        new_failure(
+          rest,
+          ParseErrorKind::Expected,
+          "Expected end of line following here-document redirect.",
+        )
+      }
+      Ok((_, None)) => break,
+      Err(monch::ParseError::Backtrace) => fail_for_trailing_input(remaining),
+      Err(monch::ParseError::Failure(failure)) => failure,
+    };
+    // the here-documents on the line are never parsed
+    line_start = items.len();
+    let offset = failure_offset(input, &failure);
+    let failure_text = &input[offset..];
+    diagnostics.push(ParseDiagnostic {
+      kind: failure_kind(),
+      message: failure.message,
+      offset,
+      length: match or(
+        parse_any_reserved_word,
+        parse_unsupported_reserved_word,
+      )(failure_text)
+      {
+        Ok((_, word)) => word.len(),
+        Err(_) => failure_text.chars().next().map_or(0, char::len_utf8),
+      },
+      severity: DiagnosticSeverity::Error,
+    });
+    remaining = match failure_text.find('\n') {
+      Some(index) => &failure_text[index + 1..],
+      None => "",
+    };
+  }
+
+  // the script runs, but it's likely a mistake that it does nothing
+  if items.is_empty() && diagnostics.is_empty() {
+    diagnostics.push(ParseDiagnostic {
+      kind: ParseErrorKind::EmptyCommand,
+      message: "Empty command.".to_string(),
+      offset: 0,
+      length: 0,
+      severity: DiagnosticSeverity::Warning,
+    });
+  }
+
+  let span = match (items.first(), items.last()) {
+    (Some(first), Some(last)) => join_spans(first.span, last.span),
+    _ => span_between(input, input),
+  };
+  let mut list = SequentialList { items, span };
+  let input_len = input.len();
+  SpanMapper(|span: Span| {
+    Span::new(input_len - span.start, input_len - span.end)
+  })
+  .visit_sequential_list_mut(&mut list);
+  RecoveredParse { list, diagnostics }
+}
+
+/// Parses the next item of a top level sequential list along with its
+/// separator, adding it to the items and returning if the separator is
+/// a newline or `None` at the end of the input.
+fn parse_sequential_list_next_item<'a>(
+  input: &'a str,
+  items: &mut Vec<SequentialListItem>,
+  line_start: usize,
+) -> ParseResult<'a, Option<bool>> {
+  let (input, _) = skip_whitespace(input)?;
+  if input.is_empty() {
+    return Ok((input, None));
+  }
+  let (input, item) =
+    terminated(parse_sequential_list_item, skip_horizontal_whitespace)(input)?;
+  items.push(item);
+  let result =
+    match parse_sequential_list_separator(input, &mut items[line_start..]) {
+      Ok((input, is_newline)) => Ok((input, Some(is_newline))),
+      Err(monch::ParseError::Backtrace) if input.trim().is_empty() => {
+        Ok((input, Some(false)))
+      }
+      Err(monch::ParseError::Backtrace) => {
+        Err(monch::ParseError::Failure(fail_for_trailing_input(input)))
+      }
+      Err(err) => Err(err),
+    };
+  if result.is_err() {
+    items.pop();
+  }
+  result
+}
+
+/// Gets the byte offset in the input where a parser failed.
+///
+/// Some text is parsed on its own (ex. a line of a here-document), so
+/// this uses the position in memory rather than the remaining length.
+fn failure_offset(input: &str, failure: &ParseErrorFailure) -> usize {
+  let offset =
+    (failure.input.as_ptr() as usize).saturating_sub(input.as_ptr() as usize);
+  offset.min(input.len())
+}
+
+fn parse_sequential_list(input: &str) -> ParseResult<SequentialList> {
+  let (mut input, _) = skip_whitespace(input)?;
+  let start_input = input;
+  let mut items = Vec::new();
+  // index of the first item on the current line
+  let mut line_start = 0;
+  while !input.is_empty() {
+    let (rest, item) =
+      match terminated(parse_sequential_list_item, skip_horizontal_whitespace)(
+        input,
+      ) {
+        Ok(result) => result,
+        Err(monch::ParseError::Backtrace) => break,
+        Err(err) => return Err(err),
+      };
+    items.push(item);
+    input = rest;
+    let (rest, is_newline) =
+      match parse_sequential_list_separator(input, &mut items[line_start..]) {
+        Ok(result) => result,
+        Err(monch::ParseError::Backtrace) => break,
+        Err(err) => return Err(err),
+      };
+    if is_newline {
+      line_start = items.len();
+    }
+    input = rest;
+  }
+  let span = match (items.first(), items.last()) {
+    (Some(first), Some(last)) => join_spans(first.span, last.span),
+    _ => span_between(start_input, start_input),
+  };
+  Ok((input, SequentialList { items, span }))
+}
+
+/// Parses the separator following the last of the items on
+/// the current line, returning if it's a newline.
+///
+/// The bodies of the here-documents in the items on the line follow
+/// the newline in order (ex. `cat <<A; cat <<B`).
+fn parse_sequential_list_separator<'a>(
+  input: &'a str,
+  line_items: &mut [SequentialListItem],
+) -> ParseResult<'a, bool> {
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  let (input, maybe_op) = maybe(or(
+    // `;;` is the case item terminator
+    terminated(tag(";"), check_not(ch(';'))),
+    terminated(tag("&"), check_not(one_of("|&"))),
+  ))(input)?;
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  match parse_newline(input) {
+    Ok((input, _)) => {
+      let (input, _) = parse_here_doc_bodies(input, line_items)?;
+      let (input, _) = skip_whitespace(input)?;
+      Ok((input, true))
+    }
+    Err(_) if maybe_op.is_some() => Ok((input, false)),
+    Err(_) => monch::ParseError::backtrace(),
+  }
+}
+
+fn parse_sequential_list_item(input: &str) -> ParseResult<SequentialListItem> {
+  let (input, sequence) = parse_sequence(input)?;
+  let is_async = maybe(parse_async_list_op)(input)?.1.is_some();
+  let mut span = sequence.span();
+  if is_async {
+    // include the `&`, which is consumed as the list's separator
+    span.end = input.len() - 1;
+  }
+  Ok((
+    input,
+    SequentialListItem {
+      is_async,
+      sequence,
+      span,
+    },
+  ))
+}
+
+fn parse_sequence(input: &str) -> ParseResult<Sequence> {
+  let (input, current) = terminated(
+    or3(
+      map(parse_function_definition, Sequence::FunctionDefinition),
+      parse_shell_var_command,
+      map(parse_pipeline, Sequence::Pipeline),
+    ),
+    skip_horizontal_whitespace,
+  )(input)?;
+
+  Ok(match parse_boolean_list_op(input) {
+    Ok((input, op)) => {
+      let (input, next_sequence) = assert_exists(
+        ParseErrorKind::Expected,
+        &parse_sequence,
+        "Expected command following boolean operator.",
+      )(input)?;
+      (
+        input,
+        Sequence::BooleanList(Box::new(BooleanList {
+          span: join_spans(current.span(), next_sequence.span()),
+          current,
+          op,
+          next: next_sequence,
+        })),
+      )
+    }
+    Err(monch::ParseError::Backtrace) => (input, current),
+    Err(err) => return Err(err),
+  })
+}
+
+fn parse_shell_var_command(input: &str) -> ParseResult<Sequence> {
+  let env_vars_input = input;
+  let (input, mut env_vars) = if_not_empty(parse_env_vars)(input)?;
+  let (input, args) = parse_command_args(input)?;
+  if !args.is_empty() {
+    return monch::ParseError::backtrace();
+  }
+  if env_vars.len() > 1 {
+    fail(
+      env_vars_input,
+      ParseErrorKind::UnsupportedFeature,
+      "Cannot set multiple environment variables when there is no following command.",
+    )
+  } else {
+    ParseResult::Ok((input, Sequence::ShellVar(env_vars.remove(0))))
+  }
+}
+
+fn parse_function_definition(input: &str) -> ParseResult<FunctionDefinition> {
+  let original_input = input;
+  let (input, _) = check_not(parse_any_reserved_word)(input)?;
+  let (input, name) = terminated(parse_env_var_name, skip_whitespace)(input)?;
+  let (input, _) = terminated(ch('('), skip_whitespace)(input)?;
+  let (input, _) = terminated(ch(')'), skip_whitespace)(input)?;
+  let (input, body) = or(parse_brace_group, |input| {
+    fail(
+      input,
+      ParseErrorKind::Expected,
+      "Expected '{' to begin function body.",
+    )
+  })(input)?;
+  Ok((
+    input,
+    FunctionDefinition {
+      name: name.to_string(),
+      body,
+      span: span_between(original_input, input),
+    },
+  ))
+}
+
+/// Parses `{ list; }`
+fn parse_brace_group(input: &str) -> ParseResult<SequentialList> {
+  let original_input = input;
+  let (input, _) = terminated(ch('{'), whitespace)(input)?;
+  let (input, list) =
+    parse_compound_list(input, "Expected command following '{'.")?;
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      ch('}'),
+      "Expected '}' to close brace group.",
+    ),
+  )(input)?;
+  Ok((input, list))
+}
+
+/// Parses a pipeline, which is a sequence of one or more commands.
+/// https://www.gnu.org/software/bash/manual/html_node/Pipelines.html
+fn parse_pipeline(input: &str) -> ParseResult<Pipeline> {
+  let original_input = input;
+  let (input, maybe_negated) = maybe(parse_negated_op)(input)?;
+  let (input, inner) = parse_pipeline_inner(input)?;
+
+  let pipeline = Pipeline {
+    negated: maybe_negated.is_some(),
+    span: Span::new(original_input.len(), inner.span().end),
+    inner,
+  };
+
+  Ok((input, pipeline))
 }
 
 fn parse_pipeline_inner(input: &str) -> ParseResult<PipelineInner> {
@@ -411,13 +1332,15 @@ fn parse_pipeline_inner(input: &str) -> ParseResult<PipelineInner> {
   let (input, inner) = match parse_pipe_sequence_op(input) {
     Ok((input, op)) => {
       let (input, next_inner) = assert_exists(
+        ParseErrorKind::Expected,
         &parse_pipeline_inner,
         "Expected command following pipeline operator.",
       )(input)?;
 
-      if command.redirect.is_some() {
-        return ParseError::fail(
+      if !command.redirects.is_empty() {
+        return fail(
           original_input,
+          ParseErrorKind::UnsupportedFeature,
           "Redirects in pipe sequence commands are currently not supported.",
         );
       }
@@ -425,13 +1348,16 @@ fn parse_pipeline_inner(input: &str) -> ParseResult<PipelineInner> {
       (
         input,
         PipelineInner::PipeSequence(Box::new(PipeSequence {
+          span: join_spans(command.span, next_inner.span()),
           current: command,
           op,
           next: next_inner,
         })),
       )
     }
-    Err(ParseError::Backtrace) => (input, PipelineInner::Command(command)),
+    Err(monch::ParseError::Backtrace) => {
+      (input, PipelineInner::Command(command))
+    }
     Err(err) => return Err(err),
   };
 
@@ -439,37 +1365,300 @@ fn parse_pipeline_inner(input: &str) -> ParseResult<PipelineInner> {
 }
 
 fn parse_command(input: &str) -> ParseResult<Command> {
-  let (input, inner) = terminated(
-    or(
-      map(parse_subshell, |l| CommandInner::Subshell(Box::new(l))),
-      map(parse_simple_command, CommandInner::Simple),
-    ),
-    skip_whitespace,
+  let original_input = input;
+  let (input, inner) = or7(
+    map(parse_subshell, |l| CommandInner::Subshell(Box::new(l))),
+    map(parse_brace_group, |l| CommandInner::Group(Box::new(l))),
+    map(parse_if_clause, CommandInner::If),
+    map(parse_for_clause, CommandInner::For),
+    map(parse_while_clause, CommandInner::While),
+    map(parse_case_clause, CommandInner::Case),
+    map(parse_simple_command, CommandInner::Simple),
   )(input)?;
+  let mut span = match &inner {
+    // the whitespace following the arguments was already skipped
+    CommandInner::Simple(command) => command.span,
+    _ => span_between(original_input, input),
+  };
+  let (input, _) = skip_horizontal_whitespace(input)?;
 
-  let before_redirects_input = input;
-  let (input, mut redirects) =
-    many0(terminated(parse_redirect, skip_whitespace))(input)?;
-
-  if redirects.len() > 1 {
-    return ParseError::fail(
-      before_redirects_input,
-      "Multiple redirects are currently not supported.",
-    );
+  // here-document bodies are parsed after the end of the line
+  let (input, redirects) =
+    many0(terminated(parse_redirect, skip_horizontal_whitespace))(input)?;
+  if let Some(redirect) = redirects.last() {
+    span.end = redirect.span.end;
   }
 
   let command = Command {
-    redirect: redirects.pop(),
+    redirects,
     inner,
+    span,
   };
 
   Ok((input, command))
 }
 
 fn parse_simple_command(input: &str) -> ParseResult<SimpleCommand> {
+  // reserved words are only special when they're the first word of a command
+  let (input, _) = check_not(parse_any_reserved_word)(input)?;
+  if parse_unsupported_reserved_word(input).is_ok() {
+    return fail(
+      input,
+      ParseErrorKind::UnsupportedReservedWord,
+      "Unsupported reserved word.",
+    );
+  }
+  let original_input = input;
   let (input, env_vars) = parse_env_vars(input)?;
   let (input, args) = if_not_empty(parse_command_args)(input)?;
-  ParseResult::Ok((input, SimpleCommand { env_vars, args }))
+  // there's always at least one argument
+  let end = args.last().unwrap().span().end;
+  ParseResult::Ok((
+    input,
+    SimpleCommand {
+      env_vars,
+      args,
+      span: Span::new(original_input.len(), end),
+    },
+  ))
+}
+
+fn parse_if_clause(input: &str) -> ParseResult<IfClause> {
+  let original_input = input;
+  let (input, _) = parse_reserved_word("if")(input)?;
+  let (input, mut if_clause) = parse_if_clause_body(input)?;
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      parse_reserved_word("fi"),
+      "Expected 'fi' to close if statement.",
+    ),
+  )(input)?;
+  if_clause.span = span_between(original_input, input);
+  Ok((input, if_clause))
+}
+
+/// Parses everything in an `if` statement following the `if` or `elif`.
+///
+/// The span starts at the condition and ends at the last command.
+fn parse_if_clause_body(input: &str) -> ParseResult<IfClause> {
+  let (input, condition) =
+    parse_compound_list(input, "Expected command following 'if' or 'elif'.")?;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    parse_reserved_word("then"),
+    "Expected 'then' following if condition.",
+  )(input)?;
+  let (input, body) =
+    parse_compound_list(input, "Expected command following 'then'.")?;
+  let (input, else_part) = maybe(or(
+    map(
+      with_span(preceded(parse_reserved_word("elif"), parse_if_clause_body)),
+      |(mut if_clause, span)| {
+        if_clause.span.start = span.start;
+        ElsePart::Elif(Box::new(if_clause))
+      },
+    ),
+    map(
+      preceded(parse_reserved_word("else"), |input| {
+        parse_compound_list(input, "Expected command following 'else'.")
+      }),
+      ElsePart::Else,
+    ),
+  ))(input)?;
+  let last_span = match &else_part {
+    Some(ElsePart::Elif(if_clause)) => if_clause.span,
+    Some(ElsePart::Else(list)) => list.span,
+    None => body.span,
+  };
+  Ok((
+    input,
+    IfClause {
+      span: join_spans(condition.span, last_span),
+      condition,
+      body,
+      else_part,
+    },
+  ))
+}
+
+fn parse_for_clause(input: &str) -> ParseResult<ForClause> {
+  let original_input = input;
+  let (input, _) = parse_reserved_word("for")(input)?;
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  let (input, var_name) = terminated(
+    assert_exists(
+      ParseErrorKind::Expected,
+      parse_env_var_name,
+      "Expected variable name following 'for'.",
+    ),
+    skip_whitespace,
+  )(input)?;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    parse_reserved_word("in"),
+    "Expected 'in' following for loop variable name.",
+  )(input)?;
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  let (input, wordlist) = parse_command_args(input)?;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    or(parse_sequential_list_op, parse_newline_list_op),
+    "Expected ';' or newline following for loop words.",
+  )(input)?;
+  let (input, body) = parse_do_group(input)?;
+  Ok((
+    input,
+    ForClause {
+      var_name: var_name.to_string(),
+      wordlist,
+      body,
+      span: span_between(original_input, input),
+    },
+  ))
+}
+
+fn parse_while_clause(input: &str) -> ParseResult<WhileClause> {
+  let original_input = input;
+  let (input, is_until) = or(
+    map(parse_reserved_word("while"), |_| false),
+    map(parse_reserved_word("until"), |_| true),
+  )(input)?;
+  let (input, condition) = parse_compound_list(
+    input,
+    "Expected command following 'while' or 'until'.",
+  )?;
+  let (input, body) = parse_do_group(input)?;
+  Ok((
+    input,
+    WhileClause {
+      is_until,
+      condition,
+      body,
+      span: span_between(original_input, input),
+    },
+  ))
+}
+
+fn parse_case_clause(input: &str) -> ParseResult<CaseClause> {
+  let original_input = input;
+  let (input, _) = parse_reserved_word("case")(input)?;
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  let (input, word) = terminated(
+    assert_exists(
+      ParseErrorKind::Expected,
+      parse_shell_arg,
+      "Expected word following 'case'.",
+    ),
+    assert_whitespace_or_end_and_skip,
+  )(input)?;
+  let (mut input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    parse_reserved_word("in"),
+    "Expected 'in' following case word.",
+  )(input)?;
+  let mut items = Vec::new();
+  loop {
+    input = skip_whitespace(input)?.0;
+    if let Ok((input, _)) = parse_reserved_word("esac")(input) {
+      let span = span_between(original_input, input);
+      return Ok((input, CaseClause { word, items, span }));
+    }
+    let (item_input, item) = parse_case_item(input)?;
+    items.push(item);
+    // the terminator may be omitted on the last item
+    input = match terminated(tag(";;"), skip_whitespace)(item_input) {
+      Ok((input, _)) => input,
+      Err(monch::ParseError::Backtrace) => {
+        input = item_input;
+        break;
+      }
+      Err(err) => return Err(err),
+    };
+  }
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      parse_reserved_word("esac"),
+      "Expected 'esac' to close case statement.",
+    ),
+  )(input)?;
+  let span = span_between(original_input, input);
+  Ok((input, CaseClause { word, items, span }))
+}
+
+/// Parses `[(]pattern[|pattern]...) list`
+fn parse_case_item(input: &str) -> ParseResult<CaseItem> {
+  let original_input = input;
+  let (input, _) = maybe(terminated(ch('('), skip_whitespace))(input)?;
+  let (input, patterns) = assert_exists(
+    ParseErrorKind::Expected,
+    if_true(
+      separated_list(
+        terminated(parse_pattern, skip_whitespace),
+        terminated(ch('|'), skip_whitespace),
+      ),
+      |patterns| !patterns.is_empty(),
+    ),
+    "Expected pattern in case item.",
+  )(input)?;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    ch(')'),
+    "Expected ')' following case item pattern.",
+  )(input)?;
+  let mut span = span_between(original_input, input);
+  let (input, _) = skip_whitespace(input)?;
+  let (input, body) =
+    terminated(parse_sequential_list, skip_whitespace)(input)?;
+  if !body.items.is_empty() {
+    span.end = body.span.end;
+  }
+  Ok((
+    input,
+    CaseItem {
+      patterns,
+      body,
+      span,
+    },
+  ))
+}
+
+/// Parses `do list; done`
+fn parse_do_group(input: &str) -> ParseResult<SequentialList> {
+  let original_input = input;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    parse_reserved_word("do"),
+    "Expected 'do' for loop body.",
+  )(input)?;
+  let (input, body) =
+    parse_compound_list(input, "Expected command following 'do'.")?;
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      parse_reserved_word("done"),
+      "Expected 'done' to close loop body.",
+    ),
+  )(input)?;
+  Ok((input, body))
+}
+
+/// Parses the non-empty list of commands found within a compound command.
+fn parse_compound_list<'a>(
+  input: &'a str,
+  empty_message: &'static str,
+) -> ParseResult<'a, SequentialList> {
+  let (input, list) = assert_exists(
+    ParseErrorKind::Expected,
+    if_true(parse_sequential_list, |list| !list.items.is_empty()),
+    empty_message,
+  )(input)?;
+  let (input, _) = skip_whitespace(input)?;
+  Ok((input, list))
 }
 
 fn parse_command_args(input: &str) -> ParseResult<Vec<StringOrWord>> {
@@ -487,7 +1676,7 @@ fn parse_command_args(input: &str) -> ParseResult<Vec<StringOrWord>> {
 fn parse_shell_arg(input: &str) -> ParseResult<StringOrWord> {
   let (input, value) = parse_string_or_word(input)?;
   if value.parts().is_empty() {
-    ParseError::backtrace()
+    monch::ParseError::backtrace()
   } else {
     Ok((input, value))
   }
@@ -512,7 +1701,16 @@ fn parse_boolean_list_op(input: &str) -> ParseResult<BooleanListOperator> {
 }
 
 fn parse_sequential_list_op(input: &str) -> ParseResult<&str> {
-  terminated(tag(";"), skip_whitespace)(input)
+  // `;;` is the case item terminator
+  terminated(tag(";"), terminated(check_not(ch(';')), skip_whitespace))(input)
+}
+
+fn parse_newline_list_op(input: &str) -> ParseResult<&str> {
+  terminated(parse_newline, skip_whitespace)(input)
+}
+
+fn parse_newline(input: &str) -> ParseResult<&str> {
+  or(tag("\r\n"), tag("\n"))(input)
 }
 
 fn parse_async_list_op(input: &str) -> ParseResult<&str> {
@@ -550,18 +1748,57 @@ fn parse_pipe_sequence_op(input: &str) -> ParseResult<PipeSequenceOperator> {
 
 fn parse_redirect(input: &str) -> ParseResult<Redirect> {
   // https://pubs.opengroup.org/onlinepubs/009604499/utilities/xcu_chap02.html#tag_02_07
+  let original_input = input;
   let (input, maybe_fd) = maybe(parse_u32)(input)?;
   let (input, maybe_ampersand) = if maybe_fd.is_none() {
     maybe(ch('&'))(input)?
   } else {
     (input, None)
   };
-  let (input, op) = or(
+  let (input, op) = or3(
     map(tag(">>"), |_| RedirectOp::Append),
-    map(or(tag(">"), tag(">|")), |_| RedirectOp::Redirect),
+    map(or(tag(">|"), tag(">")), |_| RedirectOp::Redirect),
+    if_true(
+      or3(
+        map(tag("<<<"), |_| RedirectOp::HereString),
+        map(tag("<<"), |_| RedirectOp::HereDoc),
+        map(ch('<'), |_| RedirectOp::Input),
+      ),
+      |_| maybe_ampersand.is_none(),
+    ),
   )(input)?;
-  let (input, _) = skip_whitespace(input)?;
-  let (input, io_file) = parse_string_or_word(input)?;
+  if op == RedirectOp::HereDoc {
+    let (input, here_doc) = parse_here_doc_redirect(input)?;
+    return Ok((
+      input,
+      Redirect {
+        maybe_fd: maybe_fd.map(RedirectFd::Fd),
+        op,
+        io_file: IoFile::HereDoc(here_doc),
+        span: span_between(original_input, input),
+      },
+    ));
+  }
+  let (input, io_file) =
+    if maybe_ampersand.is_none() && op != RedirectOp::HereString {
+      maybe(preceded(
+        ch('&'),
+        assert_exists(
+          ParseErrorKind::Expected,
+          or(map(parse_u32, IoFile::Fd), map(ch('-'), |_| IoFile::Close)),
+          "Expected file descriptor or '-' following '&'.",
+        ),
+      ))(input)?
+    } else {
+      (input, None)
+    };
+  let (input, io_file) = match io_file {
+    Some(io_file) => (input, io_file),
+    None => {
+      let (input, _) = skip_horizontal_whitespace(input)?;
+      map(parse_string_or_word, IoFile::Word)(input)?
+    }
+  };
 
   let maybe_fd = if let Some(fd) = maybe_fd {
     Some(RedirectFd::Fd(fd))
@@ -577,67 +1814,311 @@ fn parse_redirect(input: &str) -> ParseResult<Redirect> {
       maybe_fd,
       op,
       io_file,
+      span: span_between(original_input, input),
     },
   ))
 }
 
-fn parse_env_vars(input: &str) -> ParseResult<Vec<EnvVar>> {
-  many0(terminated(parse_env_var, skip_whitespace))(input)
-}
-
-fn parse_env_var(input: &str) -> ParseResult<EnvVar> {
-  let (input, name) = parse_env_var_name(input)?;
-  let (input, _) = ch('=')(input)?;
-  let (input, value) = with_error_context(
-    terminated(parse_env_var_value, assert_whitespace_or_end),
-    "Invalid environment variable value.",
+/// Parses the delimiter following a `<<`. The body is parsed after
+/// the end of the line by `parse_here_doc_bodies`, so until then
+/// it's `None` and the span is empty.
+fn parse_here_doc_redirect(input: &str) -> ParseResult<HereDoc> {
+  let (input, strip_tabs) = map(maybe(ch('-')), |c| c.is_some())(input)?;
+  let (input, _) = skip_horizontal_whitespace(input)?;
+  let delimiter_input = input;
+  let (input, _) = assert_exists(
+    ParseErrorKind::Expected,
+    if_true(parse_string_or_word, |word| !word.parts().is_empty()),
+    "Expected delimiter following here-document redirect.",
   )(input)?;
-  Ok((input, EnvVar::new(name.to_string(), value)))
-}
+  let raw_delimiter = &delimiter_input[..delimiter_input.len() - input.len()];
+
+  let mut delimiter = String::new();
+  let mut chars = raw_delimiter.chars();
+  while let Some(c) = chars.next() {
+    match c {
+      '\'' | '"' => {}
+      '\\' => delimiter.extend(chars.next()),
+      c => delimiter.push(c),
+    }
+  }
+
+  Ok((
+    input,
+    HereDoc {
+      delimiter,
+      is_quoted: raw_delimiter.contains(['\'', '"', '\\']),
+      strip_tabs,
+      body: None,
+      span: Span::default(),
+    },
+  ))
+}
+
+/// Parses the bodies of the pending here-documents within the items,
+/// which are found in order following the newline that ends their line.
+///
+/// The span of the last item is extended to include the bodies.
+fn parse_here_doc_bodies<'a>(
+  input: &'a str,
+  items: &mut [SequentialListItem],
+) -> ParseResult<'a, ()> {
+  let mut parser = HereDocBodyParser {
+    input,
+    has_parsed: false,
+    result: Ok(()),
+  };
+  for item in items.iter_mut() {
+    parser.visit_sequential_list_item_mut(item);
+  }
+  parser.result?;
+  if parser.has_parsed {
+    if let Some(item) = items.last_mut() {
+      item.span.end = parser.input.len();
+    }
+  }
+  Ok((parser.input, ()))
+}
+
+/// Gets if any here-documents within the items are missing their
+/// bodies, which happens when the input ends on the same line.
+fn has_pending_here_docs(items: &[SequentialListItem]) -> bool {
+  #[derive(Default)]
+  struct PendingHereDocFinder {
+    found: bool,
+  }
+
+  impl Visit for PendingHereDocFinder {
+    fn visit_here_doc(&mut self, here_doc: &HereDoc) {
+      self.found |= here_doc.body.is_none();
+    }
+  }
+
+  let mut finder = PendingHereDocFinder::default();
+  for item in items {
+    finder.visit_sequential_list_item(item);
+  }
+  finder.found
+}
+
+struct HereDocBodyParser<'a> {
+  input: &'a str,
+  has_parsed: bool,
+  result: Result<(), monch::ParseError<'a>>,
+}
+
+impl<'a> VisitMut for HereDocBodyParser<'a> {
+  fn visit_here_doc_mut(&mut self, here_doc: &mut HereDoc) {
+    // the bodies of here-documents in nested lists
+    // are parsed at the end of their own lines
+    if self.result.is_err() || here_doc.body.is_some() {
+      return;
+    }
+    let mut input = self.input;
+    if self.has_parsed {
+      // the previous body ended before the newline following its delimiter
+      if let Ok((rest, _)) = parse_newline(input) {
+        input = rest;
+      }
+    }
+    match parse_here_doc_body(input, here_doc) {
+      Ok((rest, body)) => {
+        here_doc.body = Some(body);
+        here_doc.span = span_between(input, rest);
+        self.input = rest;
+        self.has_parsed = true;
+      }
+      Err(err) => self.result = Err(err),
+    }
+  }
+}
+
+fn parse_here_doc_body<'a>(
+  input: &'a str,
+  here_doc: &HereDoc,
+) -> ParseResult<'a, Vec<StringPart>> {
+  let original_input = input;
+  let mut input = input;
+  let mut result: Vec<StringPart> = Vec::new();
+  loop {
+    if input.is_empty() {
+      return fail(
+        original_input,
+        ParseErrorKind::Unclosed,
+        format!("Expected '{}' to close here-document.", here_doc.delimiter),
+      );
+    }
+    let line_end = input.find('\n').map(|i| i + 1).unwrap_or(input.len());
+    let (line, rest) = input.split_at(line_end);
+    let line = if here_doc.strip_tabs {
+      line.trim_start_matches('\t')
+    } else {
+      line
+    };
+    let line_content = line.trim_end_matches(['\r', '\n']);
+    if line_content == here_doc.delimiter {
+      // leave the newline as it separates the command from the next one
+      let newline_len = line.len() - line_content.len();
+      return Ok((&input[line_end - newline_len..], result));
+    }
+
+    let mut parts = if here_doc.is_quoted {
+      vec![StringPart::Text {
+        value: line.to_string(),
+        span: span_between(line, ""),
+      }]
+    } else {
+      parse_string_parts(ParseStringPartsMode::HereDoc)(line)?.1
+    };
+    // the line was parsed on its own, so its spans don't include the
+    // input following the line
+    let mut mapper = SpanMapper(|span: Span| {
+      Span::new(span.start + rest.len(), span.end + rest.len())
+    });
+    for part in &mut parts {
+      mapper.visit_string_part_mut(part);
+    }
+    for part in parts {
+      match (result.last_mut(), part) {
+        (
+          Some(StringPart::Text { value, span }),
+          StringPart::Text {
+            value: part_value,
+            span: part_span,
+          },
+        ) => {
+          value.push_str(&part_value);
+          span.end = part_span.end;
+        }
+        (_, part) => result.push(part),
+      }
+    }
+    input = rest;
+  }
+}
+
+fn parse_env_vars(input: &str) -> ParseResult<Vec<EnvVar>> {
+  many0(terminated(parse_env_var, skip_horizontal_whitespace))(input)
+}
+
+fn parse_env_var(input: &str) -> ParseResult<EnvVar> {
+  let original_input = input;
+  let (input, name) = parse_env_var_name(input)?;
+  let (input, _) = ch('=')(input)?;
+  let (input, value) = with_error_context(
+    terminated(parse_env_var_value, assert_whitespace_or_end),
+    "Invalid environment variable value.",
+  )(input)?;
+  Ok((
+    input,
+    EnvVar {
+      name: name.to_string(),
+      value,
+      span: span_between(original_input, input),
+    },
+  ))
+}
 
 fn parse_env_var_name(input: &str) -> ParseResult<&str> {
   if_not_empty(take_while(is_valid_env_var_char))(input)
 }
 
+/// Parses the name of a variable or special parameter following a `$`.
+fn parse_variable_name(input: &str) -> ParseResult<&str> {
+  or(
+    parse_env_var_name,
+    // $# - number of positional parameters
+    // $@ and $* - all the positional parameters
+    // $? - last exit code
+    // $$ - process id
+    or5(tag("#"), tag("@"), tag("*"), tag("?"), tag("$")),
+  )(input)
+}
+
+/// Parses the name of a variable following a `$` without braces, where
+/// only a single digit is taken for a positional parameter (`$10` is
+/// `$1` followed by `0`).
+fn parse_unbraced_variable_name(input: &str) -> ParseResult<&str> {
+  or(
+    substring(if_true(next_char, |c| c.is_ascii_digit())),
+    parse_variable_name,
+  )(input)
+}
+
 fn parse_env_var_value(input: &str) -> ParseResult<StringOrWord> {
   parse_string_or_word(input)
 }
 
 fn parse_string_or_word(input: &str) -> ParseResult<StringOrWord> {
   or(
-    map(parse_quoted_string, StringOrWord::String),
-    map(parse_word, StringOrWord::Word),
+    map(with_span(parse_quoted_string), |(parts, span)| {
+      StringOrWord::String { parts, span }
+    }),
+    map(with_span(parse_word), |(parts, span)| StringOrWord::Word {
+      parts,
+      span,
+    }),
   )(input)
 }
 
 fn parse_word(input: &str) -> ParseResult<Vec<StringPart>> {
-  assert(
-    parse_string_parts(ParseStringPartsMode::Word),
-    |result| {
-      result
-        .ok()
-        .map(|(_, parts)| {
-          if parts.len() == 1 {
-            if let StringPart::Text(text) = &parts[0] {
-              return !is_reserved_word(text);
-            }
-          }
-          true
-        })
-        .unwrap_or(true)
-    },
-    "Unsupported reserved word.",
+  let (input, tilde) = maybe(with_span(parse_tilde_prefix))(input)?;
+  let (input, mut parts) =
+    parse_string_parts(ParseStringPartsMode::Word)(input)?;
+  if let Some((_, span)) = tilde {
+    parts.insert(0, StringPart::Tilde { span });
+  }
+  Ok((input, parts))
+}
+
+/// Parses a `~` at the start of a word that's followed by a `/` or
+/// the end of the word. Other tildes (ex. `~user`) are regular text.
+fn parse_tilde_prefix(input: &str) -> ParseResult<char> {
+  terminated(
+    ch('~'),
+    check_not(if_true(next_char, |&c| {
+      c != '/' && !is_unquoted_word_end_char(c)
+    })),
   )(input)
 }
 
+fn parse_pattern(input: &str) -> ParseResult<StringOrWord> {
+  map(with_span(if_not_empty(parse_word)), |(parts, span)| {
+    StringOrWord::Word { parts, span }
+  })(input)
+}
+
+/// Parses unquoted pattern characters such as `*`, `?`, or `[a-z]`.
+fn parse_glob(input: &str) -> ParseResult<&str> {
+  fn parse_bracket_expr(input: &str) -> ParseResult<()> {
+    let (input, _) = ch('[')(input)?;
+    let (input, _) = maybe(one_of("!^"))(input)?;
+    // a `]` at the start of the expression is a regular character
+    let (input, _) = maybe(ch(']'))(input)?;
+    let (input, _) =
+      take_while(|c| c != ']' && !is_unquoted_word_end_char(c))(input)?;
+    let (input, _) = ch(']')(input)?;
+    Ok((input, ()))
+  }
+
+  let original_input = input;
+  let (input, _) =
+    many1(or(map(one_of("*?"), |_| ()), parse_bracket_expr))(input)?;
+  Ok((input, &original_input[..original_input.len() - input.len()]))
+}
+
 fn parse_quoted_string(input: &str) -> ParseResult<Vec<StringPart>> {
   // Strings may be up beside each other, and if they are they
   // should be categorized as the same argument.
   map(
     many1(or(
-      map(parse_single_quoted_string, |text| {
-        vec![StringPart::Text(text.to_string())]
-      }),
+      |input| {
+        let (rest, text) = parse_single_quoted_string(input)?;
+        // the span of the text excludes the quotes
+        let span = Span::new(input.len() - 1, rest.len() + 1);
+        let value = text.to_string();
+        Ok((rest, vec![StringPart::Text { value, span }]))
+      },
       parse_double_quoted_string,
     )),
     |vecs| vecs.into_iter().flatten().collect(),
@@ -652,11 +2133,94 @@ fn parse_single_quoted_string(input: &str) -> ParseResult<&str> {
     take_while(|c| c != '\''),
     with_failure_input(
       input,
-      assert_exists(ch('\''), "Expected closing single quote."),
+      assert_exists(
+        ParseErrorKind::UnclosedQuote,
+        ch('\''),
+        "Expected closing single quote.",
+      ),
     ),
   )(input)
 }
 
+/// Parses an ANSI-C quoted string (ex. `$'a\tb\n'`), where backslash
+/// escape sequences are replaced with the characters they represent.
+fn parse_ansi_c_quoted_string(input: &str) -> ParseResult<String> {
+  let original_input = input;
+  let (mut input, _) = tag("$'")(input)?;
+  let mut text = String::new();
+  loop {
+    let mut chars = input.chars();
+    match chars.next() {
+      Some('\'') => return Ok((chars.as_str(), text)),
+      Some('\\') => {
+        let (rest, c) = parse_ansi_c_escape(chars.as_str());
+        match c {
+          Some(c) => text.push(c),
+          // unknown escapes are kept as-is
+          None => text.push('\\'),
+        }
+        input = rest;
+        continue;
+      }
+      Some(c) => text.push(c),
+      None => {
+        return fail(
+          original_input,
+          ParseErrorKind::UnclosedQuote,
+          "Expected closing single quote.",
+        );
+      }
+    }
+    input = chars.as_str();
+  }
+}
+
+/// Parses the escape sequence following a backslash in an
+/// ANSI-C quoted string, returning `None` for unknown sequences.
+fn parse_ansi_c_escape(input: &str) -> (&str, Option<char>) {
+  fn parse_code_point(
+    input: &str,
+    radix: u32,
+    max_len: usize,
+  ) -> Option<(&str, char)> {
+    let len = input
+      .chars()
+      .take(max_len)
+      .take_while(|c| c.is_digit(radix))
+      .count();
+    let value = u32::from_str_radix(&input[..len], radix).ok()?;
+    Some((&input[len..], char::from_u32(value)?))
+  }
+
+  let mut chars = input.chars();
+  let code_point = match chars.next() {
+    Some('x') => parse_code_point(chars.as_str(), 16, 2),
+    Some('u') => parse_code_point(chars.as_str(), 16, 4),
+    Some('U') => parse_code_point(chars.as_str(), 16, 8),
+    Some('0'..='7') => parse_code_point(input, 8, 3),
+    Some(c) => {
+      let escaped = match c {
+        'a' => Some('\x07'),
+        'b' => Some('\x08'),
+        'e' | 'E' => Some('\x1B'),
+        'f' => Some('\x0C'),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        'v' => Some('\x0B'),
+        '\\' | '\'' | '"' | '?' => Some(c),
+        _ => None,
+      };
+      escaped.map(|escaped| (chars.as_str(), escaped))
+    }
+    None => None,
+  };
+  match code_point {
+    Some((input, c)) => (input, Some(c)),
+    None => (input, None),
+  }
+}
+
 fn parse_double_quoted_string(input: &str) -> ParseResult<Vec<StringPart>> {
   // https://pubs.opengroup.org/onlinepubs/009604499/utilities/xcu_chap02.html#tag_02_02_03
   // Double quotes may have escaped
@@ -665,7 +2229,11 @@ fn parse_double_quoted_string(input: &str) -> ParseResult<Vec<StringPart>> {
     parse_string_parts(ParseStringPartsMode::DoubleQuotes),
     with_failure_input(
       input,
-      assert_exists(ch('"'), "Expected closing double quote."),
+      assert_exists(
+        ParseErrorKind::UnclosedQuote,
+        ch('"'),
+        "Expected closing double quote.",
+      ),
     ),
   )(input)
 }
@@ -674,6 +2242,10 @@ fn parse_double_quoted_string(input: &str) -> ParseResult<Vec<StringPart>> {
 enum ParseStringPartsMode {
   DoubleQuotes,
   Word,
+  /// The body of a here-document with an unquoted delimiter
+  HereDoc,
+  /// The word following an operator in a braced parameter expansion
+  ParameterWord,
 }
 
 fn parse_string_parts(
@@ -684,30 +2256,14 @@ fn parse_string_parts(
       parse_escaped_char('$'),
       terminated(
         ch('$'),
-        check_not(or(map(parse_env_var_name, |_| ()), map(ch('('), |_| ()))),
+        check_not(or(
+          map(parse_unbraced_variable_name, |_| ()),
+          map(one_of("({'"), |_| ()),
+        )),
       ),
     )(input)
   }
 
-  fn parse_special_shell_var(input: &str) -> ParseResult<char> {
-    // for now, these hard error
-    preceded(ch('$'), |input| {
-      if let Some(char) = input.chars().next() {
-        // $$ - process id
-        // $? - last exit code
-        // $# - number of arguments in $*
-        // $* - list of arguments passed to the current process
-        if "$?#*".contains(char) {
-          return ParseError::fail(
-            input,
-            format!("${} is currently not supported.", char),
-          );
-        }
-      }
-      ParseError::backtrace()
-    })(input)
-  }
-
   fn parse_escaped_char<'a>(
     c: char,
   ) -> impl Fn(&'a str) -> ParseResult<'a, char> {
@@ -717,11 +2273,12 @@ fn parse_string_parts(
   fn first_escaped_char<'a>(
     mode: ParseStringPartsMode,
   ) -> impl Fn(&'a str) -> ParseResult<'a, char> {
-    or5(
-      parse_special_shell_var,
+    or4(
       parse_escaped_dollar_sign,
       parse_escaped_char('`'),
-      parse_escaped_char('"'),
+      if_true(parse_escaped_char('"'), move |_| {
+        mode != ParseStringPartsMode::HereDoc
+      }),
       if_true(parse_escaped_char('\''), move |_| {
         mode == ParseStringPartsMode::DoubleQuotes
       }),
@@ -731,60 +2288,145 @@ fn parse_string_parts(
   move |input| {
     enum PendingPart<'a> {
       Char(char),
+      Text(String),
+      LineContinuation,
       Variable(&'a str),
       Command(SequentialList),
+      Arithmetic(ArithmeticExpr),
+      Glob(&'a str),
+      ParameterExpansion(ParameterExpansion),
       Parts(Vec<StringPart>),
     }
 
-    let (input, parts) = many0(or7(
-      map(first_escaped_char(mode), PendingPart::Char),
-      map(parse_command_substitution, PendingPart::Command),
-      map(preceded(ch('$'), parse_env_var_name), PendingPart::Variable),
-      |input| {
-        let (_, _) = ch('`')(input)?;
-        ParseError::fail(
-          input,
-          "Back ticks in strings is currently not supported.",
-        )
-      },
-      // words can have escaped spaces
+    let (input, parts) = many0(with_span(or7(
+      or3(
+        map(first_escaped_char(mode), PendingPart::Char),
+        map(
+          if_true(parse_line_continuation, |_| {
+            mode != ParseStringPartsMode::HereDoc
+          }),
+          |_| PendingPart::LineContinuation,
+        ),
+        map(
+          if_true(parse_ansi_c_quoted_string, |_| {
+            matches!(
+              mode,
+              ParseStringPartsMode::Word | ParseStringPartsMode::ParameterWord
+            )
+          }),
+          PendingPart::Text,
+        ),
+      ),
+      or(
+        map(parse_arithmetic_expansion, PendingPart::Arithmetic),
+        map(parse_command_substitution, PendingPart::Command),
+      ),
+      or(
+        map(parse_parameter_expansion, PendingPart::ParameterExpansion),
+        map(
+          preceded(ch('$'), parse_unbraced_variable_name),
+          PendingPart::Variable,
+        ),
+      ),
       map(
-        if_true(preceded(ch('\\'), ch(' ')), |_| {
-          mode == ParseStringPartsMode::Word
-        }),
-        PendingPart::Char,
+        parse_backticks_command_substitution(mode),
+        PendingPart::Command,
+      ),
+      or(
+        map(
+          if_true(parse_glob, |_| {
+            matches!(
+              mode,
+              ParseStringPartsMode::Word | ParseStringPartsMode::ParameterWord
+            )
+          }),
+          PendingPart::Glob,
+        ),
+        // words can have escaped spaces, glob characters, comments,
+        // and tildes
+        map(
+          if_true(preceded(ch('\\'), next_char), |&c| match mode {
+            ParseStringPartsMode::DoubleQuotes => false,
+            ParseStringPartsMode::HereDoc => c == '\\',
+            ParseStringPartsMode::Word => c == ' ' || "*?[]#~".contains(c),
+            ParseStringPartsMode::ParameterWord => "*?[]}\\".contains(c),
+          }),
+          PendingPart::Char,
+        ),
       ),
       map(
         if_true(next_char, |&c| match mode {
           ParseStringPartsMode::DoubleQuotes => c != '"',
+          ParseStringPartsMode::HereDoc => true,
+          ParseStringPartsMode::ParameterWord => !"*?}\"'".contains(c),
           ParseStringPartsMode::Word => {
-            !c.is_whitespace() && !"*~(){}<>?|&;\"'".contains(c)
+            !c.is_whitespace() && !"*(){}<>?|&;\"'".contains(c)
           }
         }),
         PendingPart::Char,
       ),
       |input| match mode {
-        ParseStringPartsMode::DoubleQuotes => ParseError::backtrace(),
-        ParseStringPartsMode::Word => {
+        ParseStringPartsMode::DoubleQuotes | ParseStringPartsMode::HereDoc => {
+          monch::ParseError::backtrace()
+        }
+        ParseStringPartsMode::Word | ParseStringPartsMode::ParameterWord => {
           let (input, parts) = parse_quoted_string(input)?;
           Ok((input, PendingPart::Parts(parts)))
         }
       },
-    ))(input)?;
+    )))(input)?;
 
     let mut result = Vec::new();
-    for part in parts {
+    for (part, part_span) in parts {
       match part {
         PendingPart::Char(c) => {
-          if let Some(StringPart::Text(text)) = result.last_mut() {
-            text.push(c);
+          if let Some(StringPart::Text { value, span }) = result.last_mut() {
+            value.push(c);
+            span.end = part_span.end;
+          } else {
+            result.push(StringPart::Text {
+              value: c.to_string(),
+              span: part_span,
+            });
+          }
+        }
+        PendingPart::Text(text) => {
+          if let Some(StringPart::Text { value, span }) = result.last_mut() {
+            value.push_str(&text);
+            span.end = part_span.end;
+          } else {
+            result.push(StringPart::Text {
+              value: text,
+              span: part_span,
+            });
+          }
+        }
+        PendingPart::LineContinuation => {}
+        PendingPart::Command(list) => result.push(StringPart::Command {
+          list,
+          span: part_span,
+        }),
+        PendingPart::Arithmetic(expr) => result.push(StringPart::Arithmetic {
+          expr,
+          span: part_span,
+        }),
+        PendingPart::Glob(g) => {
+          if let Some(StringPart::Glob { value, span }) = result.last_mut() {
+            value.push_str(g);
+            span.end = part_span.end;
           } else {
-            result.push(StringPart::Text(c.to_string()));
+            result.push(StringPart::Glob {
+              value: g.to_string(),
+              span: part_span,
+            });
           }
         }
-        PendingPart::Command(s) => result.push(StringPart::Command(s)),
-        PendingPart::Variable(v) => {
-          result.push(StringPart::Variable(v.to_string()))
+        PendingPart::Variable(v) => result.push(StringPart::Variable {
+          name: v.to_string(),
+          span: part_span,
+        }),
+        PendingPart::ParameterExpansion(expansion) => {
+          result.push(StringPart::ParameterExpansion(expansion))
         }
         PendingPart::Parts(parts) => {
           result.extend(parts);
@@ -796,17 +2438,374 @@ fn parse_string_parts(
   }
 }
 
+fn parse_parameter_expansion(input: &str) -> ParseResult<ParameterExpansion> {
+  let original_input = input;
+  let (input, _) = tag("${")(input)?;
+  // ${#VAR} - length of the value
+  if let Ok((input, name)) =
+    delimited(ch('#'), parse_variable_name, ch('}'))(input)
+  {
+    return Ok((
+      input,
+      ParameterExpansion {
+        name: name.to_string(),
+        op: Some(ParameterExpansionOp::Length),
+        span: span_between(original_input, input),
+      },
+    ));
+  }
+  let (input, name) = assert_exists(
+    ParseErrorKind::Expected,
+    parse_variable_name,
+    "Expected variable name in parameter expansion.",
+  )(input)?;
+  let (input, op) = maybe(parse_parameter_expansion_op)(input)?;
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      ch('}'),
+      "Expected '}' to close parameter expansion.",
+    ),
+  )(input)?;
+  Ok((
+    input,
+    ParameterExpansion {
+      name: name.to_string(),
+      op,
+      span: span_between(original_input, input),
+    },
+  ))
+}
+
+fn parse_parameter_expansion_op(
+  input: &str,
+) -> ParseResult<ParameterExpansionOp> {
+  let (input, op) = or3(
+    or4(tag(":-"), tag(":="), tag(":+"), tag(":?")),
+    or4(tag("-"), tag("="), tag("+"), tag("?")),
+    or4(tag("##"), tag("#"), tag("%%"), tag("%")),
+  )(input)?;
+  let (input, word) =
+    parse_string_parts(ParseStringPartsMode::ParameterWord)(input)?;
+  let check_null = op.starts_with(':');
+  let op = match op.trim_start_matches(':') {
+    "-" => ParameterExpansionOp::UseDefault { check_null, word },
+    "=" => ParameterExpansionOp::AssignDefault { check_null, word },
+    "+" => ParameterExpansionOp::UseAlternative { check_null, word },
+    "?" => ParameterExpansionOp::ErrorIfUnset { check_null, word },
+    "#" | "##" => ParameterExpansionOp::RemovePrefix {
+      longest: op == "##",
+      pattern: word,
+    },
+    "%" | "%%" => ParameterExpansionOp::RemoveSuffix {
+      longest: op == "%%",
+      pattern: word,
+    },
+    _ => unreachable!(),
+  };
+  Ok((input, op))
+}
+
+fn parse_arithmetic_expansion(input: &str) -> ParseResult<ArithmeticExpr> {
+  let original_input = input;
+  let (input, _) = tag("$((")(input)?;
+  if !has_arithmetic_expansion_close(input) {
+    // the `((` starts a subshell within a command
+    // substitution instead (ex. `$((echo 1) && echo 2)`)
+    return match parse_command_substitution(original_input) {
+      Ok(_) => monch::ParseError::backtrace(),
+      Err(monch::ParseError::Backtrace) => fail(
+        original_input,
+        ParseErrorKind::Unclosed,
+        "Expected '))' to close arithmetic expansion.",
+      ),
+      Err(err) => Err(err),
+    };
+  }
+  let (input, _) = skip_whitespace(input)?;
+  let (input, expr) = parse_arithmetic_expr(input)?;
+  let (input, _) = skip_whitespace(input)?;
+  let (input, _) = with_failure_input(
+    original_input,
+    assert_exists(
+      ParseErrorKind::Unclosed,
+      tag("))"),
+      "Expected '))' to close arithmetic expansion.",
+    ),
+  )(input)?;
+  Ok((input, expr))
+}
+
+/// Gets if the text following a `$((` has a matching `))`, which
+/// is when the parenthesis closing the `$((` is followed by another.
+fn has_arithmetic_expansion_close(input: &str) -> bool {
+  let mut depth = 0;
+  let mut chars = input.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      '(' => depth += 1,
+      ')' if depth > 0 => depth -= 1,
+      ')' => return chars.peek() == Some(&')'),
+      _ => {}
+    }
+  }
+  false
+}
+
+fn parse_arithmetic_expr(input: &str) -> ParseResult<ArithmeticExpr> {
+  parse_arithmetic_binary_expr(input, 0)
+}
+
+/// Number of precedence levels of the binary arithmetic operators.
+pub(crate) const ARITHMETIC_PRECEDENCE_LEVELS: usize = 6;
+
+/// Parses the binary operations with operators at or above
+/// the provided precedence level, which are all left associative.
+fn parse_arithmetic_binary_expr(
+  input: &str,
+  precedence: usize,
+) -> ParseResult<ArithmeticExpr> {
+  if precedence == ARITHMETIC_PRECEDENCE_LEVELS {
+    return parse_arithmetic_unary_expr(input);
+  }
+  let (mut input, mut expr) =
+    parse_arithmetic_binary_expr(input, precedence + 1)?;
+  loop {
+    let (rest, _) = skip_whitespace(input)?;
+    let (rest, op) = match parse_arithmetic_binary_op(rest, precedence) {
+      Ok(result) => result,
+      Err(monch::ParseError::Backtrace) => break,
+      Err(err) => return Err(err),
+    };
+    let (rest, _) = skip_whitespace(rest)?;
+    let (rest, right) = parse_arithmetic_binary_expr(rest, precedence + 1)?;
+    expr = ArithmeticExpr::Binary {
+      span: join_spans(expr.span(), right.span()),
+      left: Box::new(expr),
+      op,
+      right: Box::new(right),
+    };
+    input = rest;
+  }
+  Ok((input, expr))
+}
+
+fn parse_arithmetic_binary_op(
+  input: &str,
+  precedence: usize,
+) -> ParseResult<BinaryArithmeticOp> {
+  use BinaryArithmeticOp::*;
+  // operators that start with another operator come first
+  let ops = [
+    ("||", Or),
+    ("&&", And),
+    ("==", Equal),
+    ("!=", NotEqual),
+    ("<=", LessThanOrEqual),
+    (">=", GreaterThanOrEqual),
+    ("<", LessThan),
+    (">", GreaterThan),
+    ("+", Add),
+    ("-", Subtract),
+    ("*", Multiply),
+    ("/", Divide),
+    ("%", Modulo),
+  ];
+  for (text, op) in ops {
+    if op.precedence() != precedence {
+      continue;
+    }
+    if let Some(input) = input.strip_prefix(text) {
+      return Ok((input, op));
+    }
+  }
+  monch::ParseError::backtrace()
+}
+
+fn parse_arithmetic_unary_expr(input: &str) -> ParseResult<ArithmeticExpr> {
+  let original_input = input;
+  let (input, maybe_op) = maybe(or3(
+    map(ch('+'), |_| UnaryArithmeticOp::Plus),
+    map(ch('-'), |_| UnaryArithmeticOp::Minus),
+    map(terminated(ch('!'), check_not(ch('='))), |_| {
+      UnaryArithmeticOp::Not
+    }),
+  ))(input)?;
+  match maybe_op {
+    Some(op) => {
+      let (input, _) = skip_whitespace(input)?;
+      let (input, operand) = parse_arithmetic_unary_expr(input)?;
+      Ok((
+        input,
+        ArithmeticExpr::Unary {
+          op,
+          operand: Box::new(operand),
+          span: span_between(original_input, input),
+        },
+      ))
+    }
+    None => parse_arithmetic_primary_expr(input),
+  }
+}
+
+fn parse_arithmetic_primary_expr(input: &str) -> ParseResult<ArithmeticExpr> {
+  let original_input = input;
+  if let Ok((input, digits)) =
+    if_not_empty(take_while(|c| c.is_ascii_digit()))(input)
+  {
+    return match digits.parse::<i64>() {
+      Ok(value) => Ok((
+        input,
+        ArithmeticExpr::Number {
+          value,
+          span: span_between(original_input, input),
+        },
+      )),
+      Err(_) => fail(
+        original_input,
+        ParseErrorKind::InvalidNumber,
+        "Arithmetic number too large.",
+      ),
+    };
+  }
+  if let Ok((input, name)) = or(
+    preceded(ch('$'), parse_unbraced_variable_name),
+    parse_env_var_name,
+  )(input)
+  {
+    return Ok((
+      input,
+      ArithmeticExpr::Variable {
+        name: name.to_string(),
+        span: span_between(original_input, input),
+      },
+    ));
+  }
+  if let Ok((input, _)) = ch('(')(input) {
+    let (input, _) = skip_whitespace(input)?;
+    let (input, expr) = parse_arithmetic_expr(input)?;
+    let (input, _) = skip_whitespace(input)?;
+    let (input, _) = with_failure_input(
+      original_input,
+      assert_exists(
+        ParseErrorKind::Unclosed,
+        ch(')'),
+        "Expected closing parenthesis in arithmetic expression.",
+      ),
+    )(input)?;
+    return Ok((
+      input,
+      ArithmeticExpr::Parentheses {
+        expr: Box::new(expr),
+        span: span_between(original_input, input),
+      },
+    ));
+  }
+  fail(
+    input,
+    ParseErrorKind::Expected,
+    "Expected arithmetic operand.",
+  )
+}
+
 fn parse_command_substitution(input: &str) -> ParseResult<SequentialList> {
   delimited(tag("$("), parse_sequential_list, ch(')'))(input)
 }
 
+/// Parses a command substitution using back ticks (ex. `` `echo 1` ``).
+///
+/// Within the back ticks, a backslash only escapes `$`, `` ` ``, and `\`
+/// (and `"` when within double quotes), so the command is parsed
+/// after those backslashes are removed.
+fn parse_backticks_command_substitution(
+  mode: ParseStringPartsMode,
+) -> impl Fn(&str) -> ParseResult<SequentialList> {
+  move |input| {
+    let original_input = input;
+    let (mut input, _) = ch('`')(input)?;
+    let mut text = String::new();
+    loop {
+      let mut chars = input.chars();
+      match chars.next() {
+        Some('`') => {
+          input = chars.as_str();
+          break;
+        }
+        Some('\\') => match chars.next() {
+          Some(c)
+            if "$`\\".contains(c)
+              || c == '"' && mode == ParseStringPartsMode::DoubleQuotes =>
+          {
+            text.push(c);
+          }
+          Some(c) => {
+            text.push('\\');
+            text.push(c);
+          }
+          None => text.push('\\'),
+        },
+        Some(c) => text.push(c),
+        None => {
+          return fail(
+            original_input,
+            ParseErrorKind::UnclosedQuote,
+            "Expected closing back tick.",
+          );
+        }
+      }
+      input = chars.as_str();
+    }
+
+    let raw_text = &original_input[1..original_input.len() - input.len() - 1];
+    let text = text.trim();
+    let result = match parse_sequential_list(text) {
+      Ok((rest, list)) => {
+        if rest.trim().is_empty() {
+          Ok(list)
+        } else {
+          Err(fail_for_trailing_input(rest))
+        }
+      }
+      Err(monch::ParseError::Backtrace) => Err(fail_for_trailing_input(text)),
+      Err(monch::ParseError::Failure(err)) => Err(err),
+    };
+    match result {
+      Ok(mut list) => {
+        // the command was parsed on its own, so offset its spans to
+        // where it is in the input or use the span of the entire command
+        // substitution when the unescaped text differs from the input
+        let span = span_between(original_input, input);
+        if text == raw_text.trim() {
+          let offset =
+            input.len() + 1 + raw_text.len() - raw_text.trim_end().len();
+          SpanMapper(|span: Span| {
+            Span::new(span.start + offset, span.end + offset)
+          })
+          .visit_sequential_list_mut(&mut list);
+        } else {
+          SpanMapper(|_| span).visit_sequential_list_mut(&mut list);
+        }
+        Ok((input, list))
+      }
+      // the inner command was unescaped, so report the error
+      // at the start of the command substitution
+      Err(err) => monch::ParseError::fail(original_input, err.message),
+    }
+  }
+}
+
 fn parse_subshell(input: &str) -> ParseResult<SequentialList> {
   delimited(
     terminated(ch('('), skip_whitespace),
     parse_sequential_list,
     with_failure_input(
       input,
-      assert_exists(ch(')'), "Expected closing parenthesis on subshell."),
+      assert_exists(
+        ParseErrorKind::Unclosed,
+        ch(')'),
+        "Expected closing parenthesis on subshell.",
+      ),
     ),
   )(input)
 }
@@ -818,14 +2817,14 @@ fn parse_u32(input: &str) -> ParseResult<u32> {
     if c.is_ascii_digit() {
       let shifted_val = match value.checked_mul(10) {
         Some(val) => val,
-        None => return ParseError::backtrace(),
+        None => return monch::ParseError::backtrace(),
       };
       value = match shifted_val.checked_add(c.to_digit(10).unwrap()) {
         Some(val) => val,
-        None => return ParseError::backtrace(),
+        None => return monch::ParseError::backtrace(),
       };
     } else if byte_index == 0 {
-      return ParseError::backtrace();
+      return monch::ParseError::backtrace();
     } else {
       break;
     }
@@ -834,29 +2833,96 @@ fn parse_u32(input: &str) -> ParseResult<u32> {
   Ok((&input[byte_index..], value))
 }
 
+/// Skips whitespace, including newlines, along with any comments.
+///
+/// This is used instead of monch's `skip_whitespace` so that comments
+/// are allowed anywhere whitespace separates words.
+fn skip_whitespace(input: &str) -> ParseResult<()> {
+  let mut input = input;
+  loop {
+    let (rest, _) = monch::skip_whitespace(input)?;
+    match skip_comment(rest) {
+      Ok((rest, _)) => input = rest,
+      Err(_) => return Ok((rest, ())),
+    }
+  }
+}
+
+/// Skips spaces, tabs, and line continuations along with a trailing
+/// comment, but not the end of the line.
+fn skip_horizontal_whitespace(input: &str) -> ParseResult<()> {
+  let (input, _) =
+    many0(or(map(one_of(" \t"), |_| ()), parse_line_continuation))(input)?;
+  let (input, _) = maybe(skip_comment)(input)?;
+  Ok((input, ()))
+}
+
+/// Parses a backslash followed by a newline, which continues
+/// the current line onto the next.
+fn parse_line_continuation(input: &str) -> ParseResult<()> {
+  map(preceded(ch('\\'), parse_newline), |_| ())(input)
+}
+
+/// Skips a comment (ex. `# comment`) up to the end of the line.
+fn skip_comment(input: &str) -> ParseResult<()> {
+  map(
+    preceded(ch('#'), take_while(|c| c != '\n' && c != '\r')),
+    |_| (),
+  )(input)
+}
+
 fn assert_whitespace_or_end_and_skip(input: &str) -> ParseResult<()> {
-  terminated(assert_whitespace_or_end, skip_whitespace)(input)
+  terminated(assert_whitespace_or_end, skip_horizontal_whitespace)(input)
 }
 
 fn assert_whitespace_or_end(input: &str) -> ParseResult<()> {
   if let Some(next_char) = input.chars().next() {
     if !next_char.is_whitespace()
-      && !matches!(next_char, ';' | '&' | '|' | '(' | ')')
+      && !matches!(next_char, ';' | '&' | '|' | '(' | ')' | '<' | '>')
     {
-      return Err(ParseError::Failure(fail_for_trailing_input(input)));
+      return Err(monch::ParseError::Failure(fail_for_trailing_input(input)));
     }
   }
   Ok((input, ()))
 }
 
-fn is_valid_env_var_char(c: char) -> bool {
-  // [a-zA-Z0-9_]+
-  c.is_ascii_alphanumeric() || c == '_'
+fn parse_reserved_word<'a>(
+  word: &'static str,
+) -> impl Fn(&'a str) -> ParseResult<'a, &'a str> {
+  debug_assert!(is_reserved_word(word));
+  terminated(tag(word), check_not(parse_unquoted_word_char))
 }
 
-fn is_reserved_word(text: &str) -> bool {
-  matches!(
-    text,
+fn parse_any_reserved_word(input: &str) -> ParseResult<&str> {
+  if_true(
+    if_not_empty(take_while(|c| !is_unquoted_word_end_char(c))),
+    |text| is_reserved_word(text),
+  )(input)
+}
+
+fn parse_unsupported_reserved_word(input: &str) -> ParseResult<&str> {
+  if_true(
+    if_not_empty(take_while(|c| !is_unquoted_word_end_char(c))),
+    |text| is_unsupported_reserved_word(text),
+  )(input)
+}
+
+fn parse_unquoted_word_char(input: &str) -> ParseResult<char> {
+  if_true(next_char, |&c| !is_unquoted_word_end_char(c))(input)
+}
+
+fn is_unquoted_word_end_char(c: char) -> bool {
+  c.is_whitespace() || matches!(c, ';' | '&' | '|' | '(' | ')' | '<' | '>')
+}
+
+pub(crate) fn is_valid_env_var_char(c: char) -> bool {
+  // [a-zA-Z0-9_]+
+  c.is_ascii_alphanumeric() || c == '_'
+}
+
+pub(crate) fn is_reserved_word(text: &str) -> bool {
+  matches!(
+    text,
     "if"
       | "then"
       | "else"
@@ -873,20 +2939,33 @@ fn is_reserved_word(text: &str) -> bool {
   )
 }
 
+/// Reserved words of other shells that aren't supported.
+///
+/// `time` is left out because it's commonly a program.
+fn is_unsupported_reserved_word(text: &str) -> bool {
+  matches!(text, "select" | "function" | "coproc" | "[[" | "]]")
+}
+
 fn fail_for_trailing_input(input: &str) -> ParseErrorFailure {
-  if input.starts_with('*') || input.starts_with('?') {
-    ParseErrorFailure::new(
+  if parse_any_reserved_word(input).is_ok() {
+    new_failure(
       input,
-      "Globs are currently not supported, but will be soon.",
+      ParseErrorKind::UnexpectedReservedWord,
+      "Unexpected reserved word.",
     )
   } else {
-    ParseErrorFailure::new(input, "Unexpected character.")
+    new_failure(
+      input,
+      ParseErrorKind::UnexpectedChar,
+      "Unexpected character.",
+    )
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use crate::visit::Visit;
   use pretty_assertions::assert_eq;
 
   #[test]
@@ -900,22 +2979,6 @@ mod test {
       parse("test { test").err().unwrap().to_string(),
       concat!("Unexpected character.\n", "  { test\n", "  ~",),
     );
-    assert_eq!(
-      parse("cp test/* other").err().unwrap().to_string(),
-      concat!(
-        "Globs are currently not supported, but will be soon.\n",
-        "  * other\n",
-        "  ~",
-      ),
-    );
-    assert_eq!(
-      parse("cp test/? other").err().unwrap().to_string(),
-      concat!(
-        "Globs are currently not supported, but will be soon.\n",
-        "  ? other\n",
-        "  ~",
-      ),
-    );
     assert_eq!(
       parse("(test").err().unwrap().to_string(),
       concat!(
@@ -938,16 +3001,210 @@ mod test {
     assert!(parse("command --arg=\"value\"").is_ok());
 
     assert_eq!(
-      parse("echo `echo 1`").err().unwrap().to_string(),
-      concat!(
-        "Back ticks in strings is currently not supported.\n",
-        "  `echo 1`\n",
-        "  ~",
-      ),
+      parse("echo `echo 1").err().unwrap().to_string(),
+      concat!("Expected closing back tick.\n", "  `echo 1\n", "  ~"),
+    );
+    assert_eq!(
+      parse("echo `echo )`").err().unwrap().to_string(),
+      concat!("Unexpected character.\n", "  `echo )`\n", "  ~"),
     );
     assert!(
       parse("deno run --allow-read=. --allow-write=./testing main.ts").is_ok(),
     );
+    assert!(parse("echo if then fi").is_ok());
+    assert_eq!(
+      parse("echo 1; esac").err().unwrap().to_string(),
+      concat!("Unexpected reserved word.\n", "  esac\n", "  ~"),
+    );
+    assert_eq!(
+      parse("fi").err().unwrap().to_string(),
+      concat!("Unexpected reserved word.\n", "  fi\n", "  ~"),
+    );
+  }
+
+  #[test]
+  fn test_parse_error_kinds() {
+    fn get_kind_and_offset(input: &str) -> (ParseErrorKind, usize) {
+      let err = parse(input).err().unwrap();
+      (err.kind, err.offset)
+    }
+
+    assert_eq!(get_kind_and_offset(""), (ParseErrorKind::EmptyCommand, 0));
+    assert_eq!(
+      get_kind_and_offset("echo 1 && )"),
+      (ParseErrorKind::Expected, 10),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo 1 )"),
+      (ParseErrorKind::UnexpectedChar, 7),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo 1; done"),
+      (ParseErrorKind::UnexpectedReservedWord, 8),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo 'test"),
+      (ParseErrorKind::UnclosedQuote, 5),
+    );
+    assert_eq!(
+      get_kind_and_offset("A=\"test"),
+      (ParseErrorKind::UnclosedQuote, 2),
+    );
+    assert_eq!(
+      get_kind_and_offset("if true; then echo 1"),
+      (ParseErrorKind::Unclosed, 0),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo 1 > out | cat"),
+      (ParseErrorKind::UnsupportedFeature, 0),
+    );
+    assert_eq!(
+      get_kind_and_offset("A=1 B=2"),
+      (ParseErrorKind::UnsupportedFeature, 0),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo $((99999999999999999999))"),
+      (ParseErrorKind::InvalidNumber, 8),
+    );
+    assert_eq!(
+      get_kind_and_offset("cat <<EOF\ntest"),
+      (ParseErrorKind::Unclosed, 10),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo 1 && select x in a b; do echo $x; done"),
+      (ParseErrorKind::UnsupportedReservedWord, 10),
+    );
+    assert_eq!(
+      get_kind_and_offset("[[ -f file ]]"),
+      (ParseErrorKind::UnsupportedReservedWord, 0),
+    );
+    assert_eq!(
+      get_kind_and_offset("function f { echo 1; }"),
+      (ParseErrorKind::UnsupportedReservedWord, 0),
+    );
+    // a failure that's given context or re-raised keeps its kind
+    assert_eq!(
+      get_kind_and_offset("echo 1 && echo 'test"),
+      (ParseErrorKind::UnclosedQuote, 15),
+    );
+    assert_eq!(
+      get_kind_and_offset("echo `echo 'test`"),
+      (ParseErrorKind::UnclosedQuote, 5),
+    );
+    // only the first word of a command is reserved
+    assert!(parse("echo select function [[ ]]").is_ok());
+  }
+
+  #[test]
+  fn test_if_clause() {
+    run_test(
+      parse_command,
+      "if test -f file; then echo 1; elif true; then echo 2; else echo 3; fi",
+      Ok(Command {
+        inner: CommandInner::If(IfClause {
+          condition: SequentialList {
+            items: vec![SequentialListItem {
+              is_async: false,
+              sequence: SimpleCommand {
+                env_vars: vec![],
+                args: vec![
+                  StringOrWord::new_word("test"),
+                  StringOrWord::new_word("-f"),
+                  StringOrWord::new_word("file"),
+                ],
+                span: Span::default(),
+              }
+              .into(),
+              span: Span::default(),
+            }],
+            span: Span::default(),
+          },
+          body: SequentialList {
+            items: vec![SequentialListItem {
+              is_async: false,
+              sequence: SimpleCommand {
+                env_vars: vec![],
+                args: vec![
+                  StringOrWord::new_word("echo"),
+                  StringOrWord::new_word("1"),
+                ],
+                span: Span::default(),
+              }
+              .into(),
+              span: Span::default(),
+            }],
+            span: Span::default(),
+          },
+          else_part: Some(ElsePart::Elif(Box::new(IfClause {
+            condition: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![StringOrWord::new_word("true")],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            body: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![
+                    StringOrWord::new_word("echo"),
+                    StringOrWord::new_word("2"),
+                  ],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            else_part: Some(ElsePart::Else(SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![
+                    StringOrWord::new_word("echo"),
+                    StringOrWord::new_word("3"),
+                  ],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            })),
+            span: Span::default(),
+          }))),
+          span: Span::default(),
+        }),
+        redirects: Vec::new(),
+        span: Span::default(),
+      }),
+    );
+
+    run_test(
+      parse_command,
+      "if true; then fi",
+      Err("Expected command following 'then'."),
+    );
+    run_test(
+      parse_command,
+      "if true; echo 1; fi",
+      Err("Expected 'then' following if condition."),
+    );
+    run_test(
+      parse_command,
+      "if true; then echo 1",
+      Err("Expected 'fi' to close if statement."),
+    );
   }
 
   #[test]
@@ -980,6 +3237,7 @@ mod test {
                   StringOrWord::new_word("command"),
                   StringOrWord::new_word("arg1"),
                 ],
+                span: Span::default(),
               }
               .into(),
               op: BooleanListOperator::Or,
@@ -990,9 +3248,12 @@ mod test {
                   StringOrWord::new_word("arg12"),
                   StringOrWord::new_word("arg13"),
                 ],
+                span: Span::default(),
               }
               .into(),
+              span: Span::default(),
             })),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: true,
@@ -1000,23 +3261,29 @@ mod test {
               current: SimpleCommand {
                 env_vars: vec![],
                 args: vec![StringOrWord::new_word("command3")],
+                span: Span::default(),
               }
               .into(),
               op: BooleanListOperator::And,
               next: SimpleCommand {
                 env_vars: vec![],
                 args: vec![StringOrWord::new_word("command4")],
+                span: Span::default(),
               }
               .into(),
+              span: Span::default(),
             })),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
             sequence: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("command5")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
@@ -1026,8 +3293,10 @@ mod test {
                 StringOrWord::new_word("export"),
                 StringOrWord::new_word("ENV6=5"),
               ],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
@@ -1041,16 +3310,21 @@ mod test {
                 current: SimpleCommand {
                   env_vars: vec![],
                   args: vec![StringOrWord::new_word("command8")],
+                  span: Span::default(),
                 }
                 .into(),
                 op: BooleanListOperator::Or,
                 next: SimpleCommand {
                   env_vars: vec![],
                   args: vec![StringOrWord::new_word("command9")],
+                  span: Span::default(),
                 }
                 .into(),
+                span: Span::default(),
               })),
+              span: Span::default(),
             })),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
@@ -1058,6 +3332,7 @@ mod test {
               current: SimpleCommand {
                 env_vars: vec![],
                 args: vec![StringOrWord::new_word("cmd10")],
+                span: Span::default(),
               }
               .into(),
               op: BooleanListOperator::And,
@@ -1069,23 +3344,32 @@ mod test {
                       current: SimpleCommand {
                         env_vars: vec![],
                         args: vec![StringOrWord::new_word("cmd11")],
+                        span: Span::default(),
                       }
                       .into(),
                       op: BooleanListOperator::Or,
                       next: SimpleCommand {
                         env_vars: vec![],
                         args: vec![StringOrWord::new_word("cmd12")],
+                        span: Span::default(),
                       }
                       .into(),
+                      span: Span::default(),
                     })),
+                    span: Span::default(),
                   }],
+                  span: Span::default(),
                 })),
-                redirect: None,
+                redirects: Vec::new(),
+                span: Span::default(),
               }
               .into(),
+              span: Span::default(),
             })),
+            span: Span::default(),
           },
         ],
+        span: Span::default(),
       }),
     );
 
@@ -1099,16 +3383,20 @@ mod test {
             sequence: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("command1")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
             sequence: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("command2")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           },
           SequentialListItem {
             is_async: false,
@@ -1118,10 +3406,13 @@ mod test {
                 StringOrWord::new_string("b"),
               )],
               args: vec![StringOrWord::new_word("command3")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           },
         ],
+        span: Span::default(),
       }),
     );
 
@@ -1140,9 +3431,12 @@ mod test {
           sequence: SimpleCommand {
             env_vars: vec![],
             args: vec![StringOrWord::new_word("command")],
+            span: Span::default(),
           }
           .into(),
+          span: Span::default(),
         }],
+        span: Span::default(),
       }),
     );
 
@@ -1156,17 +3450,22 @@ mod test {
             current: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("test")],
+              span: Span::default(),
             }
             .into(),
             op: PipeSequenceOperator::Stdout,
             next: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("other")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           }
           .into(),
+          span: Span::default(),
         }],
+        span: Span::default(),
       }),
     );
 
@@ -1180,17 +3479,22 @@ mod test {
             current: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("test")],
+              span: Span::default(),
             }
             .into(),
             op: PipeSequenceOperator::StdoutStderr,
             next: SimpleCommand {
               env_vars: vec![],
               args: vec![StringOrWord::new_word("other")],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           }
           .into(),
+          span: Span::default(),
         }],
+        span: Span::default(),
       }),
     );
 
@@ -1210,13 +3514,17 @@ mod test {
             env_vars: vec![],
             args: vec![
               StringOrWord::new_word("echo"),
-              StringOrWord::Word(vec![StringPart::Variable(
-                "MY_ENV".to_string(),
-              )]),
+              StringOrWord::Word {
+                parts: vec![StringPart::new_variable("MY_ENV")],
+                span: Span::default(),
+              },
             ],
+            span: Span::default(),
           }
           .into(),
+          span: Span::default(),
         }],
+        span: Span::default(),
       }),
     );
 
@@ -1233,178 +3541,645 @@ mod test {
                 current: SimpleCommand {
                   args: vec![StringOrWord::new_word("cmd1")],
                   env_vars: vec![],
+                  span: Span::default(),
                 }
                 .into(),
                 op: PipeSequenceOperator::Stdout,
                 next: SimpleCommand {
                   args: vec![StringOrWord::new_word("cmd2")],
                   env_vars: vec![],
+                  span: Span::default(),
                 }
                 .into(),
+                span: Span::default(),
               }
               .into(),
+              span: Span::default(),
             }
             .into(),
             op: BooleanListOperator::And,
             next: SimpleCommand {
               args: vec![StringOrWord::new_word("cmd3")],
               env_vars: vec![],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           })),
+          span: Span::default(),
         }],
+        span: Span::default(),
       }),
     );
   }
 
   #[test]
-  fn test_env_var() {
+  fn test_for_clause() {
     run_test(
-      parse_env_var,
-      "Name=Value",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::new_word("Value"),
+      parse_for_clause,
+      "for i in 1 $VAR; do echo $i; done",
+      Ok(ForClause {
+        var_name: "i".to_string(),
+        wordlist: vec![
+          StringOrWord::new_word("1"),
+          StringOrWord::Word {
+            parts: vec![StringPart::new_variable("VAR")],
+            span: Span::default(),
+          },
+        ],
+        body: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::Word {
+                  parts: vec![StringPart::new_variable("i")],
+                  span: Span::default(),
+                },
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
       }),
     );
     run_test(
-      parse_env_var,
-      "Name='quoted value'",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::new_string("quoted value"),
+      parse_for_clause,
+      "for i in; do echo $i; done",
+      Ok(ForClause {
+        var_name: "i".to_string(),
+        wordlist: vec![],
+        body: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::Word {
+                  parts: vec![StringPart::new_variable("i")],
+                  span: Span::default(),
+                },
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
       }),
     );
     run_test(
-      parse_env_var,
-      "Name=\"double quoted value\"",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::new_string("double quoted value"),
-      }),
+      parse_for_clause,
+      "for $i in 1; do echo; done",
+      Err("Expected variable name following 'for'."),
+    );
+    run_test(
+      parse_for_clause,
+      "for i 1 2; do echo; done",
+      Err("Expected 'in' following for loop variable name."),
+    );
+    run_test(
+      parse_for_clause,
+      "for i in 1 2; echo; done",
+      Err("Expected 'do' for loop body."),
     );
     run_test_with_end(
-      parse_env_var,
-      "Name= command_name",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::Word(vec![]),
-      }),
-      " command_name",
+      parse_for_clause,
+      "for i in 1 2; do echo",
+      Err("Expected 'done' to close loop body."),
+      "do echo",
     );
+  }
 
+  #[test]
+  fn test_while_clause() {
     run_test(
-      parse_env_var,
-      "Name=$(test)",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::Word(vec![StringPart::Command(SequentialList {
+      parse_while_clause,
+      "while test -f file; do sleep 1; done",
+      Ok(WhileClause {
+        is_until: false,
+        condition: SequentialList {
           items: vec![SequentialListItem {
             is_async: false,
             sequence: SimpleCommand {
               env_vars: vec![],
-              args: vec![StringOrWord::new_word("test")],
+              args: vec![
+                StringOrWord::new_word("test"),
+                StringOrWord::new_word("-f"),
+                StringOrWord::new_word("file"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        body: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("sleep"),
+                StringOrWord::new_word("1"),
+              ],
+              span: Span::default(),
             }
             .into(),
+            span: Span::default(),
           }],
-        })]),
+          span: Span::default(),
+        },
+        span: Span::default(),
       }),
     );
-
     run_test(
-      parse_env_var,
-      "Name=$(OTHER=5)",
-      Ok(EnvVar {
-        name: "Name".to_string(),
-        value: StringOrWord::Word(vec![StringPart::Command(SequentialList {
+      parse_while_clause,
+      "until false; do break; done",
+      Ok(WhileClause {
+        is_until: true,
+        condition: SequentialList {
           items: vec![SequentialListItem {
             is_async: false,
-            sequence: Sequence::ShellVar(EnvVar {
-              name: "OTHER".to_string(),
-              value: StringOrWord::new_word("5"),
-            }),
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![StringOrWord::new_word("false")],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        body: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![StringOrWord::new_word("break")],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
           }],
-        })]),
+          span: Span::default(),
+        },
+        span: Span::default(),
       }),
     );
-  }
-
-  #[test]
-  fn test_single_quotes() {
-    run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      "'test'",
-      Ok(StringOrWord::new_string("test")),
-    );
-    run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#"'te\\'"#,
-      Ok(StringOrWord::new_string(r#"te\\"#)),
-    );
-    run_test_with_end(
-      map(parse_quoted_string, StringOrWord::String),
-      r#"'te\'st'"#,
-      Ok(StringOrWord::new_string(r#"te\"#)),
-      "st'",
-    );
     run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      "'  '",
-      Ok(StringOrWord::new_string("  ")),
+      parse_while_clause,
+      "while; do echo; done",
+      Err("Expected command following 'while' or 'until'."),
     );
     run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      "'  ",
-      Err("Expected closing single quote."),
+      parse_while_clause,
+      "while true; echo; done",
+      Err("Expected 'do' for loop body."),
     );
   }
 
   #[test]
-  fn test_double_quotes() {
+  fn test_function_definition() {
     run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#""  ""#,
-      Ok(StringOrWord::new_string("  ")),
-    );
-    run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#""test""#,
-      Ok(StringOrWord::new_string("test")),
+      parse_sequence,
+      "greet() { echo hello $1; }",
+      Ok(Sequence::FunctionDefinition(FunctionDefinition {
+        name: "greet".to_string(),
+        body: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_word("hello"),
+                StringOrWord::Word {
+                  parts: vec![StringPart::new_variable("1")],
+                  span: Span::default(),
+                },
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      })),
     );
-    run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#""te\"\$\`st""#,
-      Ok(StringOrWord::new_string(r#"te"$`st"#)),
+    run_test_with_end(
+      parse_sequence,
+      "f ( ) { true; } && f",
+      Ok(Sequence::BooleanList(Box::new(BooleanList {
+        current: Sequence::FunctionDefinition(FunctionDefinition {
+          name: "f".to_string(),
+          body: SequentialList {
+            items: vec![SequentialListItem {
+              is_async: false,
+              sequence: SimpleCommand {
+                env_vars: vec![],
+                args: vec![StringOrWord::new_word("true")],
+                span: Span::default(),
+              }
+              .into(),
+              span: Span::default(),
+            }],
+            span: Span::default(),
+          },
+          span: Span::default(),
+        }),
+        op: BooleanListOperator::And,
+        next: SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("f")],
+          span: Span::default(),
+        }
+        .into(),
+        span: Span::default(),
+      }))),
+      "",
     );
     run_test(
-      parse_quoted_string,
-      r#""  "#,
-      Err("Expected closing double quote."),
+      parse_sequence,
+      "f() echo",
+      Err("Expected '{' to begin function body."),
     );
     run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#""$Test""#,
-      Ok(StringOrWord::String(vec![StringPart::Variable(
-        "Test".to_string(),
-      )])),
+      parse_sequence,
+      "f() { }",
+      Err("Expected command following '{'."),
     );
     run_test(
-      map(parse_quoted_string, StringOrWord::String),
-      r#""$Test,$Other_Test""#,
-      Ok(StringOrWord::String(vec![
-        StringPart::Variable("Test".to_string()),
-        StringPart::Text(",".to_string()),
-        StringPart::Variable("Other_Test".to_string()),
-      ])),
+      parse_sequence,
+      "f() { echo",
+      Err("Expected '}' to close brace group."),
     );
+  }
+
+  #[test]
+  fn test_brace_group() {
     run_test(
-      parse_quoted_string,
+      parse_command,
+      "{ cd sub && export X=1; } > log.txt",
+      Ok(Command {
+        inner: CommandInner::Group(Box::new(SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: Sequence::BooleanList(Box::new(BooleanList {
+              current: SimpleCommand {
+                env_vars: vec![],
+                args: vec![
+                  StringOrWord::new_word("cd"),
+                  StringOrWord::new_word("sub"),
+                ],
+                span: Span::default(),
+              }
+              .into(),
+              op: BooleanListOperator::And,
+              next: SimpleCommand {
+                env_vars: vec![],
+                args: vec![
+                  StringOrWord::new_word("export"),
+                  StringOrWord::new_word("X=1"),
+                ],
+                span: Span::default(),
+              }
+              .into(),
+              span: Span::default(),
+            })),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        })),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Word(StringOrWord::new_word("log.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_command,
+      "{ echo 1",
+      Err("Expected '}' to close brace group."),
+    );
+    run_test(
+      parse_command,
+      "{ ; }",
+      Err("Expected command following '{'."),
+    );
+  }
+
+  #[test]
+  fn test_case_clause() {
+    run_test(
+      parse_case_clause,
+      "case $OS in linux|darwin) echo unix;; \"win\"*) ;; (*) echo other; esac",
+      Ok(CaseClause {
+        word: StringOrWord::Word {
+          parts: vec![StringPart::new_variable("OS")],
+          span: Span::default(),
+        },
+        items: vec![
+          CaseItem {
+            patterns: vec![
+              StringOrWord::new_word("linux"),
+              StringOrWord::new_word("darwin"),
+            ],
+            body: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![
+                    StringOrWord::new_word("echo"),
+                    StringOrWord::new_word("unix"),
+                  ],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            span: Span::default(),
+          },
+          CaseItem {
+            patterns: vec![StringOrWord::Word {
+              parts: vec![
+                StringPart::new_text("win"),
+                StringPart::new_glob("*"),
+              ],
+              span: Span::default(),
+            }],
+            body: SequentialList {
+              items: vec![],
+              span: Span::default(),
+            },
+            span: Span::default(),
+          },
+          CaseItem {
+            patterns: vec![StringOrWord::Word {
+              parts: vec![StringPart::new_glob("*")],
+              span: Span::default(),
+            }],
+            body: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![
+                    StringOrWord::new_word("echo"),
+                    StringOrWord::new_word("other"),
+                  ],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            span: Span::default(),
+          },
+        ],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_case_clause,
+      "case a in esac",
+      Ok(CaseClause {
+        word: StringOrWord::new_word("a"),
+        items: vec![],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_pattern,
+      "[a-z]?.\\*[x",
+      Ok(StringOrWord::Word {
+        parts: vec![
+          StringPart::new_glob("[a-z]?"),
+          StringPart::new_text(".*[x"),
+        ],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_case_clause,
+      "case in a) echo; esac",
+      Err("Expected 'in' following case word."),
+    );
+    run_test(
+      parse_case_clause,
+      "case a in ) echo; esac",
+      Err("Expected pattern in case item."),
+    );
+    run_test(
+      parse_case_clause,
+      "case a in b echo; esac",
+      Err("Expected ')' following case item pattern."),
+    );
+    run_test(
+      parse_case_clause,
+      "case a in b) echo 1 c) echo 2;; esac",
+      Err("Expected 'esac' to close case statement."),
+    );
+  }
+
+  #[test]
+  fn test_env_var() {
+    run_test(
+      parse_env_var,
+      "Name=Value",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::new_word("Value"),
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_env_var,
+      "Name='quoted value'",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::new_string("quoted value"),
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_env_var,
+      "Name=\"double quoted value\"",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::new_string("double quoted value"),
+        span: Span::default(),
+      }),
+    );
+    run_test_with_end(
+      parse_env_var,
+      "Name= command_name",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::Word {
+          parts: vec![],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }),
+      " command_name",
+    );
+
+    run_test(
+      parse_env_var,
+      "Name=$(test)",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::Word {
+          parts: vec![StringPart::Command {
+            list: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: SimpleCommand {
+                  env_vars: vec![],
+                  args: vec![StringOrWord::new_word("test")],
+                  span: Span::default(),
+                }
+                .into(),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }),
+    );
+
+    run_test(
+      parse_env_var,
+      "Name=$(OTHER=5)",
+      Ok(EnvVar {
+        name: "Name".to_string(),
+        value: StringOrWord::Word {
+          parts: vec![StringPart::Command {
+            list: SequentialList {
+              items: vec![SequentialListItem {
+                is_async: false,
+                sequence: Sequence::ShellVar(EnvVar {
+                  name: "OTHER".to_string(),
+                  value: StringOrWord::new_word("5"),
+                  span: Span::default(),
+                }),
+                span: Span::default(),
+              }],
+              span: Span::default(),
+            },
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }),
+    );
+  }
+
+  #[test]
+  fn test_single_quotes() {
+    run_test(
+      parse_string_or_word,
+      "'test'",
+      Ok(StringOrWord::new_string("test")),
+    );
+    run_test(
+      parse_string_or_word,
+      r#"'te\\'"#,
+      Ok(StringOrWord::new_string(r#"te\\"#)),
+    );
+    run_test_with_end(
+      parse_string_or_word,
+      r#"'te\'st'"#,
+      Ok(StringOrWord::new_string(r#"te\"#)),
+      "st'",
+    );
+    run_test(
+      parse_string_or_word,
+      "'  '",
+      Ok(StringOrWord::new_string("  ")),
+    );
+    run_test(
+      parse_string_or_word,
+      "'  ",
+      Err("Expected closing single quote."),
+    );
+  }
+
+  #[test]
+  fn test_double_quotes() {
+    run_test(
+      parse_string_or_word,
+      r#""  ""#,
+      Ok(StringOrWord::new_string("  ")),
+    );
+    run_test(
+      parse_string_or_word,
+      r#""test""#,
+      Ok(StringOrWord::new_string("test")),
+    );
+    run_test(
+      parse_string_or_word,
+      r#""te\"\$\`st""#,
+      Ok(StringOrWord::new_string(r#"te"$`st"#)),
+    );
+    run_test(
+      parse_quoted_string,
+      r#""  "#,
+      Err("Expected closing double quote."),
+    );
+    run_test(
+      parse_string_or_word,
+      r#""$Test""#,
+      Ok(StringOrWord::String {
+        parts: vec![StringPart::new_variable("Test")],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_string_or_word,
+      r#""$Test,$Other_Test""#,
+      Ok(StringOrWord::String {
+        parts: vec![
+          StringPart::new_variable("Test"),
+          StringPart::new_text(","),
+          StringPart::new_variable("Other_Test"),
+        ],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_quoted_string,
       r#""asdf`""#,
-      Err("Back ticks in strings is currently not supported."),
+      Err("Expected closing back tick."),
     );
 
     run_test_with_end(
-      map(parse_quoted_string, StringOrWord::String),
+      parse_string_or_word,
       r#""test" asdf"#,
       Ok(StringOrWord::new_string("test")),
       " asdf",
@@ -1413,161 +4188,1558 @@ mod test {
 
   #[test]
   fn test_parse_word() {
-    run_test(parse_word, "if", Err("Unsupported reserved word."));
-    run_test(parse_word, "$", Ok(vec![StringPart::Text("$".to_string())]));
+    // reserved words are only special in the command name position
+    run_test(parse_word, "if", Ok(vec![StringPart::new_text("if")]));
+    run_test(parse_word, "$", Ok(vec![StringPart::new_text("$")]));
     // unsupported shell variables
-    run_test(parse_word, "$$", Err("$$ is currently not supported."));
-    run_test(parse_word, "$?", Err("$? is currently not supported."));
-    run_test(parse_word, "$#", Err("$# is currently not supported."));
-    run_test(parse_word, "$*", Err("$* is currently not supported."));
+    run_test(parse_word, "$$", Ok(vec![StringPart::new_variable("$")]));
+    run_test(parse_word, "$?", Ok(vec![StringPart::new_variable("?")]));
+    run_test(
+      parse_word,
+      "a$?b",
+      Ok(vec![
+        StringPart::new_text("a"),
+        StringPart::new_variable("?"),
+        StringPart::new_text("b"),
+      ]),
+    );
+    run_test(parse_word, "$#", Ok(vec![StringPart::new_variable("#")]));
+    run_test(
+      parse_word,
+      "$@$*",
+      Ok(vec![
+        StringPart::new_variable("@"),
+        StringPart::new_variable("*"),
+      ]),
+    );
+    // an unbraced positional parameter is a single digit
+    run_test(
+      parse_word,
+      "$1abc",
+      Ok(vec![
+        StringPart::new_variable("1"),
+        StringPart::new_text("abc"),
+      ]),
+    );
+    run_test(
+      parse_word,
+      "$10",
+      Ok(vec![
+        StringPart::new_variable("1"),
+        StringPart::new_text("0"),
+      ]),
+    );
+    run_test(
+      parse_double_quoted_string,
+      r#""$1-x""#,
+      Ok(vec![
+        StringPart::new_variable("1"),
+        StringPart::new_text("-x"),
+      ]),
+    );
     run_test(
       parse_word,
       "test\\ test",
-      Ok(vec![StringPart::Text("test test".to_string())]),
+      Ok(vec![StringPart::new_text("test test")]),
+    );
+    run_test(
+      parse_word,
+      "src/**/[a-z]?.json",
+      Ok(vec![
+        StringPart::new_text("src/"),
+        StringPart::new_glob("**"),
+        StringPart::new_text("/"),
+        StringPart::new_glob("[a-z]?"),
+        StringPart::new_text(".json"),
+      ]),
+    );
+    run_test(parse_word, "\\*.ts", Ok(vec![StringPart::new_text("*.ts")]));
+    run_test(
+      parse_word,
+      "~",
+      Ok(vec![StringPart::Tilde {
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_word,
+      "~/projects",
+      Ok(vec![
+        StringPart::Tilde {
+          span: Span::default(),
+        },
+        StringPart::new_text("/projects"),
+      ]),
+    );
+    run_test(
+      parse_word,
+      "~user/a~",
+      Ok(vec![StringPart::new_text("~user/a~")]),
+    );
+    run_test(
+      parse_word,
+      "'~'/projects",
+      Ok(vec![StringPart::new_text("~/projects")]),
+    );
+    run_test(
+      parse_word,
+      "\\~/projects",
+      Ok(vec![StringPart::new_text("~/projects")]),
+    );
+    run_test(parse_word, "\\~", Ok(vec![StringPart::new_text("~")]));
+  }
+
+  #[test]
+  fn test_parameter_expansion() {
+    fn expansion(
+      name: &str,
+      op: Option<ParameterExpansionOp>,
+    ) -> Vec<StringPart> {
+      vec![StringPart::ParameterExpansion(ParameterExpansion {
+        name: name.to_string(),
+        op,
+        span: Span::default(),
+      })]
+    }
+
+    run_test(parse_word, "${VAR}", Ok(expansion("VAR", None)));
+    run_test(parse_word, "${#}", Ok(expansion("#", None)));
+    run_test(parse_word, "${10}", Ok(expansion("10", None)));
+    run_test(
+      parse_word,
+      "${#VAR}",
+      Ok(expansion("VAR", Some(ParameterExpansionOp::Length))),
+    );
+    run_test(
+      parse_word,
+      "${PORT:-8000}",
+      Ok(expansion(
+        "PORT",
+        Some(ParameterExpansionOp::UseDefault {
+          check_null: true,
+          word: vec![StringPart::new_text("8000")],
+        }),
+      )),
+    );
+    run_test(
+      parse_word,
+      "${VAR=a b $OTHER}",
+      Ok(expansion(
+        "VAR",
+        Some(ParameterExpansionOp::AssignDefault {
+          check_null: false,
+          word: vec![
+            StringPart::new_text("a b "),
+            StringPart::new_variable("OTHER"),
+          ],
+        }),
+      )),
+    );
+    run_test(
+      parse_word,
+      "${VAR:+'}'}",
+      Ok(expansion(
+        "VAR",
+        Some(ParameterExpansionOp::UseAlternative {
+          check_null: true,
+          word: vec![StringPart::new_text("}")],
+        }),
+      )),
+    );
+    run_test(
+      parse_word,
+      "${VAR?}",
+      Ok(expansion(
+        "VAR",
+        Some(ParameterExpansionOp::ErrorIfUnset {
+          check_null: false,
+          word: vec![],
+        }),
+      )),
+    );
+    run_test(
+      parse_word,
+      "${VAR##*/}",
+      Ok(expansion(
+        "VAR",
+        Some(ParameterExpansionOp::RemovePrefix {
+          longest: true,
+          pattern: vec![StringPart::new_glob("*"), StringPart::new_text("/")],
+        }),
+      )),
+    );
+    run_test(
+      parse_word,
+      "${VAR%.*}",
+      Ok(expansion(
+        "VAR",
+        Some(ParameterExpansionOp::RemoveSuffix {
+          longest: false,
+          pattern: vec![StringPart::new_text("."), StringPart::new_glob("*")],
+        }),
+      )),
+    );
+    run_test(
+      parse_double_quoted_string,
+      r#""a${VAR:-$(echo 1)}b""#,
+      Ok(vec![
+        StringPart::new_text("a"),
+        StringPart::ParameterExpansion(ParameterExpansion {
+          name: "VAR".to_string(),
+          op: Some(ParameterExpansionOp::UseDefault {
+            check_null: true,
+            word: vec![StringPart::Command {
+              list: SequentialList {
+                items: vec![SequentialListItem {
+                  is_async: false,
+                  sequence: SimpleCommand {
+                    env_vars: vec![],
+                    args: vec![
+                      StringOrWord::new_word("echo"),
+                      StringOrWord::new_word("1"),
+                    ],
+                    span: Span::default(),
+                  }
+                  .into(),
+                  span: Span::default(),
+                }],
+                span: Span::default(),
+              },
+              span: Span::default(),
+            }],
+          }),
+          span: Span::default(),
+        }),
+        StringPart::new_text("b"),
+      ]),
+    );
+    run_test(
+      parse_word,
+      "${}",
+      Err("Expected variable name in parameter expansion."),
+    );
+    run_test(
+      parse_word,
+      "${VAR",
+      Err("Expected '}' to close parameter expansion."),
+    );
+    run_test(
+      parse_word,
+      "${VAR/a/b}",
+      Err("Expected '}' to close parameter expansion."),
+    );
+  }
+
+  #[test]
+  fn test_ansi_c_quoted_string() {
+    run_test(
+      parse_word,
+      r"$'a\tb\n'",
+      Ok(vec![StringPart::new_text("a\tb\n")]),
+    );
+    run_test(
+      parse_word,
+      r"a$'\x41\u00e9\U0001F600\101\e'b",
+      Ok(vec![StringPart::new_text("aAé😀A\x1Bb")]),
+    );
+    run_test(
+      parse_word,
+      r"$'\'\\\q\x'",
+      Ok(vec![StringPart::new_text(r"'\\q\x")]),
+    );
+    run_test(
+      parse_word,
+      "$'*'$VAR",
+      Ok(vec![
+        StringPart::new_text("*"),
+        StringPart::new_variable("VAR"),
+      ]),
+    );
+    // only special outside of double quotes
+    run_test(
+      parse_double_quoted_string,
+      r#""$'\t'""#,
+      Ok(vec![StringPart::new_text(r"$'\t'")]),
+    );
+    run_test(parse_word, "$'a", Err("Expected closing single quote."));
+  }
+
+  #[test]
+  fn test_backticks() {
+    fn command(args: &[&str]) -> StringPart {
+      StringPart::Command {
+        list: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: args
+                .iter()
+                .map(|arg| StringOrWord::new_word(arg))
+                .collect(),
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }
+    }
+
+    run_test(parse_word, "`echo 1`", Ok(vec![command(&["echo", "1"])]));
+    run_test(
+      parse_word,
+      "a` echo 1 `b",
+      Ok(vec![
+        StringPart::new_text("a"),
+        command(&["echo", "1"]),
+        StringPart::new_text("b"),
+      ]),
+    );
+    run_test(
+      parse_word,
+      r"`echo \`echo 1\``",
+      Ok(vec![StringPart::Command {
+        list: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::Word {
+                  parts: vec![command(&["echo", "1"])],
+                  span: Span::default(),
+                },
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_double_quoted_string,
+      r#""`echo \"a\" \$b \\`""#,
+      Ok(vec![StringPart::Command {
+        list: SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_string("a"),
+                StringOrWord::Word {
+                  parts: vec![StringPart::new_variable("b")],
+                  span: Span::default(),
+                },
+                StringOrWord::new_word("\\"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        },
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_word,
+      r"\`echo\`",
+      Ok(vec![StringPart::new_text("`echo`")]),
+    );
+    run_test(parse_word, "`echo 1", Err("Expected closing back tick."));
+  }
+
+  #[test]
+  fn test_arithmetic_expansion() {
+    fn number(value: i64) -> Box<ArithmeticExpr> {
+      Box::new(ArithmeticExpr::Number {
+        value,
+        span: Span::default(),
+      })
+    }
+
+    fn var(name: &str) -> Box<ArithmeticExpr> {
+      Box::new(ArithmeticExpr::Variable {
+        name: name.to_string(),
+        span: Span::default(),
+      })
+    }
+
+    fn binary(
+      left: Box<ArithmeticExpr>,
+      op: BinaryArithmeticOp,
+      right: Box<ArithmeticExpr>,
+    ) -> Box<ArithmeticExpr> {
+      Box::new(ArithmeticExpr::Binary {
+        left,
+        op,
+        right,
+        span: Span::default(),
+      })
+    }
+
+    run_test(
+      parse_word,
+      "$((1 + 2))",
+      Ok(vec![StringPart::Arithmetic {
+        expr: *binary(number(1), BinaryArithmeticOp::Add, number(2)),
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_word,
+      "a$((1+2*3-4))b",
+      Ok(vec![
+        StringPart::new_text("a"),
+        StringPart::Arithmetic {
+          expr: *binary(
+            binary(
+              number(1),
+              BinaryArithmeticOp::Add,
+              binary(number(2), BinaryArithmeticOp::Multiply, number(3)),
+            ),
+            BinaryArithmeticOp::Subtract,
+            number(4),
+          ),
+          span: Span::default(),
+        },
+        StringPart::new_text("b"),
+      ]),
+    );
+    run_test(
+      parse_word,
+      "$(( (COUNT + $1) % 2 ))",
+      Ok(vec![StringPart::Arithmetic {
+        expr: *binary(
+          Box::new(ArithmeticExpr::Parentheses {
+            expr: binary(var("COUNT"), BinaryArithmeticOp::Add, var("1")),
+            span: Span::default(),
+          }),
+          BinaryArithmeticOp::Modulo,
+          number(2),
+        ),
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_word,
+      "$((-A <= 2 || !B && C != 3))",
+      Ok(vec![StringPart::Arithmetic {
+        expr: *binary(
+          binary(
+            Box::new(ArithmeticExpr::Unary {
+              op: UnaryArithmeticOp::Minus,
+              operand: var("A"),
+              span: Span::default(),
+            }),
+            BinaryArithmeticOp::LessThanOrEqual,
+            number(2),
+          ),
+          BinaryArithmeticOp::Or,
+          binary(
+            Box::new(ArithmeticExpr::Unary {
+              op: UnaryArithmeticOp::Not,
+              operand: var("B"),
+              span: Span::default(),
+            }),
+            BinaryArithmeticOp::And,
+            binary(var("C"), BinaryArithmeticOp::NotEqual, number(3)),
+          ),
+        ),
+        span: Span::default(),
+      }]),
+    );
+    run_test(
+      parse_double_quoted_string,
+      r#""$((1 == 1))""#,
+      Ok(vec![StringPart::Arithmetic {
+        expr: *binary(number(1), BinaryArithmeticOp::Equal, number(1)),
+        span: Span::default(),
+      }]),
+    );
+    run_test(parse_word, "$(())", Err("Expected arithmetic operand."));
+    run_test(parse_word, "$((1 +))", Err("Expected arithmetic operand."));
+    // without a matching `))` it's a subshell in a command substitution
+    let (input, parts) = parse_word("$((echo 1) && echo 2)").unwrap();
+    assert_eq!(input, "");
+    assert!(matches!(parts.as_slice(), [StringPart::Command { .. }]));
+    run_test(
+      parse_word,
+      "$((1 + 2)",
+      Err("Expected '))' to close arithmetic expansion."),
+    );
+    run_test(
+      parse_word,
+      "$(((1 + 2))",
+      Err("Expected '))' to close arithmetic expansion."),
+    );
+    for input in ["echo $((1 + 2)", "echo $(((1 + 2))"] {
+      let err = parse(input).err().unwrap();
+      assert_eq!(err.kind, ParseErrorKind::Unclosed);
+      assert_eq!(err.offset, 5);
+      assert_eq!(err.message, "Expected '))' to close arithmetic expansion.");
+    }
+    run_test(
+      parse_word,
+      "$((1 = 2))",
+      Err("Expected '))' to close arithmetic expansion."),
+    );
+    run_test(
+      parse_word,
+      "$((99999999999999999999))",
+      Err("Arithmetic number too large."),
+    );
+  }
+
+  #[test]
+  fn test_parse_u32() {
+    run_test(parse_u32, "999", Ok(999));
+    run_test(parse_u32, "11", Ok(11));
+    run_test(parse_u32, "0", Ok(0));
+    run_test_with_end(parse_u32, "1>", Ok(1), ">");
+    run_test(parse_u32, "-1", Err("backtrace"));
+    run_test(parse_u32, "a", Err("backtrace"));
+    run_test(
+      parse_u32,
+      "16116951273372934291112534924737",
+      Err("backtrace"),
+    );
+    run_test(parse_u32, "4294967295", Ok(4294967295));
+    run_test(parse_u32, "4294967296", Err("backtrace"));
+  }
+
+  /// Runs the combinator, comparing the result to the expected
+  /// value while ignoring the spans of the parsed nodes.
+  fn run_test<'a, T: ClearSpans + PartialEq + std::fmt::Debug>(
+    combinator: impl Fn(&'a str) -> ParseResult<'a, T>,
+    input: &'a str,
+    expected: Result<T, &str>,
+  ) {
+    run_test_with_end(combinator, input, expected, "");
+  }
+
+  fn run_test_with_end<'a, T: ClearSpans + PartialEq + std::fmt::Debug>(
+    combinator: impl Fn(&'a str) -> ParseResult<'a, T>,
+    input: &'a str,
+    expected: Result<T, &str>,
+    expected_end: &str,
+  ) {
+    match combinator(input) {
+      Ok((input, mut value)) => {
+        value.clear_spans();
+        assert_eq!(value, expected.unwrap());
+        assert_eq!(input, expected_end);
+      }
+      Err(monch::ParseError::Backtrace) => {
+        assert_eq!("backtrace", expected.err().unwrap());
+      }
+      Err(monch::ParseError::Failure(err)) => {
+        assert_eq!(
+          err.message,
+          match expected.err() {
+            Some(expected) => expected,
+            None => panic!("Got error: {}\n  {}", err.message, err.input),
+          }
+        );
+      }
+    }
+  }
+
+  /// Resets the spans within a parsed node so it can be compared
+  /// to a node constructed with the default spans.
+  trait ClearSpans {
+    fn clear_spans(&mut self);
+  }
+
+  fn span_clearer() -> SpanMapper<impl Fn(Span) -> Span> {
+    SpanMapper(|_| Span::default())
+  }
+
+  impl ClearSpans for SequentialList {
+    fn clear_spans(&mut self) {
+      SequentialList::clear_spans(self);
+    }
+  }
+
+  impl ClearSpans for Sequence {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_sequence_mut(self);
+    }
+  }
+
+  impl ClearSpans for Command {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_command_mut(self);
+    }
+  }
+
+  impl ClearSpans for ForClause {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_for_clause_mut(self);
+    }
+  }
+
+  impl ClearSpans for WhileClause {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_while_clause_mut(self);
+    }
+  }
+
+  impl ClearSpans for CaseClause {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_case_clause_mut(self);
+    }
+  }
+
+  impl ClearSpans for EnvVar {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_env_var_mut(self);
+    }
+  }
+
+  impl ClearSpans for StringOrWord {
+    fn clear_spans(&mut self) {
+      span_clearer().visit_string_or_word_mut(self);
+    }
+  }
+
+  impl ClearSpans for Vec<StringPart> {
+    fn clear_spans(&mut self) {
+      for part in self {
+        span_clearer().visit_string_part_mut(part);
+      }
+    }
+  }
+
+  impl ClearSpans for u32 {
+    fn clear_spans(&mut self) {}
+  }
+
+  #[test]
+  fn test_redirects() {
+    let expected = Ok(Command {
+      inner: CommandInner::Simple(SimpleCommand {
+        env_vars: vec![],
+        args: vec![StringOrWord::new_word("echo"), StringOrWord::new_word("1")],
+        span: Span::default(),
+      }),
+      redirects: vec![Redirect {
+        maybe_fd: None,
+        op: RedirectOp::Redirect,
+        io_file: IoFile::Word(StringOrWord::new_word("test.txt")),
+        span: Span::default(),
+      }],
+      span: Span::default(),
+    });
+
+    run_test(parse_command, "echo 1 > test.txt", expected.clone());
+    run_test(parse_command, "echo 1 >test.txt", expected.clone());
+
+    // append
+    run_test(
+      parse_command,
+      r#"command >> "test.txt""#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::Append,
+          io_file: IoFile::Word(StringOrWord::new_string("test.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+
+    // fd
+    run_test(
+      parse_command,
+      r#"command 2> test.txt"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: Some(RedirectFd::Fd(2)),
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Word(StringOrWord::new_word("test.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+
+    // both
+    run_test(
+      parse_command,
+      r#"command &> test.txt"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: Some(RedirectFd::StdoutStderr),
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Word(StringOrWord::new_word("test.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+
+    // input
+    run_test(
+      parse_command,
+      r#"cat<input.txt"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("cat")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::Input,
+          io_file: IoFile::Word(StringOrWord::new_word("input.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_command,
+      r#"cat 0< input.txt"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("cat")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: Some(RedirectFd::Fd(0)),
+          op: RedirectOp::Input,
+          io_file: IoFile::Word(StringOrWord::new_word("input.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+
+    // duplicating and closing file descriptors
+    run_test(
+      parse_command,
+      r#"command 2>&1"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: Some(RedirectFd::Fd(2)),
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Fd(1),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_command,
+      r#"command >&-"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Close,
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_command,
+      r#"command >&file.txt"#,
+      Err("Expected file descriptor or '-' following '&'."),
+    );
+    run_test(
+      parse_command,
+      r#"command >| test.txt"#,
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("command")],
+          span: Span::default(),
+        }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::Redirect,
+          io_file: IoFile::Word(StringOrWord::new_word("test.txt")),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+
+    // multiple
+    run_test(
+      parse_command,
+      "echo 1 1> stdout.txt 2>&1",
+      Ok(Command {
+        inner: CommandInner::Simple(SimpleCommand {
+          env_vars: vec![],
+          args: vec![
+            StringOrWord::new_word("echo"),
+            StringOrWord::new_word("1"),
+          ],
+          span: Span::default(),
+        }),
+        redirects: vec![
+          Redirect {
+            maybe_fd: Some(RedirectFd::Fd(1)),
+            op: RedirectOp::Redirect,
+            io_file: IoFile::Word(StringOrWord::new_word("stdout.txt")),
+            span: Span::default(),
+          },
+          Redirect {
+            maybe_fd: Some(RedirectFd::Fd(2)),
+            op: RedirectOp::Redirect,
+            io_file: IoFile::Fd(1),
+            span: Span::default(),
+          },
+        ],
+        span: Span::default(),
+      }),
+    );
+
+    // redirect in pipeline sequence command should error
+    run_test_with_end(
+      parse_sequence,
+      "echo 1 1> stdout.txt | cat",
+      Err("Redirects in pipe sequence commands are currently not supported."),
+      "echo 1 1> stdout.txt | cat",
     );
   }
 
   #[test]
-  fn test_parse_u32() {
-    run_test(parse_u32, "999", Ok(999));
-    run_test(parse_u32, "11", Ok(11));
-    run_test(parse_u32, "0", Ok(0));
-    run_test_with_end(parse_u32, "1>", Ok(1), ">");
-    run_test(parse_u32, "-1", Err("backtrace"));
-    run_test(parse_u32, "a", Err("backtrace"));
+  fn test_comments() {
+    fn echo(args: &[&str]) -> Sequence {
+      SimpleCommand {
+        env_vars: vec![],
+        args: args.iter().map(|arg| StringOrWord::new_word(arg)).collect(),
+        span: Span::default(),
+      }
+      .into()
+    }
+
     run_test(
-      parse_u32,
-      "16116951273372934291112534924737",
-      Err("backtrace"),
+      parse_sequential_list,
+      "# comment\necho a#b # c1\necho '#' \\#;# c2",
+      Ok(SequentialList {
+        items: vec![
+          SequentialListItem {
+            is_async: false,
+            sequence: echo(&["echo", "a#b"]),
+            span: Span::default(),
+          },
+          SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_string("#"),
+                StringOrWord::new_word("#"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          },
+        ],
+        span: Span::default(),
+      }),
+    );
+    run_test(
+      parse_sequential_list,
+      "echo 1 && # comment\n  echo 2",
+      Ok(SequentialList {
+        items: vec![SequentialListItem {
+          is_async: false,
+          sequence: Sequence::BooleanList(Box::new(BooleanList {
+            current: echo(&["echo", "1"]),
+            op: BooleanListOperator::And,
+            next: echo(&["echo", "2"]),
+            span: Span::default(),
+          })),
+          span: Span::default(),
+        }],
+        span: Span::default(),
+      }),
+    );
+    run_test(parse_word, "$#", Ok(vec![StringPart::new_variable("#")]));
+    assert_eq!(
+      parse("# only a comment").err().unwrap().to_string(),
+      "Empty command."
     );
-    run_test(parse_u32, "4294967295", Ok(4294967295));
-    run_test(parse_u32, "4294967296", Err("backtrace"));
-  }
-
-  fn run_test<'a, T: PartialEq + std::fmt::Debug>(
-    combinator: impl Fn(&'a str) -> ParseResult<'a, T>,
-    input: &'a str,
-    expected: Result<T, &str>,
-  ) {
-    run_test_with_end(combinator, input, expected, "");
   }
 
-  fn run_test_with_end<'a, T: PartialEq + std::fmt::Debug>(
-    combinator: impl Fn(&'a str) -> ParseResult<'a, T>,
-    input: &'a str,
-    expected: Result<T, &str>,
-    expected_end: &str,
-  ) {
-    match combinator(input) {
-      Ok((input, value)) => {
-        assert_eq!(value, expected.unwrap());
-        assert_eq!(input, expected_end);
-      }
-      Err(ParseError::Backtrace) => {
-        assert_eq!("backtrace", expected.err().unwrap());
+  #[test]
+  fn test_multi_line() {
+    fn command(args: &[&str]) -> Sequence {
+      SimpleCommand {
+        env_vars: vec![],
+        args: args.iter().map(|arg| StringOrWord::new_word(arg)).collect(),
+        span: Span::default(),
       }
-      Err(ParseError::Failure(err)) => {
-        assert_eq!(
-          err.message,
-          match expected.err() {
-            Some(err) => err,
-            None =>
-              panic!("Got error: {:#}", error_for_failure(err).err().unwrap()),
-          }
-        );
+      .into()
+    }
+
+    fn item(sequence: Sequence) -> SequentialListItem {
+      SequentialListItem {
+        is_async: false,
+        sequence,
+        span: Span::default(),
       }
     }
-  }
 
-  #[test]
-  fn test_redirects() {
-    let expected = Ok(Command {
-      inner: CommandInner::Simple(SimpleCommand {
-        env_vars: vec![],
-        args: vec![StringOrWord::new_word("echo"), StringOrWord::new_word("1")],
+    run_test(
+      parse_sequential_list,
+      "\n  echo 1\r\n\n\techo 2 &\necho \\\n  3 &&\n  echo 4 |\n\n  cat\n",
+      Ok(SequentialList {
+        items: vec![
+          item(command(&["echo", "1"])),
+          SequentialListItem {
+            is_async: true,
+            sequence: command(&["echo", "2"]),
+            span: Span::default(),
+          },
+          item(Sequence::BooleanList(Box::new(BooleanList {
+            current: command(&["echo", "3"]),
+            op: BooleanListOperator::And,
+            next: Pipeline {
+              negated: false,
+              inner: PipelineInner::PipeSequence(Box::new(PipeSequence {
+                current: Command {
+                  inner: CommandInner::Simple(SimpleCommand {
+                    env_vars: vec![],
+                    args: vec![
+                      StringOrWord::new_word("echo"),
+                      StringOrWord::new_word("4"),
+                    ],
+                    span: Span::default(),
+                  }),
+                  redirects: vec![],
+                  span: Span::default(),
+                },
+                op: PipeSequenceOperator::Stdout,
+                next: PipelineInner::Command(Command {
+                  inner: CommandInner::Simple(SimpleCommand {
+                    env_vars: vec![],
+                    args: vec![StringOrWord::new_word("cat")],
+                    span: Span::default(),
+                  }),
+                  redirects: vec![],
+                  span: Span::default(),
+                }),
+                span: Span::default(),
+              })),
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }))),
+        ],
+        span: Span::default(),
       }),
-      redirect: Some(Redirect {
-        maybe_fd: None,
-        op: RedirectOp::Redirect,
-        io_file: StringOrWord::Word(vec![StringPart::Text(
-          "test.txt".to_string(),
-        )]),
+    );
+    run_test(
+      parse_sequential_list,
+      "VAR=1\necho a\\\nb \"c\\\nd\"",
+      Ok(SequentialList {
+        items: vec![
+          item(Sequence::ShellVar(EnvVar::new(
+            "VAR".to_string(),
+            StringOrWord::new_word("1"),
+          ))),
+          item(
+            SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_word("ab"),
+                StringOrWord::new_string("cd"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+          ),
+        ],
+        span: Span::default(),
       }),
-    });
+    );
+    run_test(
+      parse_sequential_list,
+      "for i in 1 2\ndo\n  echo $i\ndone\nif true\nthen\n  echo 1\nfi",
+      Ok(SequentialList {
+        items: vec![
+          item(
+            Command {
+              inner: CommandInner::For(ForClause {
+                var_name: "i".to_string(),
+                wordlist: vec![
+                  StringOrWord::new_word("1"),
+                  StringOrWord::new_word("2"),
+                ],
+                body: SequentialList {
+                  items: vec![item(
+                    SimpleCommand {
+                      env_vars: vec![],
+                      args: vec![
+                        StringOrWord::new_word("echo"),
+                        StringOrWord::Word {
+                          parts: vec![StringPart::new_variable("i")],
+                          span: Span::default(),
+                        },
+                      ],
+                      span: Span::default(),
+                    }
+                    .into(),
+                  )],
+                  span: Span::default(),
+                },
+                span: Span::default(),
+              }),
+              redirects: vec![],
+              span: Span::default(),
+            }
+            .into(),
+          ),
+          item(
+            Command {
+              inner: CommandInner::If(IfClause {
+                condition: SequentialList {
+                  items: vec![item(command(&["true"]))],
+                  span: Span::default(),
+                },
+                body: SequentialList {
+                  items: vec![item(command(&["echo", "1"]))],
+                  span: Span::default(),
+                },
+                else_part: None,
+                span: Span::default(),
+              }),
+              redirects: vec![],
+              span: Span::default(),
+            }
+            .into(),
+          ),
+        ],
+        span: Span::default(),
+      }),
+    );
+    assert_eq!(
+      parse("echo 1\n&& echo 2").err().unwrap().to_string(),
+      concat!("Unexpected character.\n", "  && echo 2\n", "  ~"),
+    );
+  }
 
-    run_test(parse_command, "echo 1 > test.txt", expected.clone());
-    run_test(parse_command, "echo 1 >test.txt", expected.clone());
+  #[test]
+  fn test_here_docs() {
+    // the bodies are parsed by the list following the end of the line
+    fn parse_first_command(input: &str) -> ParseResult<Command> {
+      let (input, mut list) = parse_sequential_list(input)?;
+      match list.items.remove(0).sequence {
+        Sequence::Pipeline(Pipeline {
+          inner: PipelineInner::Command(command),
+          ..
+        }) => Ok((input, command)),
+        _ => unreachable!(),
+      }
+    }
 
-    // append
     run_test(
-      parse_command,
-      r#"command >> "test.txt""#,
+      parse_first_command,
+      "cat <<EOF > out.txt\nhello $NAME\n\\$HOME \\\\ \"q\"\nEOF\necho 1",
       Ok(Command {
         inner: CommandInner::Simple(SimpleCommand {
           env_vars: vec![],
-          args: vec![StringOrWord::new_word("command")],
-        }),
-        redirect: Some(Redirect {
-          maybe_fd: None,
-          op: RedirectOp::Append,
-          io_file: StringOrWord::String(vec![StringPart::Text(
-            "test.txt".to_string(),
-          )]),
+          args: vec![StringOrWord::new_word("cat")],
+          span: Span::default(),
         }),
+        redirects: vec![
+          Redirect {
+            maybe_fd: None,
+            op: RedirectOp::HereDoc,
+            io_file: IoFile::HereDoc(HereDoc {
+              delimiter: "EOF".to_string(),
+              is_quoted: false,
+              strip_tabs: false,
+              body: Some(vec![
+                StringPart::new_text("hello "),
+                StringPart::new_variable("NAME"),
+                StringPart::new_text("\n$HOME \\ \"q\"\n"),
+              ]),
+              span: Span::default(),
+            }),
+            span: Span::default(),
+          },
+          Redirect {
+            maybe_fd: None,
+            op: RedirectOp::Redirect,
+            io_file: IoFile::Word(StringOrWord::new_word("out.txt")),
+            span: Span::default(),
+          },
+        ],
+        span: Span::default(),
       }),
     );
 
-    // fd
+    // quoted delimiter and stripped tabs
     run_test(
-      parse_command,
-      r#"command 2> test.txt"#,
+      parse_first_command,
+      "cat <<- 'EOF'\n\thello $NAME\n\tEOF",
       Ok(Command {
         inner: CommandInner::Simple(SimpleCommand {
           env_vars: vec![],
-          args: vec![StringOrWord::new_word("command")],
-        }),
-        redirect: Some(Redirect {
-          maybe_fd: Some(RedirectFd::Fd(2)),
-          op: RedirectOp::Redirect,
-          io_file: StringOrWord::Word(vec![StringPart::Text(
-            "test.txt".to_string(),
-          )]),
+          args: vec![StringOrWord::new_word("cat")],
+          span: Span::default(),
         }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::HereDoc,
+          io_file: IoFile::HereDoc(HereDoc {
+            delimiter: "EOF".to_string(),
+            is_quoted: true,
+            strip_tabs: true,
+            body: Some(vec![StringPart::new_text("hello $NAME\n")]),
+            span: Span::default(),
+          }),
+          span: Span::default(),
+        }],
+        span: Span::default(),
       }),
     );
 
-    // both
+    // here-string
     run_test(
       parse_command,
-      r#"command &> test.txt"#,
+      "cat <<< \"$NAME\"",
       Ok(Command {
         inner: CommandInner::Simple(SimpleCommand {
           env_vars: vec![],
-          args: vec![StringOrWord::new_word("command")],
-        }),
-        redirect: Some(Redirect {
-          maybe_fd: Some(RedirectFd::StdoutStderr),
-          op: RedirectOp::Redirect,
-          io_file: StringOrWord::Word(vec![StringPart::Text(
-            "test.txt".to_string(),
-          )]),
+          args: vec![StringOrWord::new_word("cat")],
+          span: Span::default(),
         }),
+        redirects: vec![Redirect {
+          maybe_fd: None,
+          op: RedirectOp::HereString,
+          io_file: IoFile::Word(StringOrWord::String {
+            parts: vec![StringPart::new_variable("NAME")],
+            span: Span::default(),
+          }),
+          span: Span::default(),
+        }],
+        span: Span::default(),
       }),
     );
 
-    run_test_with_end(
+    run_test(
       parse_command,
-      "echo 1 1> stdout.txt 2> stderr.txt",
-      Err("Multiple redirects are currently not supported."),
-      "1> stdout.txt 2> stderr.txt",
+      "cat <<",
+      Err("Expected delimiter following here-document redirect."),
+    );
+    run_test(
+      parse_first_command,
+      "cat <<EOF\ntext\nEOF2\n",
+      Err("Expected 'EOF' to close here-document."),
     );
 
-    // redirect in pipeline sequence command should error
-    run_test_with_end(
-      parse_sequence,
-      "echo 1 1> stdout.txt | cat",
-      Err("Redirects in pipe sequence commands are currently not supported."),
-      "echo 1 1> stdout.txt | cat",
+    // the bodies of all the here-documents on the line follow it in order
+    fn here_doc_bodies(input: &str) -> Vec<String> {
+      #[derive(Default)]
+      struct HereDocBodies(Vec<String>);
+
+      impl Visit for HereDocBodies {
+        fn visit_here_doc(&mut self, here_doc: &HereDoc) {
+          self.0.push(match here_doc.body.as_deref() {
+            Some([StringPart::Text { value, .. }]) => value.clone(),
+            _ => unreachable!(),
+          });
+        }
+      }
+
+      let mut bodies = HereDocBodies::default();
+      bodies.visit_sequential_list(&parse(input).unwrap());
+      bodies.0
+    }
+
+    assert_eq!(
+      here_doc_bodies("cat <<A; cat <<B\na\nA\nb\nB\ncat <<C\n\nc\nC"),
+      vec!["a\n", "b\n", "\nc\n"],
+    );
+    assert_eq!(
+      here_doc_bodies(
+        "cat <<A && { cat <<B; } | cat <<C\na\nA\nb\nB\nc\nC\necho 1",
+      ),
+      vec!["a\n", "b\n", "c\n"],
+    );
+    assert_eq!(
+      here_doc_bodies("if true; then\n  cat <<A; cat <<B\na\nA\nb\nB\nfi"),
+      vec!["a\n", "b\n"],
+    );
+    assert_eq!(
+      parse("cat <<A; cat <<B\na\nA\n").err().unwrap().message,
+      "Expected 'B' to close here-document.",
+    );
+    // a here-document in a nested list has its body parsed first
+    assert_eq!(
+      here_doc_bodies("cat $(cat <<A\na\nA\n) <<B\nb\nB"),
+      vec!["a\n", "b\n"],
+    );
+    assert_eq!(
+      parse("cat <<EOF && echo 1").err().unwrap().message,
+      "Expected end of line following here-document redirect.",
+    );
+    // the body is missing when the line has an error
+    let result = parse_with_recovery("cat <<EOF");
+    assert_eq!(result.diagnostics.len(), 1);
+    match &result.list.items[0].sequence {
+      Sequence::Pipeline(Pipeline {
+        inner: PipelineInner::Command(command),
+        ..
+      }) => match &command.redirects[0].io_file {
+        IoFile::HereDoc(here_doc) => assert_eq!(here_doc.body, None),
+        _ => unreachable!(),
+      },
+      _ => unreachable!(),
+    }
+  }
+
+  #[test]
+  fn test_parse_with_recovery() {
+    let input = concat!(
+      "echo 1 && )\n",
+      "echo 2; ; echo 3\n",
+      "echo 4 # comment\n",
+      "fi",
+    );
+    let result = parse_with_recovery(input);
+    assert_eq!(
+      result.diagnostics,
+      vec![
+        ParseDiagnostic {
+          kind: ParseErrorKind::Expected,
+          message: "Expected command following boolean operator.".to_string(),
+          offset: 10,
+          length: 1,
+          severity: DiagnosticSeverity::Error,
+        },
+        ParseDiagnostic {
+          kind: ParseErrorKind::UnexpectedChar,
+          message: "Unexpected character.".to_string(),
+          offset: 20,
+          length: 1,
+          severity: DiagnosticSeverity::Error,
+        },
+        ParseDiagnostic {
+          kind: ParseErrorKind::UnexpectedReservedWord,
+          message: "Unexpected reserved word.".to_string(),
+          offset: 46,
+          length: 2,
+          severity: DiagnosticSeverity::Error,
+        },
+      ],
+    );
+    let span = result.list.items[1].span;
+    assert_eq!(&input[span.start..span.end], "echo 4");
+    let mut list = result.list;
+    list.clear_spans();
+    assert_eq!(
+      list,
+      SequentialList {
+        items: vec![
+          SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_word("2"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          },
+          SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::new_word("4"),
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          },
+        ],
+        span: Span::default(),
+      },
+    );
+
+    // the here-document's lines are parsed separately
+    assert_eq!(
+      parse_with_recovery("cat <<EOF\n${A\nEOF").diagnostics,
+      vec![ParseDiagnostic {
+        kind: ParseErrorKind::Unclosed,
+        message: "Expected '}' to close parameter expansion.".to_string(),
+        offset: 10,
+        length: 1,
+        severity: DiagnosticSeverity::Error,
+      }],
+    );
+
+    let result = parse_with_recovery(" \n# comment\n");
+    assert!(result.list.items.is_empty());
+    assert_eq!(
+      result.diagnostics,
+      vec![ParseDiagnostic {
+        kind: ParseErrorKind::EmptyCommand,
+        message: "Empty command.".to_string(),
+        offset: 0,
+        length: 0,
+        severity: DiagnosticSeverity::Warning,
+      }],
+    );
+    assert_eq!(
+      parse(" \n# comment\n").err().unwrap().kind,
+      ParseErrorKind::EmptyCommand,
+    );
+
+    // only errors are reported when nothing could be parsed
+    let result = parse_with_recovery("fi");
+    assert_eq!(result.diagnostics.len(), 1);
+    assert_eq!(result.diagnostics[0].severity, DiagnosticSeverity::Error);
+  }
+
+  #[test]
+  fn test_spans() {
+    struct SpanTexts<'a> {
+      input: &'a str,
+      texts: Vec<&'a str>,
+    }
+
+    impl<'a> Visit for SpanTexts<'a> {
+      fn visit_span(&mut self, span: &Span) {
+        self.texts.push(&self.input[span.start..span.end]);
+      }
+    }
+
+    fn span_texts(input: &str) -> Vec<&str> {
+      let mut visitor = SpanTexts {
+        input,
+        texts: Vec::new(),
+      };
+      visitor.visit_sequential_list(&parse(input).unwrap());
+      let mut texts = visitor.texts;
+      // parent nodes often have the same span as their only child
+      texts.dedup();
+      texts
+    }
+
+    // nodes at different positions aren't equal
+    assert_ne!(parse("echo 1").unwrap(), parse("echo  1").unwrap());
+
+    assert_eq!(
+      span_texts("A=1 echo $HOME 'a b' > out.txt && ! cat a|b &"),
+      vec![
+        "A=1 echo $HOME 'a b' > out.txt && ! cat a|b &",
+        "A=1 echo $HOME 'a b' > out.txt && ! cat a|b",
+        "A=1 echo $HOME 'a b' > out.txt",
+        "A=1 echo $HOME 'a b'",
+        "A=1",
+        "1",
+        "echo",
+        "$HOME",
+        "'a b'",
+        "a b",
+        "> out.txt",
+        "out.txt",
+        "! cat a|b",
+        "cat a|b",
+        "cat a",
+        "cat",
+        "a",
+        "b",
+      ],
+    );
+    assert_eq!(
+      span_texts("  if true; then echo `echo \\$A`; fi # comment"),
+      vec![
+        "if true; then echo `echo \\$A`; fi",
+        "true",
+        "echo `echo \\$A`",
+        "echo",
+        "`echo \\$A`",
+      ],
+    );
+    assert_eq!(
+      span_texts(concat!(
+        "f() { cat <<EOF\nhi $X\nEOF\n}\n",
+        "for i in *.ts; do echo ~/$((i + 1)); done",
+      )),
+      vec![
+        "f() { cat <<EOF\nhi $X\nEOF\n}\nfor i in *.ts; do echo ~/$((i + 1)); done",
+        "f() { cat <<EOF\nhi $X\nEOF\n}",
+        "cat <<EOF\nhi $X\nEOF",
+        "cat <<EOF",
+        "cat",
+        "<<EOF",
+        "hi $X\nEOF",
+        "hi ",
+        "$X",
+        "\n",
+        "for i in *.ts; do echo ~/$((i + 1)); done",
+        "*.ts",
+        "*",
+        ".ts",
+        "echo ~/$((i + 1))",
+        "echo",
+        "~/$((i + 1))",
+        "~",
+        "/",
+        "$((i + 1))",
+        "i + 1",
+        "i",
+        "1",
+      ],
+    );
+  }
+
+  #[cfg(feature = "serialization")]
+  #[test]
+  fn test_serialization_format() {
+    use serde_json::json;
+
+    let list = parse("echo ~/$X\"*\" ${Y} $((-1 * (N + 2)))").unwrap();
+    let command = match &list.items[0].sequence {
+      Sequence::Pipeline(Pipeline {
+        inner:
+          PipelineInner::Command(Command {
+            inner: CommandInner::Simple(command),
+            ..
+          }),
+        ..
+      }) => command,
+      _ => unreachable!(),
+    };
+    let span = |start: usize, end: usize| json!({ "start": start, "end": end });
+    assert_eq!(
+      serde_json::to_value(ParameterExpansionOp::UseDefault {
+        check_null: true,
+        word: vec![],
+      })
+      .unwrap(),
+      json!({ "kind": "useDefault", "checkNull": true, "word": [] }),
+    );
+    assert_eq!(
+      serde_json::to_value(&command.args[1..]).unwrap(),
+      json!([
+        {
+          "kind": "word",
+          "value": [
+            { "kind": "tilde", "span": span(5, 6) },
+            { "kind": "text", "value": "/", "span": span(6, 7) },
+            { "kind": "variable", "value": "X", "span": span(7, 9) },
+            { "kind": "text", "value": "*", "span": span(10, 11) },
+          ],
+          "span": span(5, 12),
+        },
+        {
+          "kind": "word",
+          "value": [{
+            "kind": "parameterExpansion",
+            "value": { "name": "Y", "op": null, "span": span(13, 17) },
+            "span": span(13, 17),
+          }],
+          "span": span(13, 17),
+        },
+        {
+          "kind": "word",
+          "value": [{
+            "kind": "arithmetic",
+            "value": {
+              "kind": "binary",
+              "value": {
+                "left": {
+                  "kind": "unary",
+                  "value": {
+                    "op": "minus",
+                    "operand": {
+                      "kind": "number",
+                      "value": 1,
+                      "span": span(22, 23),
+                    },
+                  },
+                  "span": span(21, 23),
+                },
+                "op": "multiply",
+                "right": {
+                  "kind": "parentheses",
+                  "value": {
+                    "kind": "binary",
+                    "value": {
+                      "left": {
+                        "kind": "variable",
+                        "value": "N",
+                        "span": span(27, 28),
+                      },
+                      "op": "add",
+                      "right": {
+                        "kind": "number",
+                        "value": 2,
+                        "span": span(31, 32),
+                      },
+                    },
+                    "span": span(27, 32),
+                  },
+                  "span": span(26, 33),
+                },
+              },
+              "span": span(21, 33),
+            },
+            "span": span(18, 35),
+          }],
+          "span": span(18, 35),
+        },
+      ]),
     );
   }
 }
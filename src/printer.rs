@@ -0,0 +1,1008 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use crate::parser::*;
+
+/// Prints the list as shell source that parses back to an equal list.
+///
+/// The output is canonical, so lists that only differ in formatting
+/// (ex. whitespace or comments) print the same text. The round trip
+/// only holds up to spans, which are compared by `PartialEq` and point
+/// into the original text, so use `SequentialList::clear_spans` on both
+/// lists before comparing them.
+pub fn print(list: &SequentialList) -> String {
+  let mut printer = Printer::default();
+  printer.write_list(list);
+  printer.write_here_doc_bodies();
+  printer.text
+}
+
+/// Prints an item the same way as when it's within a list.
+///
+/// Also returns the bodies of the here-documents on the item's
+/// line, which must be written on the lines following it.
+pub(crate) fn print_item(item: &SequentialListItem) -> (String, Vec<String>) {
+  let mut printer = Printer::default();
+  printer.write_sequence(&item.sequence);
+  if item.is_async {
+    printer.write(" &");
+  }
+  (printer.text, printer.here_doc_bodies)
+}
+
+/// Prints a sequence the same way as when it's within a list, also
+/// returning the bodies of the here-documents on its last line.
+pub(crate) fn print_sequence(sequence: &Sequence) -> (String, Vec<String>) {
+  let mut printer = Printer::default();
+  printer.write_sequence(sequence);
+  (printer.text, printer.here_doc_bodies)
+}
+
+/// Prints a command the same way as when it's within a list, also
+/// returning the bodies of the here-documents on its last line.
+pub(crate) fn print_command(command: &Command) -> (String, Vec<String>) {
+  let mut printer = Printer::default();
+  printer.write_command(command);
+  (printer.text, printer.here_doc_bodies)
+}
+
+/// Prints the redirects following a command (ex. ` > out.txt`), also
+/// returning the bodies of their here-documents.
+pub(crate) fn print_redirects(redirects: &[Redirect]) -> (String, Vec<String>) {
+  let mut printer = Printer::default();
+  printer.write_redirects(redirects);
+  (printer.text, printer.here_doc_bodies)
+}
+
+/// Prints a word that's an argument of a command.
+pub(crate) fn print_argument(word: &StringOrWord) -> String {
+  let mut printer = Printer::default();
+  printer.write_string_or_word(word, WordPosition::Argument);
+  printer.text
+}
+
+/// Prints a pattern of a case item.
+pub(crate) fn print_case_pattern(word: &StringOrWord) -> String {
+  let mut printer = Printer::default();
+  printer.write_string_or_word(word, WordPosition::CasePattern);
+  printer.text
+}
+
+#[derive(Default)]
+struct Printer {
+  text: String,
+  /// Bodies of the here-documents on the current line, which are
+  /// written on the lines following it.
+  here_doc_bodies: Vec<String>,
+}
+
+impl Printer {
+  fn write(&mut self, text: &str) {
+    self.text.push_str(text);
+  }
+
+  /// Writes the bodies of the here-documents on the current line,
+  /// returning if there were any.
+  fn write_here_doc_bodies(&mut self) -> bool {
+    if self.here_doc_bodies.is_empty() {
+      return false;
+    }
+    for body in std::mem::take(&mut self.here_doc_bodies) {
+      self.write("\n");
+      self.write(&body);
+    }
+    true
+  }
+
+  /// Writes the separator following an item in a list.
+  fn write_separator(&mut self, is_async: bool) {
+    if self.write_here_doc_bodies() {
+      self.write("\n");
+    } else if is_async {
+      self.write(" ");
+    } else {
+      self.write("; ");
+    }
+  }
+
+  /// Ends the current line if it has here-documents, which is
+  /// necessary before anything that closes a list (ex. `)`).
+  fn write_line_break_after_here_docs(&mut self) {
+    if self.write_here_doc_bodies() {
+      self.write("\n");
+    }
+  }
+
+  fn write_list(&mut self, list: &SequentialList) {
+    for (i, item) in list.items.iter().enumerate() {
+      if i > 0 {
+        self.write_separator(list.items[i - 1].is_async);
+      }
+      self.write_sequence(&item.sequence);
+      if item.is_async {
+        self.write(" &");
+      }
+    }
+  }
+
+  /// Writes a list that's followed by a reserved word or
+  /// closing brace (ex. the condition of an `if` statement).
+  fn write_terminated_list(&mut self, list: &SequentialList) {
+    self.write_list(list);
+    let is_async = matches!(list.items.last(), Some(item) if item.is_async);
+    self.write_separator(is_async);
+  }
+
+  fn write_sequence(&mut self, sequence: &Sequence) {
+    match sequence {
+      Sequence::ShellVar(env_var) => self.write_env_var(env_var),
+      Sequence::Pipeline(pipeline) => {
+        if pipeline.negated {
+          self.write("! ");
+        }
+        self.write_pipeline_inner(&pipeline.inner);
+      }
+      Sequence::BooleanList(list) => {
+        self.write_sequence(&list.current);
+        self.write(" ");
+        self.write(list.op.as_str());
+        self.write(" ");
+        self.write_sequence(&list.next);
+      }
+      Sequence::FunctionDefinition(definition) => {
+        self.write(&definition.name);
+        self.write("() { ");
+        self.write_terminated_list(&definition.body);
+        self.write("}");
+      }
+    }
+  }
+
+  fn write_pipeline_inner(&mut self, inner: &PipelineInner) {
+    match inner {
+      PipelineInner::Command(command) => self.write_command(command),
+      PipelineInner::PipeSequence(sequence) => {
+        self.write_command(&sequence.current);
+        self.write(match sequence.op {
+          PipeSequenceOperator::Stdout => " | ",
+          PipeSequenceOperator::StdoutStderr => " |& ",
+        });
+        self.write_pipeline_inner(&sequence.next);
+      }
+    }
+  }
+
+  fn write_command(&mut self, command: &Command) {
+    match &command.inner {
+      CommandInner::Simple(command) => self.write_simple_command(command),
+      CommandInner::Subshell(list) => {
+        self.write("(");
+        self.write_list(list);
+        self.write_line_break_after_here_docs();
+        self.write(")");
+      }
+      CommandInner::Group(list) => {
+        self.write("{ ");
+        self.write_terminated_list(list);
+        self.write("}");
+      }
+      CommandInner::If(if_clause) => {
+        self.write("if ");
+        self.write_if_clause_body(if_clause);
+        self.write("fi");
+      }
+      CommandInner::For(for_clause) => {
+        self.write("for ");
+        self.write(&for_clause.var_name);
+        self.write(" in");
+        for word in &for_clause.wordlist {
+          self.write(" ");
+          self.write_string_or_word(word, WordPosition::Argument);
+        }
+        self.write("; do ");
+        self.write_terminated_list(&for_clause.body);
+        self.write("done");
+      }
+      CommandInner::While(while_clause) => {
+        self.write(if while_clause.is_until {
+          "until "
+        } else {
+          "while "
+        });
+        self.write_terminated_list(&while_clause.condition);
+        self.write("do ");
+        self.write_terminated_list(&while_clause.body);
+        self.write("done");
+      }
+      CommandInner::Case(case_clause) => {
+        self.write("case ");
+        self.write_string_or_word(&case_clause.word, WordPosition::Argument);
+        self.write(" in ");
+        for item in &case_clause.items {
+          for (i, pattern) in item.patterns.iter().enumerate() {
+            if i > 0 {
+              self.write("|");
+            }
+            self.write_string_or_word(pattern, WordPosition::CasePattern);
+          }
+          self.write(") ");
+          self.write_list(&item.body);
+          self.write_line_break_after_here_docs();
+          self.write(";; ");
+        }
+        self.write("esac");
+      }
+    }
+    self.write_redirects(&command.redirects);
+  }
+
+  fn write_redirects(&mut self, redirects: &[Redirect]) {
+    for redirect in redirects {
+      self.write(" ");
+      self.write_redirect(redirect);
+    }
+    for redirect in redirects {
+      if let IoFile::HereDoc(here_doc) = &redirect.io_file {
+        self.here_doc_bodies.push(print_here_doc_body(here_doc));
+      }
+    }
+  }
+
+  /// Writes everything in an `if` statement following the `if` or `elif`.
+  fn write_if_clause_body(&mut self, if_clause: &IfClause) {
+    self.write_terminated_list(&if_clause.condition);
+    self.write("then ");
+    self.write_terminated_list(&if_clause.body);
+    match &if_clause.else_part {
+      Some(ElsePart::Elif(if_clause)) => {
+        self.write("elif ");
+        self.write_if_clause_body(if_clause);
+      }
+      Some(ElsePart::Else(list)) => {
+        self.write("else ");
+        self.write_terminated_list(list);
+      }
+      None => {}
+    }
+  }
+
+  fn write_simple_command(&mut self, command: &SimpleCommand) {
+    for env_var in &command.env_vars {
+      self.write_env_var(env_var);
+      self.write(" ");
+    }
+    for (i, arg) in command.args.iter().enumerate() {
+      if i > 0 {
+        self.write(" ");
+        self.write_string_or_word(arg, WordPosition::Argument);
+      } else {
+        self.write_string_or_word(arg, WordPosition::CommandName);
+      }
+    }
+  }
+
+  fn write_env_var(&mut self, env_var: &EnvVar) {
+    self.write(&env_var.name);
+    self.write("=");
+    self.write_string_or_word(&env_var.value, WordPosition::Argument);
+  }
+
+  fn write_redirect(&mut self, redirect: &Redirect) {
+    match &redirect.maybe_fd {
+      Some(RedirectFd::Fd(fd)) => self.write(&fd.to_string()),
+      Some(RedirectFd::StdoutStderr) => self.write("&"),
+      None => {}
+    }
+    self.write(match redirect.op {
+      RedirectOp::Redirect => ">",
+      RedirectOp::Append => ">>",
+      RedirectOp::Input => "<",
+      RedirectOp::HereDoc => "<<",
+      RedirectOp::HereString => "<<<",
+    });
+    match &redirect.io_file {
+      IoFile::Word(word) => {
+        self.write(" ");
+        self.write_string_or_word(word, WordPosition::Argument);
+      }
+      IoFile::Fd(fd) => {
+        self.write("&");
+        self.write(&fd.to_string());
+      }
+      IoFile::Close => self.write("&-"),
+      IoFile::HereDoc(here_doc) => {
+        if here_doc.strip_tabs {
+          self.write("-");
+        }
+        if here_doc.is_quoted {
+          self.write(&format!("'{}'", here_doc.delimiter));
+        } else {
+          self.write(&here_doc.delimiter);
+        }
+      }
+    }
+  }
+
+  fn write_string_or_word(
+    &mut self,
+    value: &StringOrWord,
+    position: WordPosition,
+  ) {
+    let text = match value {
+      StringOrWord::Word { parts, .. } => print_word(parts, position),
+      StringOrWord::String { parts, .. } => print_string(parts),
+    };
+    self.write(&text);
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WordPosition {
+  /// The first word of a command, where reserved words and
+  /// assignments have a special meaning.
+  CommandName,
+  /// A pattern of a case item, where `esac` ends the case statement.
+  CasePattern,
+  Argument,
+}
+
+/// Prints the parts of an unquoted word.
+///
+/// Within a word, the parser combines text with the text directly before
+/// it unless it's quoted, so text following other text is always quoted
+/// and other text is only quoted using ANSI-C quoting (ex. `$'a b'`).
+fn print_word(parts: &[StringPart], position: WordPosition) -> String {
+  let mut text = String::new();
+  for (i, part) in parts.iter().enumerate() {
+    let previous = if i > 0 { parts.get(i - 1) } else { None };
+    match part {
+      StringPart::Text { value, .. } => match previous {
+        Some(StringPart::Text { .. }) => {
+          // quoting the start of the text separates it from the
+          // previous text, then the rest is combined with it
+          let (quoted, rest) = match value.find('\'') {
+            Some(0) => ("\"'\"".to_string(), &value[1..]),
+            Some(index) => (format!("'{}'", &value[..index]), &value[index..]),
+            None => (format!("'{}'", value), ""),
+          };
+          text.push_str(&quoted);
+          if !rest.is_empty() {
+            text.push_str(&ansi_c_quote(rest));
+          }
+        }
+        _ => {
+          let continues_variable_name = matches!(
+            previous,
+            Some(StringPart::Variable { name, .. })
+              if variable_name_continues(name, value)
+          );
+          let is_safe = !value.is_empty()
+            && value.chars().all(is_safe_unquoted_char)
+            && !continues_variable_name
+            && (i > 0 || !is_special_word_start(value, parts.len(), position));
+          if is_safe {
+            text.push_str(value);
+          } else {
+            text.push_str(&ansi_c_quote(value));
+          }
+        }
+      },
+      StringPart::Glob { value, .. } => text.push_str(value),
+      StringPart::Tilde { .. } => text.push('~'),
+      part => text.push_str(&print_expansion(part)),
+    }
+  }
+  text
+}
+
+/// Prints the parts of a quoted string.
+///
+/// Each quoted string in the source becomes separate parts, so the
+/// parts are grouped into as few quoted strings as possible while
+/// starting a new one wherever a part must stay separate.
+fn print_string(parts: &[StringPart]) -> String {
+  let mut groups: Vec<Vec<&StringPart>> = Vec::new();
+  for part in parts {
+    let starts_group = match (groups.last().and_then(|g| g.last()), part) {
+      (None, _) => true,
+      (Some(StringPart::Text { .. }), StringPart::Text { .. }) => true,
+      (
+        Some(StringPart::Variable { name, .. }),
+        StringPart::Text { value, .. },
+      ) => variable_name_continues(name, value),
+      (Some(StringPart::Text { value, .. }), _) => {
+        !can_double_quote_text(value)
+      }
+      (_, StringPart::Text { value, .. }) => !can_double_quote_text(value),
+      _ => false,
+    };
+    if starts_group {
+      groups.push(Vec::new());
+    }
+    groups.last_mut().unwrap().push(part);
+  }
+  if groups.is_empty() {
+    return "\"\"".to_string();
+  }
+
+  let mut text = String::new();
+  for group in groups {
+    match group.as_slice() {
+      [StringPart::Text { value, .. }] if !value.contains('\'') => {
+        text.push('\'');
+        text.push_str(value);
+        text.push('\'');
+      }
+      group => {
+        text.push('"');
+        for part in group {
+          match part {
+            StringPart::Text { value, .. } => {
+              let mut previous = None;
+              for c in value.chars() {
+                if matches!(c, '$' | '`' | '"')
+                  || c == '\'' && previous == Some('\\')
+                {
+                  text.push('\\');
+                }
+                text.push(c);
+                previous = Some(c);
+              }
+            }
+            StringPart::Glob { value, .. } => text.push_str(value),
+            StringPart::Tilde { .. } => text.push('~'),
+            part => text.push_str(&print_expansion(part)),
+          }
+        }
+        text.push('"');
+      }
+    }
+  }
+  text
+}
+
+/// Prints the body of a here-document along with its closing delimiter.
+fn print_here_doc_body(here_doc: &HereDoc) -> String {
+  let mut body = String::new();
+  for part in here_doc.body.iter().flatten() {
+    match part {
+      StringPart::Text { value, .. } if here_doc.is_quoted => {
+        body.push_str(value)
+      }
+      StringPart::Text { value, .. } => {
+        for c in value.chars() {
+          if matches!(c, '\\' | '$' | '`') {
+            body.push('\\');
+          }
+          body.push(c);
+        }
+      }
+      part => body.push_str(&print_expansion(part)),
+    }
+  }
+  if !body.is_empty() && !body.ends_with('\n') {
+    body.push('\n');
+  }
+  body.push_str(&here_doc.delimiter);
+  body
+}
+
+/// Prints a part that's expanded the same way within and
+/// outside of quotes (ex. `$VAR` or `$(command)`).
+fn print_expansion(part: &StringPart) -> String {
+  match part {
+    StringPart::Variable { name, .. } => format!("${}", name),
+    StringPart::Command { list, .. } => {
+      let mut printer = Printer::default();
+      printer.write_list(list);
+      printer.write_line_break_after_here_docs();
+      // `$((` starts an arithmetic expansion
+      if printer.text.starts_with('(') {
+        format!("$( {})", printer.text)
+      } else {
+        format!("$({})", printer.text)
+      }
+    }
+    StringPart::ParameterExpansion(expansion) => {
+      print_parameter_expansion(expansion)
+    }
+    StringPart::Arithmetic { expr, .. } => {
+      format!("$(({}))", print_arithmetic_expr(expr))
+    }
+    StringPart::Text { value, .. } | StringPart::Glob { value, .. } => {
+      value.clone()
+    }
+    StringPart::Tilde { .. } => "~".to_string(),
+  }
+}
+
+fn print_parameter_expansion(expansion: &ParameterExpansion) -> String {
+  let (op, word) = match &expansion.op {
+    None => ("", None),
+    Some(ParameterExpansionOp::Length) => {
+      return format!("${{#{}}}", expansion.name);
+    }
+    Some(ParameterExpansionOp::UseDefault { check_null, word }) => {
+      (if *check_null { ":-" } else { "-" }, Some(word))
+    }
+    Some(ParameterExpansionOp::AssignDefault { check_null, word }) => {
+      (if *check_null { ":=" } else { "=" }, Some(word))
+    }
+    Some(ParameterExpansionOp::UseAlternative { check_null, word }) => {
+      (if *check_null { ":+" } else { "+" }, Some(word))
+    }
+    Some(ParameterExpansionOp::ErrorIfUnset { check_null, word }) => {
+      (if *check_null { ":?" } else { "?" }, Some(word))
+    }
+    Some(ParameterExpansionOp::RemovePrefix { longest, pattern }) => {
+      (if *longest { "##" } else { "#" }, Some(pattern))
+    }
+    Some(ParameterExpansionOp::RemoveSuffix { longest, pattern }) => {
+      (if *longest { "%%" } else { "%" }, Some(pattern))
+    }
+  };
+  let word = word
+    .map(|word| print_word(word, WordPosition::Argument))
+    .unwrap_or_default();
+  format!("${{{}{}{}}}", expansion.name, op, word)
+}
+
+fn print_arithmetic_expr(expr: &ArithmeticExpr) -> String {
+  match expr {
+    // a negative number can only be constructed in code, so keep
+    // it a single operand (ex. `2 - (-1)` rather than `2 - -1`)
+    ArithmeticExpr::Number { value, .. } if *value < 0 => {
+      format!("({})", value)
+    }
+    ArithmeticExpr::Number { value, .. } => value.to_string(),
+    ArithmeticExpr::Variable { name, .. } => {
+      if name.chars().all(is_valid_env_var_char) {
+        name.clone()
+      } else {
+        format!("${}", name)
+      }
+    }
+    ArithmeticExpr::Parentheses { expr, .. } => {
+      format!("({})", print_arithmetic_expr(expr))
+    }
+    ArithmeticExpr::Unary { op, operand, .. } => {
+      let op = match op {
+        UnaryArithmeticOp::Plus => "+",
+        UnaryArithmeticOp::Minus => "-",
+        UnaryArithmeticOp::Not => "!",
+      };
+      format!(
+        "{}{}",
+        op,
+        print_arithmetic_operand(operand, UNARY_ARITHMETIC_PRECEDENCE)
+      )
+    }
+    ArithmeticExpr::Binary {
+      left, op, right, ..
+    } => {
+      let precedence = op.precedence();
+      let op = match op {
+        BinaryArithmeticOp::Add => "+",
+        BinaryArithmeticOp::Subtract => "-",
+        BinaryArithmeticOp::Multiply => "*",
+        BinaryArithmeticOp::Divide => "/",
+        BinaryArithmeticOp::Modulo => "%",
+        BinaryArithmeticOp::LessThan => "<",
+        BinaryArithmeticOp::LessThanOrEqual => "<=",
+        BinaryArithmeticOp::GreaterThan => ">",
+        BinaryArithmeticOp::GreaterThanOrEqual => ">=",
+        BinaryArithmeticOp::Equal => "==",
+        BinaryArithmeticOp::NotEqual => "!=",
+        BinaryArithmeticOp::And => "&&",
+        BinaryArithmeticOp::Or => "||",
+      };
+      // the operators are left associative, so an operand on the right
+      // with the same precedence also needs parentheses
+      format!(
+        "{} {} {}",
+        print_arithmetic_operand(left, precedence),
+        op,
+        print_arithmetic_operand(right, precedence + 1)
+      )
+    }
+  }
+}
+
+/// Precedence of a unary operator, which binds more tightly
+/// than all the binary operators.
+const UNARY_ARITHMETIC_PRECEDENCE: usize = ARITHMETIC_PRECEDENCE_LEVELS;
+
+/// Prints an operand, adding parentheses when its operator binds less
+/// tightly than the provided precedence.
+fn print_arithmetic_operand(
+  expr: &ArithmeticExpr,
+  precedence: usize,
+) -> String {
+  let expr_precedence = match expr {
+    ArithmeticExpr::Binary { op, .. } => op.precedence(),
+    ArithmeticExpr::Unary { .. } => UNARY_ARITHMETIC_PRECEDENCE,
+    ArithmeticExpr::Number { .. }
+    | ArithmeticExpr::Variable { .. }
+    | ArithmeticExpr::Parentheses { .. } => UNARY_ARITHMETIC_PRECEDENCE + 1,
+  };
+  let text = print_arithmetic_expr(expr);
+  if expr_precedence < precedence {
+    format!("({})", text)
+  } else {
+    text
+  }
+}
+
+/// Quotes the text with ANSI-C quoting (ex. `$'it\'s'`), which can
+/// represent any text and is combined with the text before it.
+fn ansi_c_quote(text: &str) -> String {
+  let mut quoted = String::from("$'");
+  for c in text.chars() {
+    match c {
+      '\\' => quoted.push_str("\\\\"),
+      '\'' => quoted.push_str("\\'"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('\'');
+  quoted
+}
+
+fn is_safe_unquoted_char(c: char) -> bool {
+  c.is_alphanumeric()
+    || matches!(
+      c,
+      '-' | '_' | '.' | '/' | '=' | ':' | ',' | '+' | '@' | '%' | '^' | '~'
+    )
+}
+
+/// Gets if the text at the start of a word would be parsed as something
+/// other than text (ex. `~/dir`, or `if` as the name of a command).
+fn is_special_word_start(
+  text: &str,
+  parts_len: usize,
+  position: WordPosition,
+) -> bool {
+  if text == "~" || text.starts_with("~/") {
+    return true;
+  }
+  let is_whole_word = parts_len == 1;
+  match position {
+    WordPosition::CommandName => match text.split_once('=') {
+      Some((name, _)) => {
+        !name.is_empty() && name.chars().all(is_valid_env_var_char)
+      }
+      None => is_whole_word && is_reserved_word(text),
+    },
+    WordPosition::CasePattern => is_whole_word && text == "esac",
+    WordPosition::Argument => false,
+  }
+}
+
+/// Gets if the text would be parsed as part of a preceding
+/// unbraced variable name.
+fn variable_name_continues(name: &str, text: &str) -> bool {
+  name.chars().all(is_valid_env_var_char)
+    && text.starts_with(is_valid_env_var_char)
+}
+
+/// Gets if the text can be within a double quoted string with other
+/// parts. A backslash at the end would escape what follows it.
+fn can_double_quote_text(text: &str) -> bool {
+  !text.ends_with('\\') && !text.contains("\\\n")
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_print() {
+    assert_print("echo  1 ;echo 2&&echo 3", "echo 1; echo 2 && echo 3");
+    assert_print(
+      "echo 1 & echo 2|echo 3 |&echo 4 || ! echo 5 &",
+      "echo 1 & echo 2 | echo 3 |& echo 4 || ! echo 5 &",
+    );
+    assert_print("A=1 B=2 cmd # comment\nC=3", "A=1 B=2 cmd; C=3");
+    assert_print("(echo 1;echo 2) > out.txt", "(echo 1; echo 2) > out.txt");
+    assert_print(
+      "cmd 2>&1 &>/dev/null <in.txt >>log.txt 3>&-",
+      "cmd 2>&1 &> /dev/null < in.txt >> log.txt 3>&-",
+    );
+    assert_print("f() {\n  echo 1\n}", "f() { echo 1; }");
+    assert_print("{ echo 1 & }", "{ echo 1 & }");
+    assert_print(
+      "if true\nthen echo 1\nelif false; then echo 2\nelse echo 3\nfi",
+      "if true; then echo 1; elif false; then echo 2; else echo 3; fi",
+    );
+    assert_print(
+      "for i in a 'b c'\ndo echo $i\ndone",
+      "for i in a 'b c'; do echo $i; done",
+    );
+    assert_print(
+      "while true; do echo 1; done && until false; do echo 2; done",
+      "while true; do echo 1; done && until false; do echo 2; done",
+    );
+    assert_print(
+      "case $1 in\n  (a|b) echo 1;;\n  *.ts) ;;\n  'esac'|if) echo 2\nesac",
+      "case $1 in a|b) echo 1;; *.ts) ;; $'esac'|if) echo 2;; esac",
+    );
+  }
+
+  #[test]
+  fn test_print_words() {
+    assert_print("echo \"a b\" 'c d' \"it's\"", "echo 'a b' 'c d' \"it's\"");
+    assert_print(
+      "echo \"$A-${B}$C\" \"\\$1 \\\"q\\\" \\`\"",
+      "echo \"$A-${B}$C\" '$1 \"q\" `'",
+    );
+    assert_print("echo 'a'\"b\" x\"$A\"b", "echo 'a''b' x$A$'b'");
+    assert_print(
+      "echo --arg=\"a b\" a'b'c $A'b' ~/dir ~user *.ts [ab]?",
+      "echo --arg='a b' a'bc' $A$'b' ~/dir ~user *.ts [ab]?",
+    );
+    assert_print(
+      "echo a\\ b $'it\\'s' 'if' \\#",
+      "echo $'a b' $'it\\'s' 'if' $'#'",
+    );
+    assert_print("'if' \"A=1\" 'b c'", "'if' 'A=1' 'b c'");
+    assert_print("$'if' A$'=1'", "$'if' A=1");
+    assert_print("A$'=1' b", "$'A=1' b");
+    assert_print(
+      "echo $(echo 1; echo 2) `echo 3` $( (echo 4) ) \"$(echo \"5\")\"",
+      "echo $(echo 1; echo 2) $(echo 3) $( (echo 4)) \"$(echo '5')\"",
+    );
+    assert_print(
+      "echo ${A:-a b} ${A=} ${A:+$B} ${A?'x'} ${#A} ${A##*/} ${A%.*}",
+      "echo ${A:-$'a b'} ${A=} ${A:+$B} ${A?x} ${#A} ${A##*/} ${A%.*}",
+    );
+    assert_print("echo $(( (1+2)*-A / $B ))", "echo $(((1 + 2) * -A / B))");
+  }
+
+  #[test]
+  fn test_print_here_docs() {
+    assert_print(
+      "cat <<EOF\n$A \\$B\nEOF\necho 1",
+      "cat <<EOF\n$A \\$B\nEOF\necho 1",
+    );
+    assert_print(
+      "cat <<-'EOF' >out <<B\n\ta $b\n\tEOF\nc\nB",
+      "cat <<-'EOF' > out <<B\na $b\nEOF\nc\nB",
+    );
+    assert_print(
+      "if cat <<EOF\nEOF\nthen (cat <<EOF\na\nEOF\n); fi",
+      "if cat <<EOF\nEOF\nthen (cat <<EOF\na\nEOF\n); fi",
+    );
+    assert_print("echo $(cat <<EOF\na\nEOF\n)", "echo $(cat <<EOF\na\nEOF\n)");
+    assert_print(
+      "cat <<A && cat <<B &\na\nA\nb\nB\necho 1",
+      "cat <<A && cat <<B &\na\nA\nb\nB\necho 1",
+    );
+    assert_print(
+      "f() { cat <<A; }; { cat <<B; }\na\nA\nb\nB",
+      "f() { cat <<A\na\nA\n}; { cat <<B\nb\nB\n}",
+    );
+  }
+
+  #[test]
+  fn test_print_arithmetic_round_trips() {
+    fn number(value: i64) -> ArithmeticExpr {
+      ArithmeticExpr::Number {
+        value,
+        span: Span::default(),
+      }
+    }
+
+    fn var(name: &str) -> ArithmeticExpr {
+      ArithmeticExpr::Variable {
+        name: name.to_string(),
+        span: Span::default(),
+      }
+    }
+
+    fn parens(expr: ArithmeticExpr) -> ArithmeticExpr {
+      ArithmeticExpr::Parentheses {
+        expr: Box::new(expr),
+        span: Span::default(),
+      }
+    }
+
+    fn unary(op: UnaryArithmeticOp, operand: ArithmeticExpr) -> ArithmeticExpr {
+      ArithmeticExpr::Unary {
+        op,
+        operand: Box::new(operand),
+        span: Span::default(),
+      }
+    }
+
+    fn binary(
+      left: ArithmeticExpr,
+      op: BinaryArithmeticOp,
+      right: ArithmeticExpr,
+    ) -> ArithmeticExpr {
+      ArithmeticExpr::Binary {
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+        span: Span::default(),
+      }
+    }
+
+    /// Asserts the expression prints as the text, which parses
+    /// to the expression with any parentheses that were added.
+    #[track_caller]
+    fn assert_round_trip(
+      expr: ArithmeticExpr,
+      expected_text: &str,
+      expected_expr: ArithmeticExpr,
+    ) {
+      fn list(expr: ArithmeticExpr) -> SequentialList {
+        SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![],
+              args: vec![
+                StringOrWord::new_word("echo"),
+                StringOrWord::Word {
+                  parts: vec![StringPart::Arithmetic {
+                    expr,
+                    span: Span::default(),
+                  }],
+                  span: Span::default(),
+                },
+              ],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        }
+      }
+
+      let printed = print(&list(expr));
+      assert_eq!(printed, format!("echo $(({}))", expected_text));
+      assert_eq_ignoring_spans(parse(&printed).unwrap(), list(expected_expr));
+    }
+
+    use BinaryArithmeticOp::*;
+    use UnaryArithmeticOp::*;
+
+    let expr = binary(number(1), Add, binary(number(2), Multiply, number(3)));
+    assert_round_trip(expr.clone(), "1 + 2 * 3", expr);
+    let expr =
+      binary(binary(number(1), Subtract, number(2)), Subtract, var("A"));
+    assert_round_trip(expr.clone(), "1 - 2 - A", expr);
+    let expr = unary(Minus, unary(Not, var("A")));
+    assert_round_trip(expr.clone(), "-!A", expr);
+
+    // parentheses are added where the operators would otherwise
+    // be applied in a different order
+    assert_round_trip(
+      binary(binary(number(1), Add, number(2)), Multiply, number(3)),
+      "(1 + 2) * 3",
+      binary(
+        parens(binary(number(1), Add, number(2))),
+        Multiply,
+        number(3),
+      ),
+    );
+    assert_round_trip(
+      binary(number(1), Subtract, binary(number(2), Subtract, number(3))),
+      "1 - (2 - 3)",
+      binary(
+        number(1),
+        Subtract,
+        parens(binary(number(2), Subtract, number(3))),
+      ),
+    );
+    assert_round_trip(
+      binary(binary(var("A"), Or, var("B")), And, var("C")),
+      "(A || B) && C",
+      binary(parens(binary(var("A"), Or, var("B"))), And, var("C")),
+    );
+    assert_round_trip(
+      unary(Minus, binary(number(1), Add, number(2))),
+      "-(1 + 2)",
+      unary(Minus, parens(binary(number(1), Add, number(2)))),
+    );
+    assert_round_trip(
+      binary(number(2), Subtract, number(-1)),
+      "2 - (-1)",
+      binary(number(2), Subtract, parens(unary(Minus, number(1)))),
+    );
+  }
+
+  #[test]
+  fn test_print_round_trips_text() {
+    let texts = [
+      "a b",
+      "it's",
+      "'",
+      "''",
+      "$A",
+      "\"quoted\"",
+      "back\\slash",
+      "trailing\\",
+      "\\'",
+      "line\nbreak",
+      "~",
+      "*",
+      "A=1",
+      "if",
+      "#",
+      "",
+    ];
+    for text in texts {
+      for word in [StringOrWord::new_word(text), StringOrWord::new_string(text)]
+      {
+        let list = SequentialList {
+          items: vec![SequentialListItem {
+            is_async: false,
+            sequence: SimpleCommand {
+              env_vars: vec![EnvVar::new("A".to_string(), word.clone())],
+              args: vec![word.clone(), word.clone()],
+              span: Span::default(),
+            }
+            .into(),
+            span: Span::default(),
+          }],
+          span: Span::default(),
+        };
+        let printed = print(&list);
+        assert_eq_ignoring_spans(parse(&printed).unwrap(), list.clone());
+      }
+    }
+
+    let word = StringOrWord::Word {
+      parts: vec![
+        StringPart::new_text("a"),
+        StringPart::new_text("b'c"),
+        StringPart::new_variable("B"),
+        StringPart::new_text("d e"),
+        StringPart::new_glob("*"),
+      ],
+      span: Span::default(),
+    };
+    let list = SequentialList {
+      items: vec![SequentialListItem {
+        is_async: false,
+        sequence: SimpleCommand {
+          env_vars: vec![],
+          args: vec![StringOrWord::new_word("echo"), word],
+          span: Span::default(),
+        }
+        .into(),
+        span: Span::default(),
+      }],
+      span: Span::default(),
+    };
+    let printed = print(&list);
+    assert_eq!(printed, "echo a'b'$'\\'c'$B$'d e'*");
+    assert_eq_ignoring_spans(parse(&printed).unwrap(), list);
+  }
+
+  #[track_caller]
+  fn assert_print(input: &str, expected: &str) {
+    let list = parse(input).unwrap();
+    let text = print(&list);
+    assert_eq!(text, expected);
+    assert_eq_ignoring_spans(parse(&text).unwrap(), list);
+  }
+
+  /// Asserts the lists are equal other than their spans, which
+  /// differ when the same list is formatted differently.
+  #[track_caller]
+  fn assert_eq_ignoring_spans(
+    mut actual: SequentialList,
+    mut expected: SequentialList,
+  ) {
+    actual.clear_spans();
+    expected.clear_spans();
+    assert_eq!(actual, expected);
+  }
+}
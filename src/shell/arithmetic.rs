@@ -0,0 +1,89 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use anyhow::bail;
+use anyhow::Result;
+
+use crate::parser::ArithmeticExpr;
+use crate::parser::BinaryArithmeticOp;
+use crate::parser::UnaryArithmeticOp;
+
+use super::types::ShellState;
+
+/// Evaluates an arithmetic expression (ex. `$((1 + 2))`) to an integer.
+///
+/// Comparison and logical operators evaluate to `1` when true and `0`
+/// when false, and overflowing operations wrap around.
+pub fn evaluate_arithmetic(
+  expr: &ArithmeticExpr,
+  state: &ShellState,
+) -> Result<i64> {
+  Ok(match expr {
+    ArithmeticExpr::Number { value, .. } => *value,
+    ArithmeticExpr::Variable { name, .. } => evaluate_variable(name, state)?,
+    ArithmeticExpr::Parentheses { expr, .. } => {
+      evaluate_arithmetic(expr, state)?
+    }
+    ArithmeticExpr::Unary { op, operand, .. } => {
+      let value = evaluate_arithmetic(operand, state)?;
+      match op {
+        UnaryArithmeticOp::Plus => value,
+        UnaryArithmeticOp::Minus => value.wrapping_neg(),
+        UnaryArithmeticOp::Not => (value == 0) as i64,
+      }
+    }
+    ArithmeticExpr::Binary {
+      left, op, right, ..
+    } => {
+      let left = evaluate_arithmetic(left, state)?;
+      // the logical operators short circuit
+      match op {
+        BinaryArithmeticOp::And if left == 0 => return Ok(0),
+        BinaryArithmeticOp::Or if left != 0 => return Ok(1),
+        _ => {}
+      }
+      let right = evaluate_arithmetic(right, state)?;
+      match op {
+        BinaryArithmeticOp::Add => left.wrapping_add(right),
+        BinaryArithmeticOp::Subtract => left.wrapping_sub(right),
+        BinaryArithmeticOp::Multiply => left.wrapping_mul(right),
+        BinaryArithmeticOp::Divide | BinaryArithmeticOp::Modulo => {
+          if right == 0 {
+            bail!("arithmetic: division by zero");
+          }
+          if *op == BinaryArithmeticOp::Divide {
+            left.wrapping_div(right)
+          } else {
+            left.wrapping_rem(right)
+          }
+        }
+        BinaryArithmeticOp::LessThan => (left < right) as i64,
+        BinaryArithmeticOp::LessThanOrEqual => (left <= right) as i64,
+        BinaryArithmeticOp::GreaterThan => (left > right) as i64,
+        BinaryArithmeticOp::GreaterThanOrEqual => (left >= right) as i64,
+        BinaryArithmeticOp::Equal => (left == right) as i64,
+        BinaryArithmeticOp::NotEqual => (left != right) as i64,
+        BinaryArithmeticOp::And | BinaryArithmeticOp::Or => (right != 0) as i64,
+      }
+    }
+  })
+}
+
+/// Unset and empty variables evaluate to `0`.
+fn evaluate_variable(name: &str, state: &ShellState) -> Result<i64> {
+  let value = match state.get_var(name) {
+    Some(value) => value,
+    None => return Ok(0),
+  };
+  let text = value.trim();
+  if text.is_empty() {
+    return Ok(0);
+  }
+  match text.parse::<i64>() {
+    Ok(value) => Ok(value),
+    Err(_) => bail!(
+      "arithmetic: {}: expected integer, but found '{}'",
+      name,
+      text
+    ),
+  }
+}
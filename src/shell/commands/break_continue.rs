@@ -0,0 +1,124 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use anyhow::bail;
+use anyhow::Result;
+
+use crate::shell::types::ExecuteResult;
+use crate::shell::types::LoopControl;
+use crate::shell::types::ShellPipeWriter;
+
+use super::args::parse_arg_kinds;
+use super::args::ArgKind;
+
+pub fn break_command(
+  args: Vec<String>,
+  loop_depth: u32,
+  stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  execute_loop_control("break", args, loop_depth, stderr, LoopControl::Break)
+}
+
+pub fn continue_command(
+  args: Vec<String>,
+  loop_depth: u32,
+  stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  execute_loop_control(
+    "continue",
+    args,
+    loop_depth,
+    stderr,
+    LoopControl::Continue,
+  )
+}
+
+fn execute_loop_control(
+  command_name: &str,
+  args: Vec<String>,
+  loop_depth: u32,
+  mut stderr: ShellPipeWriter,
+  create_control: impl FnOnce(u32) -> LoopControl,
+) -> ExecuteResult {
+  match parse_args(args) {
+    Ok(_) if loop_depth == 0 => {
+      let _ = stderr.write_line(&format!(
+        "{}: only meaningful in a `for', `while', or `until' loop",
+        command_name
+      ));
+      ExecuteResult::from_exit_code(0)
+    }
+    Ok(count) => ExecuteResult::LoopControl(
+      // exiting more loops than exist will exit all of them
+      create_control(std::cmp::min(count, loop_depth)),
+      Vec::new(),
+      Vec::new(),
+    ),
+    Err(err) => {
+      let _ = stderr.write_line(&format!("{}: {}", command_name, err));
+      ExecuteResult::from_exit_code(1)
+    }
+  }
+}
+
+fn parse_args(args: Vec<String>) -> Result<u32> {
+  let mut counts = Vec::new();
+  for arg in parse_arg_kinds(&args) {
+    match arg {
+      ArgKind::Arg(arg) => counts.push(arg),
+      _ => arg.bail_unsupported()?,
+    }
+  }
+
+  match counts.len() {
+    0 => Ok(1),
+    1 => match counts[0].parse::<i64>() {
+      Ok(value) if value >= 1 && value <= u32::MAX as i64 => Ok(value as u32),
+      Ok(_) => bail!("loop count out of range"),
+      Err(_) => bail!("numeric argument required"),
+    },
+    _ => bail!("too many arguments"),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn parses_args() {
+    assert_eq!(parse_args(vec![]).unwrap(), 1);
+    assert_eq!(parse_args(vec!["2".to_string()]).unwrap(), 2);
+    assert_eq!(
+      parse_args(vec!["0".to_string()]).err().unwrap().to_string(),
+      "loop count out of range"
+    );
+    assert_eq!(
+      parse_args(vec!["-1".to_string()])
+        .err()
+        .unwrap()
+        .to_string(),
+      "loop count out of range"
+    );
+    assert_eq!(
+      parse_args(vec!["test".to_string()])
+        .err()
+        .unwrap()
+        .to_string(),
+      "numeric argument required"
+    );
+    assert_eq!(
+      parse_args(vec!["1".to_string(), "2".to_string()])
+        .err()
+        .unwrap()
+        .to_string(),
+      "too many arguments"
+    );
+    assert_eq!(
+      parse_args(vec!["-a".to_string()])
+        .err()
+        .unwrap()
+        .to_string(),
+      "unsupported flag: -a"
+    );
+  }
+}
@@ -0,0 +1,180 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use crate::shell::types::ExecuteResult;
+use crate::shell::types::ShellPipeWriter;
+
+pub fn echo_command(
+  args: Vec<String>,
+  mut stdout: ShellPipeWriter,
+) -> ExecuteResult {
+  let _ = stdout.write(execute_echo(args).as_bytes());
+  ExecuteResult::from_exit_code(0)
+}
+
+fn execute_echo(args: Vec<String>) -> String {
+  let flags = parse_args(args);
+  let mut text = String::new();
+  for (i, arg) in flags.args.iter().enumerate() {
+    if i > 0 {
+      text.push(' ');
+    }
+    if flags.escapes {
+      if let EscapeResult::StopOutput = push_escaped(&mut text, arg) {
+        return text;
+      }
+    } else {
+      text.push_str(arg);
+    }
+  }
+  if flags.newline {
+    text.push('\n');
+  }
+  text
+}
+
+#[derive(Debug, PartialEq)]
+struct EchoFlags {
+  newline: bool,
+  escapes: bool,
+  args: Vec<String>,
+}
+
+fn parse_args(args: Vec<String>) -> EchoFlags {
+  let mut newline = true;
+  let mut escapes = false;
+  // echo only treats leading arguments consisting entirely of
+  // its flags as options and outputs everything else as-is
+  let flag_count = args
+    .iter()
+    .take_while(|arg| match arg.strip_prefix('-') {
+      Some(flags) => {
+        !flags.is_empty() && flags.chars().all(|c| "neE".contains(c))
+      }
+      None => false,
+    })
+    .count();
+  for flag in args[..flag_count].iter().flat_map(|arg| arg[1..].chars()) {
+    match flag {
+      'n' => newline = false,
+      'e' => escapes = true,
+      _ => escapes = false,
+    }
+  }
+  EchoFlags {
+    newline,
+    escapes,
+    args: args.into_iter().skip(flag_count).collect(),
+  }
+}
+
+enum EscapeResult {
+  Continue,
+  /// `\c` - Produce no further output.
+  StopOutput,
+}
+
+fn push_escaped(text: &mut String, arg: &str) -> EscapeResult {
+  let mut chars = arg.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      text.push(c);
+      continue;
+    }
+    let escaped = match chars.peek() {
+      Some('a') => '\x07',
+      Some('b') => '\x08',
+      Some('c') => return EscapeResult::StopOutput,
+      Some('e') => '\x1B',
+      Some('f') => '\x0C',
+      Some('n') => '\n',
+      Some('r') => '\r',
+      Some('t') => '\t',
+      Some('v') => '\x0B',
+      Some('\\') => '\\',
+      Some('0') | Some('x') => {
+        let (radix, max_len) = if chars.next() == Some('0') {
+          (8, 3)
+        } else {
+          (16, 2)
+        };
+        let mut value = 0;
+        let mut len = 0;
+        while let Some(digit) = chars.peek().and_then(|c| c.to_digit(radix)) {
+          if len == max_len {
+            break;
+          }
+          value = value * radix + digit;
+          len += 1;
+          chars.next();
+        }
+        if radix == 16 && len == 0 {
+          // not an escape sequence
+          text.push_str("\\x");
+        } else {
+          text.push(char::from_u32(value).unwrap_or_default());
+        }
+        continue;
+      }
+      _ => {
+        text.push('\\');
+        continue;
+      }
+    };
+    chars.next();
+    text.push(escaped);
+  }
+  EscapeResult::Continue
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use pretty_assertions::assert_eq;
+
+  fn echo(args: &[&str]) -> String {
+    execute_echo(args.iter().map(|arg| arg.to_string()).collect())
+  }
+
+  #[test]
+  fn parses_args() {
+    assert_eq!(
+      parse_args(vec!["-n".to_string(), "-e".to_string(), "a".to_string()]),
+      EchoFlags {
+        newline: false,
+        escapes: true,
+        args: vec!["a".to_string()],
+      }
+    );
+    assert_eq!(
+      parse_args(vec!["-eE".to_string(), "-a".to_string(), "-n".to_string()]),
+      EchoFlags {
+        newline: true,
+        escapes: false,
+        args: vec!["-a".to_string(), "-n".to_string()],
+      }
+    );
+    assert_eq!(
+      parse_args(vec!["-".to_string(), "--".to_string()]),
+      EchoFlags {
+        newline: true,
+        escapes: false,
+        args: vec!["-".to_string(), "--".to_string()],
+      }
+    );
+  }
+
+  #[test]
+  fn echoes() {
+    assert_eq!(echo(&[]), "\n");
+    assert_eq!(echo(&["a", "b"]), "a b\n");
+    assert_eq!(echo(&["-n", "a"]), "a");
+    assert_eq!(echo(&["a\\tb"]), "a\\tb\n");
+    assert_eq!(echo(&["-e", "a\\tb\\\\c\\n"]), "a\tb\\c\n\n");
+    assert_eq!(
+      echo(&["-e", "\\x41\\x4a2\\0101\\0\\xg\\q"]),
+      "AJ2A\0\\xg\\q\n"
+    );
+    assert_eq!(echo(&["-e", "a\\cb", "c"]), "a");
+    assert_eq!(echo(&["-e", "trailing\\"]), "trailing\\\n");
+  }
+}
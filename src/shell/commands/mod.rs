@@ -1,9 +1,11 @@
 // Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
 
 mod args;
+mod break_continue;
 mod cat;
 mod cd;
 mod cp_mv;
+mod echo;
 mod exit;
 mod mkdir;
 mod pwd;
@@ -11,9 +13,11 @@ mod rm;
 mod sleep;
 mod xargs;
 
+pub use break_continue::*;
 pub use cat::*;
 pub use cd::*;
 pub use cp_mv::*;
+pub use echo::*;
 pub use exit::*;
 pub use mkdir::*;
 pub use pwd::*;
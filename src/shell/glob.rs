@@ -0,0 +1,260 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use std::path::Path;
+use std::path::PathBuf;
+
+use super::pattern::Pattern;
+
+/// A glob (ex. `src/**/*.json`) that is expanded to the matching paths.
+///
+/// The glob is split into its path components, which are matched
+/// one directory at a time so that `*` never matches a separator.
+#[derive(Debug, Default)]
+pub struct Glob {
+  segments: Vec<GlobSegment>,
+  current: PendingSegment,
+}
+
+#[derive(Debug)]
+enum GlobSegment {
+  /// A component without any glob characters.
+  Literal(String),
+  /// A component with glob characters (ex. `*.json`).
+  Pattern {
+    pattern: Pattern,
+    /// Hidden entries are only matched when the component
+    /// explicitly starts with a `.`
+    matches_hidden: bool,
+  },
+  /// `**` - Matches zero or more directories.
+  AnyDirectories,
+}
+
+#[derive(Debug, Default)]
+struct PendingSegment {
+  text: String,
+  pattern: Pattern,
+  has_glob: bool,
+  starts_with_dot: Option<bool>,
+}
+
+impl PendingSegment {
+  fn into_segment(self) -> GlobSegment {
+    if !self.has_glob {
+      GlobSegment::Literal(self.text)
+    } else if self.text == "**" {
+      GlobSegment::AnyDirectories
+    } else {
+      GlobSegment::Pattern {
+        pattern: self.pattern,
+        matches_hidden: self.starts_with_dot.unwrap_or(false),
+      }
+    }
+  }
+}
+
+impl Glob {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds text that should be matched exactly.
+  pub fn push_literal(&mut self, text: &str) {
+    for c in text.chars() {
+      if std::path::is_separator(c) {
+        self.finish_segment();
+      } else {
+        self.current.text.push(c);
+        self
+          .current
+          .pattern
+          .push_literal(c.encode_utf8(&mut [0; 4]));
+        self.current.starts_with_dot.get_or_insert(c == '.');
+      }
+    }
+  }
+
+  /// Adds text where `*`, `?`, and `[...]` have special meaning.
+  pub fn push_glob(&mut self, text: &str) {
+    for (i, component) in text.split('/').enumerate() {
+      if i > 0 {
+        self.finish_segment();
+      }
+      if !component.is_empty() {
+        self.current.text.push_str(component);
+        self.current.pattern.push_glob(component);
+        self.current.has_glob = true;
+        self
+          .current
+          .starts_with_dot
+          .get_or_insert(component.starts_with('.'));
+      }
+    }
+  }
+
+  fn finish_segment(&mut self) {
+    let segment = std::mem::take(&mut self.current);
+    self.segments.push(segment.into_segment());
+  }
+
+  /// Gets the sorted paths that match the glob, relative to the cwd
+  /// when the glob is relative.
+  pub fn expand(mut self, cwd: &Path) -> Vec<String> {
+    self.finish_segment();
+
+    // the leading components without glob characters are used as-is
+    let literal_count = self
+      .segments
+      .iter()
+      .take_while(|s| matches!(s, GlobSegment::Literal(_)))
+      .count();
+    let mut prefix = String::new();
+    for segment in &self.segments[..literal_count] {
+      if let GlobSegment::Literal(text) = segment {
+        prefix.push_str(text);
+        prefix.push('/');
+      }
+    }
+    if literal_count == self.segments.len() {
+      // no glob characters, so nothing to expand
+      prefix.pop();
+      return vec![prefix];
+    }
+
+    let mut matches = vec![(cwd.join(&prefix), prefix)];
+    for segment in &self.segments[literal_count..] {
+      let mut next_matches = Vec::new();
+      for (path, text) in matches {
+        match segment {
+          GlobSegment::Literal(name) => {
+            let path = path.join(name);
+            if path.exists() {
+              next_matches.push((path, join_text(&text, name)));
+            }
+          }
+          GlobSegment::Pattern {
+            pattern,
+            matches_hidden,
+          } => {
+            for (entry_path, name) in read_dir_names(&path) {
+              if (*matches_hidden || !name.starts_with('.'))
+                && pattern.matches(&name)
+              {
+                next_matches.push((entry_path, join_text(&text, &name)));
+              }
+            }
+          }
+          GlobSegment::AnyDirectories => {
+            collect_directories(path, text, &mut next_matches);
+          }
+        }
+      }
+      matches = next_matches;
+    }
+
+    let mut paths = matches
+      .into_iter()
+      .map(|(_, text)| text)
+      .collect::<Vec<_>>();
+    paths.sort();
+    // `**` may match the same path multiple times
+    paths.dedup();
+    paths
+  }
+}
+
+fn join_text(text: &str, name: &str) -> String {
+  if text.is_empty() || text.ends_with('/') {
+    format!("{}{}", text, name)
+  } else {
+    format!("{}/{}", text, name)
+  }
+}
+
+fn read_dir_names(path: &Path) -> Vec<(PathBuf, String)> {
+  match std::fs::read_dir(path) {
+    Ok(entries) => entries
+      .filter_map(|entry| entry.ok())
+      .filter_map(|entry| {
+        let name = entry.file_name().to_str()?.to_string();
+        Some((entry.path(), name))
+      })
+      .collect(),
+    Err(_) => Vec::new(),
+  }
+}
+
+/// Collects the provided directory and all its non-hidden descendant
+/// directories.
+fn collect_directories(
+  path: PathBuf,
+  text: String,
+  result: &mut Vec<(PathBuf, String)>,
+) {
+  for (entry_path, name) in read_dir_names(&path) {
+    if !name.starts_with('.') && entry_path.is_dir() {
+      let entry_text = join_text(&text, &name);
+      collect_directories(entry_path, entry_text, result);
+    }
+  }
+  result.push((path, text));
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn expand(cwd: &Path, text: &str) -> Vec<String> {
+    let mut glob = Glob::new();
+    glob.push_glob(text);
+    glob.expand(cwd)
+  }
+
+  #[test]
+  fn expands_globs() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cwd = temp_dir.path();
+    std::fs::create_dir_all(cwd.join("src/sub/deep")).unwrap();
+    std::fs::create_dir_all(cwd.join("src/.hidden")).unwrap();
+    for path in [
+      "a.json",
+      "b.json",
+      ".c.json",
+      "src/d.json",
+      "src/e.ts",
+      "src/sub/f.json",
+      "src/sub/deep/g.json",
+      "src/.hidden/h.json",
+    ] {
+      std::fs::write(cwd.join(path), "").unwrap();
+    }
+
+    assert_eq!(expand(cwd, "*.json"), vec!["a.json", "b.json"]);
+    assert_eq!(expand(cwd, "?.json"), vec!["a.json", "b.json"]);
+    assert_eq!(expand(cwd, "[!a].json"), vec!["b.json"]);
+    assert_eq!(expand(cwd, ".*.json"), vec![".c.json"]);
+    assert_eq!(expand(cwd, "*/*.ts"), vec!["src/e.ts"]);
+    assert_eq!(expand(cwd, "*.txt"), Vec::<String>::new());
+    assert_eq!(
+      expand(cwd, "src/**/*.json"),
+      vec!["src/d.json", "src/sub/deep/g.json", "src/sub/f.json"]
+    );
+    assert_eq!(expand(cwd, "**/deep"), vec!["src/sub/deep"]);
+
+    let src_dir = cwd.join("src").display().to_string();
+    let mut glob = Glob::new();
+    glob.push_literal(&src_dir);
+    glob.push_literal("/");
+    glob.push_glob("*.ts");
+    assert_eq!(
+      glob.expand(Path::new("/other")),
+      vec![format!("{}/e.ts", src_dir.replace('\\', "/"))]
+    );
+
+    let mut glob = Glob::new();
+    glob.push_literal("src/sub/");
+    glob.push_glob("*");
+    glob.push_literal("/g.json");
+    assert_eq!(glob.expand(cwd), vec!["src/sub/deep/g.json"]);
+  }
+}
@@ -1,17 +1,27 @@
 // Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::bail;
 use anyhow::Result;
+use futures::future::BoxFuture;
 use futures::FutureExt;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
+use crate::parser::CaseClause;
 use crate::parser::Command;
 use crate::parser::CommandInner;
+use crate::parser::ElsePart;
+use crate::parser::ForClause;
+use crate::parser::IfClause;
+use crate::parser::IoFile;
+use crate::parser::ParameterExpansion;
+use crate::parser::ParameterExpansionOp;
 use crate::parser::PipeSequence;
 use crate::parser::PipeSequenceOperator;
 use crate::parser::Pipeline;
@@ -24,9 +34,14 @@ use crate::parser::SequentialList;
 use crate::parser::SimpleCommand;
 use crate::parser::StringOrWord;
 use crate::parser::StringPart;
+use crate::parser::WhileClause;
+use crate::shell::arithmetic::evaluate_arithmetic;
+use crate::shell::commands::break_command;
 use crate::shell::commands::cat_command;
 use crate::shell::commands::cd_command;
+use crate::shell::commands::continue_command;
 use crate::shell::commands::cp_command;
+use crate::shell::commands::echo_command;
 use crate::shell::commands::exit_command;
 use crate::shell::commands::mkdir_command;
 use crate::shell::commands::mv_command;
@@ -34,18 +49,24 @@ use crate::shell::commands::pwd_command;
 use crate::shell::commands::rm_command;
 use crate::shell::commands::sleep_command;
 use crate::shell::commands::xargs_collect_args;
+use crate::shell::glob::Glob;
+use crate::shell::pattern::Pattern;
 use crate::shell::types::pipe;
 use crate::shell::types::EnvChange;
 use crate::shell::types::ExecuteResult;
 use crate::shell::types::FutureExecuteResult;
+use crate::shell::types::LoopControl;
 use crate::shell::types::ShellPipeReader;
 use crate::shell::types::ShellPipeWriter;
 use crate::shell::types::ShellState;
 
 use self::types::CANCELLATION_EXIT_CODE;
 
+mod arithmetic;
 mod commands;
 mod fs_util;
+mod glob;
+mod pattern;
 mod types;
 
 #[cfg(test)]
@@ -53,15 +74,19 @@ mod test;
 #[cfg(test)]
 mod test_builder;
 
+/// Executes the list, where the provided arguments are the
+/// positional parameters (ex. `$1` or `$@`) of the script.
 pub async fn execute(
   list: SequentialList,
   env_vars: HashMap<String, String>,
   cwd: &Path,
+  args: Vec<String>,
 ) -> i32 {
   execute_with_pipes(
     list,
     env_vars,
     cwd,
+    args,
     ShellPipeReader::stdin(),
     ShellPipeWriter::stdout(),
     ShellPipeWriter::stderr(),
@@ -73,12 +98,14 @@ pub(crate) async fn execute_with_pipes(
   list: SequentialList,
   env_vars: HashMap<String, String>,
   cwd: &Path,
+  args: Vec<String>,
   stdin: ShellPipeReader,
   stdout: ShellPipeWriter,
   stderr: ShellPipeWriter,
 ) -> i32 {
   assert!(cwd.is_absolute());
-  let state = ShellState::new(env_vars, cwd);
+  let mut state = ShellState::new(env_vars, cwd);
+  state.set_args(args);
 
   // spawn a sequential list and pipe its output to the environment
   let result = execute_sequential_list(
@@ -91,10 +118,7 @@ pub(crate) async fn execute_with_pipes(
   )
   .await;
 
-  match result {
-    ExecuteResult::Exit(code, _) => code,
-    ExecuteResult::Continue(exit_code, _, _) => exit_code,
-  }
+  result.into_exit_code_and_handles().0
 }
 
 #[derive(Debug, PartialEq)]
@@ -116,19 +140,23 @@ fn execute_sequential_list(
     let mut final_changes = Vec::new();
     let mut async_handles = Vec::new();
     let mut was_exit = false;
+    let mut loop_control = None;
     for item in list.items {
       if item.is_async {
-        let state = state.clone();
+        let async_state = state.clone();
         let stdin = stdin.clone();
         let stdout = stdout.clone();
         let stderr = stderr.clone();
         async_handles.push(tokio::task::spawn(async move {
-          let main_token = state.token();
+          let main_token = async_state.token();
           let result =
-            execute_sequence(item.sequence, state, stdin, stdout, stderr).await;
+            execute_sequence(item.sequence, async_state, stdin, stdout, stderr)
+              .await;
           let (exit_code, handles) = result.into_exit_code_and_handles();
           wait_handles(exit_code, handles, main_token).await
         }));
+        // starting an async command always succeeds
+        state.set_last_exit_code(0);
       } else {
         let result = execute_sequence(
           item.sequence,
@@ -147,11 +175,20 @@ fn execute_sequential_list(
           }
           ExecuteResult::Continue(exit_code, changes, handles) => {
             state.apply_changes(&changes);
+            state.set_last_exit_code(exit_code);
             final_changes.extend(changes);
             async_handles.extend(handles);
             // use the final sequential item's exit code
             final_exit_code = exit_code;
           }
+          ExecuteResult::LoopControl(control, changes, handles) => {
+            state.apply_changes(&changes);
+            final_changes.extend(changes);
+            async_handles.extend(handles);
+            final_exit_code = 0;
+            loop_control = Some(control);
+            break;
+          }
         }
       }
     }
@@ -168,6 +205,8 @@ fn execute_sequential_list(
 
     if was_exit {
       ExecuteResult::Exit(final_exit_code, async_handles)
+    } else if let Some(control) = loop_control {
+      ExecuteResult::LoopControl(control, final_changes, async_handles)
     } else {
       ExecuteResult::Continue(final_exit_code, final_changes, async_handles)
     }
@@ -202,19 +241,37 @@ fn execute_sequence(
   mut state: ShellState,
   stdin: ShellPipeReader,
   stdout: ShellPipeWriter,
-  stderr: ShellPipeWriter,
+  mut stderr: ShellPipeWriter,
 ) -> FutureExecuteResult {
   // requires boxed async because of recursive async
   async move {
     match sequence {
-      Sequence::ShellVar(var) => ExecuteResult::Continue(
+      Sequence::FunctionDefinition(definition) => ExecuteResult::Continue(
         0,
-        vec![EnvChange::SetShellVar(
-          var.name,
-          evaluate_string_or_word(var.value, &state, stdin, stderr).await,
+        vec![EnvChange::SetFunction(
+          definition.name,
+          Arc::new(definition.body),
         )],
         Vec::new(),
       ),
+      Sequence::ShellVar(var) => {
+        let mut changes = Vec::new();
+        let value = evaluate_string_or_word(
+          var.value,
+          &mut state,
+          &mut changes,
+          stdin,
+          stderr.clone(),
+        )
+        .await;
+        match value {
+          Ok(value) => {
+            changes.push(EnvChange::SetShellVar(var.name, value));
+            ExecuteResult::Continue(0, changes, Vec::new())
+          }
+          Err(err) => evaluation_error_result(err, &mut stderr),
+        }
+      }
       Sequence::BooleanList(list) => {
         let mut changes = vec![];
         let first_result = execute_sequence(
@@ -226,9 +283,12 @@ fn execute_sequence(
         )
         .await;
         let (exit_code, mut async_handles) = match first_result {
-          ExecuteResult::Exit(_, _) => return first_result,
+          ExecuteResult::Exit(_, _) | ExecuteResult::LoopControl(_, _, _) => {
+            return first_result
+          }
           ExecuteResult::Continue(exit_code, sub_changes, async_handles) => {
             state.apply_changes(&sub_changes);
+            state.set_last_exit_code(exit_code);
             changes.extend(sub_changes);
             (exit_code, async_handles)
           }
@@ -264,6 +324,11 @@ fn execute_sequence(
               async_handles.extend(sub_handles);
               ExecuteResult::Continue(exit_code, changes, async_handles)
             }
+            ExecuteResult::LoopControl(control, sub_changes, sub_handles) => {
+              changes.extend(sub_changes);
+              async_handles.extend(sub_handles);
+              ExecuteResult::LoopControl(control, changes, async_handles)
+            }
           }
         } else {
           ExecuteResult::Continue(exit_code, changes, async_handles)
@@ -288,11 +353,11 @@ async fn execute_pipeline(
     execute_pipeline_inner(pipeline.inner, state, stdin, stdout, stderr).await;
   if pipeline.negated {
     match result {
-      ExecuteResult::Exit(code, handles) => ExecuteResult::Exit(code, handles),
       ExecuteResult::Continue(code, changes, handles) => {
         let new_code = if code == 0 { 1 } else { 0 };
         ExecuteResult::Continue(new_code, changes, handles)
       }
+      ExecuteResult::Exit(_, _) | ExecuteResult::LoopControl(_, _, _) => result,
     }
   } else {
     result
@@ -301,39 +366,62 @@ async fn execute_pipeline(
 
 async fn execute_pipeline_inner(
   pipeline: PipelineInner,
-  state: ShellState,
-  stdin: ShellPipeReader,
-  stdout: ShellPipeWriter,
+  mut state: ShellState,
+  mut stdin: ShellPipeReader,
+  mut stdout: ShellPipeWriter,
   mut stderr: ShellPipeWriter,
 ) -> ExecuteResult {
   match pipeline {
     PipelineInner::Command(command) => {
       // We only support redirects that are not in a pipe sequence
-      // at the moment, so this is fine to do only here
-      let (stdout, stderr) = if let Some(redirect) = &command.redirect {
-        let pipe =
-          match resolve_redirect_pipe(redirect, &state, &stdin, &mut stderr)
-            .await
-          {
-            Ok(value) => value,
-            Err(value) => return value,
-          };
+      // at the moment, so this is fine to do only here.
+      // Redirects are applied left to right, so a file descriptor
+      // duplication refers to the pipes as they are at that point.
+      let mut changes = Vec::new();
+      for redirect in &command.redirects {
+        let pipe = match resolve_redirect_pipe(
+          redirect,
+          &mut state,
+          &mut changes,
+          &stdin,
+          &stdout,
+          &mut stderr,
+        )
+        .await
+        {
+          Ok(value) => value,
+          Err(value) => return value,
+        };
 
-        match redirect.maybe_fd {
-          Some(RedirectFd::Fd(2)) => (stdout, pipe),
-          Some(RedirectFd::Fd(1)) | None => (pipe, stderr),
-          Some(RedirectFd::Fd(_)) => {
-            let _ = stderr.write_line(
-              "only redirecting to stdout (1) and stderr (2) is supported",
-            );
-            return ExecuteResult::from_exit_code(1);
-          }
-          Some(RedirectFd::StdoutStderr) => (pipe.clone(), pipe),
+        match pipe {
+          RedirectPipe::Input(pipe) => match redirect.maybe_fd {
+            Some(RedirectFd::Fd(0)) | None => stdin = pipe,
+            Some(_) => {
+              let _ = stderr.write_line(
+                "only redirecting from a file to stdin (0) is supported",
+              );
+              return ExecuteResult::from_exit_code(1);
+            }
+          },
+          RedirectPipe::Output(pipe) => match redirect.maybe_fd {
+            Some(RedirectFd::Fd(2)) => stderr = pipe,
+            Some(RedirectFd::Fd(1)) | None => stdout = pipe,
+            Some(RedirectFd::Fd(_)) => {
+              let _ = stderr.write_line(
+                "only redirecting to stdout (1) and stderr (2) is supported",
+              );
+              return ExecuteResult::from_exit_code(1);
+            }
+            Some(RedirectFd::StdoutStderr) => {
+              stdout = pipe.clone();
+              stderr = pipe;
+            }
+          },
         }
-      } else {
-        (stdout, stderr)
-      };
-      execute_command(command, state, stdin, stdout, stderr).await
+      }
+      execute_command(command, state, stdin, stdout, stderr)
+        .await
+        .with_prior_changes(changes)
     }
     PipelineInner::PipeSequence(pipe_sequence) => {
       execute_pipe_sequence(*pipe_sequence, state, stdin, stdout, stderr).await
@@ -341,19 +429,96 @@ async fn execute_pipeline_inner(
   }
 }
 
+enum RedirectPipe {
+  Input(ShellPipeReader),
+  Output(ShellPipeWriter),
+}
+
 async fn resolve_redirect_pipe(
   redirect: &Redirect,
-  state: &ShellState,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
   stdin: &ShellPipeReader,
+  stdout: &ShellPipeWriter,
   stderr: &mut ShellPipeWriter,
-) -> Result<ShellPipeWriter, ExecuteResult> {
+) -> Result<RedirectPipe, ExecuteResult> {
+  let word = match &redirect.io_file {
+    IoFile::Word(word) => word,
+    IoFile::HereDoc(here_doc) => {
+      // the body is expanded like a quoted string
+      let words = evaluate_quoted_string_parts(
+        here_doc.body.clone().unwrap_or_default(),
+        state,
+        changes,
+        stdin.clone(),
+        stderr.clone(),
+      )
+      .await;
+      return match words {
+        Ok(words) => {
+          let text = words.join(" ");
+          Ok(RedirectPipe::Input(ShellPipeReader::from_text(text)))
+        }
+        Err(err) => Err(evaluation_error_result(err, stderr)),
+      };
+    }
+    IoFile::Fd(fd) => {
+      return match (&redirect.op, fd) {
+        (RedirectOp::Input, 0) => Ok(RedirectPipe::Input(stdin.clone())),
+        (
+          RedirectOp::Input | RedirectOp::HereDoc | RedirectOp::HereString,
+          _,
+        ) => {
+          let _ = stderr.write_line(
+            "only redirecting from a file to stdin (0) is supported",
+          );
+          Err(ExecuteResult::from_exit_code(1))
+        }
+        (RedirectOp::Redirect | RedirectOp::Append, 1) => {
+          Ok(RedirectPipe::Output(stdout.clone()))
+        }
+        (RedirectOp::Redirect | RedirectOp::Append, 2) => {
+          Ok(RedirectPipe::Output(stderr.clone()))
+        }
+        (RedirectOp::Redirect | RedirectOp::Append, _) => {
+          let _ = stderr.write_line(
+            "only redirecting to stdout (1) and stderr (2) is supported",
+          );
+          Err(ExecuteResult::from_exit_code(1))
+        }
+      };
+    }
+    IoFile::Close => {
+      return Ok(match redirect.op {
+        RedirectOp::Input | RedirectOp::HereDoc | RedirectOp::HereString => {
+          // the writer is dropped, so there will be no input
+          let (reader, _) = pipe();
+          RedirectPipe::Input(reader)
+        }
+        RedirectOp::Redirect | RedirectOp::Append => {
+          RedirectPipe::Output(ShellPipeWriter::null())
+        }
+      });
+    }
+  };
   let words = evaluate_string_parts(
-    redirect.io_file.clone().into_parts(),
+    word.clone().into_parts(),
     state,
+    changes,
     stdin.clone(),
     stderr.clone(),
   )
   .await;
+  let words = match words {
+    Ok(words) => words,
+    Err(err) => {
+      return Err(evaluation_error_result(err, stderr));
+    }
+  };
+  if redirect.op == RedirectOp::HereString {
+    let text = format!("{}\n", words.join(" "));
+    return Ok(RedirectPipe::Input(ShellPipeReader::from_text(text)));
+  }
   // edge case that's not supported
   if words.is_empty() {
     let _ = stderr.write_line("redirect path must be 1 argument, but found 0");
@@ -369,26 +534,45 @@ async fn resolve_redirect_pipe(
     ));
     return Err(ExecuteResult::from_exit_code(1));
   }
-  let output_path = &words[0];
+  let path = &words[0];
 
-  // cross platform suppress output
-  if output_path == "/dev/null" {
-    return Ok(ShellPipeWriter::null());
+  // cross platform suppress output and empty input
+  if path == "/dev/null" {
+    return Ok(match redirect.op {
+      RedirectOp::Input | RedirectOp::HereDoc | RedirectOp::HereString => {
+        // the writer is dropped, so this will be at the end of the input
+        let (reader, _) = pipe();
+        RedirectPipe::Input(reader)
+      }
+      RedirectOp::Redirect | RedirectOp::Append => {
+        RedirectPipe::Output(ShellPipeWriter::null())
+      }
+    });
   }
 
-  let output_path = state.cwd().join(output_path);
-  let std_file_result = std::fs::OpenOptions::new()
-    .write(true)
-    .create(true)
-    .append(redirect.op == RedirectOp::Append)
-    .truncate(redirect.op != RedirectOp::Append)
-    .open(&output_path);
+  let path = state.cwd().join(path);
+  let std_file_result = match redirect.op {
+    RedirectOp::Input | RedirectOp::HereDoc | RedirectOp::HereString => {
+      std::fs::File::open(&path).map(|std_file| {
+        RedirectPipe::Input(ShellPipeReader::from_std(std_file))
+      })
+    }
+    RedirectOp::Redirect | RedirectOp::Append => std::fs::OpenOptions::new()
+      .write(true)
+      .create(true)
+      .append(redirect.op == RedirectOp::Append)
+      .truncate(redirect.op != RedirectOp::Append)
+      .open(&path)
+      .map(|std_file| {
+        RedirectPipe::Output(ShellPipeWriter::from_std(std_file))
+      }),
+  };
   match std_file_result {
-    Ok(std_file) => Ok(ShellPipeWriter::from_std(std_file)),
+    Ok(pipe) => Ok(pipe),
     Err(err) => {
       let _ = stderr.write_line(&format!(
         "error opening file for redirect ({}). {:#}",
-        output_path.display(),
+        path.display(),
         err
       ));
       Err(ExecuteResult::from_exit_code(1))
@@ -403,7 +587,7 @@ async fn execute_command(
   stdout: ShellPipeWriter,
   stderr: ShellPipeWriter,
 ) -> ExecuteResult {
-  // todo: handle command.redirect
+  // todo: handle command.redirects
   match command.inner {
     CommandInner::Simple(command) => {
       execute_simple_command(command, state, stdin, stdout, stderr).await
@@ -411,6 +595,30 @@ async fn execute_command(
     CommandInner::Subshell(list) => {
       execute_subshell(list, state, stdin, stdout, stderr).await
     }
+    // groups execute in the current shell, so all their changes are kept
+    CommandInner::Group(list) => {
+      execute_sequential_list(
+        *list,
+        state,
+        stdin,
+        stdout,
+        stderr,
+        AsyncCommandBehavior::Yield,
+      )
+      .await
+    }
+    CommandInner::If(if_clause) => {
+      execute_if_clause(if_clause, state, stdin, stdout, stderr).await
+    }
+    CommandInner::For(for_clause) => {
+      execute_for_clause(for_clause, state, stdin, stdout, stderr).await
+    }
+    CommandInner::While(while_clause) => {
+      execute_while_clause(while_clause, state, stdin, stdout, stderr).await
+    }
+    CommandInner::Case(case_clause) => {
+      execute_case_clause(case_clause, state, stdin, stdout, stderr).await
+    }
   }
 }
 
@@ -464,6 +672,11 @@ async fn execute_pipe_sequence(
       handles.extend(all_handles);
       ExecuteResult::Continue(code, Vec::new(), handles)
     }
+    // commands in a pipe sequence can't control a loop outside of it
+    ExecuteResult::LoopControl(_, _, mut handles) => {
+      handles.extend(all_handles);
+      ExecuteResult::Continue(0, Vec::new(), handles)
+    }
   }
 }
 
@@ -485,45 +698,390 @@ async fn execute_subshell(
   )
   .await;
 
-  // sub shells do not cause an exit
+  // sub shells do not cause an exit, control loops outside
+  // of them, or change the environment of the parent
   match result {
     ExecuteResult::Exit(code, handles) => {
       ExecuteResult::Continue(code, Vec::new(), handles)
     }
-    ExecuteResult::Continue(_, _, _) => result,
+    ExecuteResult::LoopControl(_, _, handles) => {
+      ExecuteResult::Continue(0, Vec::new(), handles)
+    }
+    ExecuteResult::Continue(code, _, handles) => {
+      ExecuteResult::Continue(code, Vec::new(), handles)
+    }
   }
 }
 
-async fn execute_simple_command(
-  command: SimpleCommand,
-  state: ShellState,
+fn execute_if_clause(
+  if_clause: IfClause,
+  mut state: ShellState,
+  stdin: ShellPipeReader,
+  stdout: ShellPipeWriter,
+  stderr: ShellPipeWriter,
+) -> FutureExecuteResult {
+  // requires boxed async because of recursive async
+  async move {
+    let mut changes = Vec::new();
+    let condition_result = execute_sequential_list(
+      if_clause.condition,
+      state.clone(),
+      stdin.clone(),
+      stdout.clone(),
+      stderr.clone(),
+      AsyncCommandBehavior::Yield,
+    )
+    .await;
+    let (exit_code, mut async_handles) = match condition_result {
+      ExecuteResult::Exit(_, _) | ExecuteResult::LoopControl(_, _, _) => {
+        return condition_result
+      }
+      ExecuteResult::Continue(exit_code, sub_changes, async_handles) => {
+        state.apply_changes(&sub_changes);
+        state.set_last_exit_code(exit_code);
+        changes.extend(sub_changes);
+        (exit_code, async_handles)
+      }
+    };
+
+    let branch_result = if exit_code == 0 {
+      execute_sequential_list(
+        if_clause.body,
+        state,
+        stdin,
+        stdout,
+        stderr,
+        AsyncCommandBehavior::Yield,
+      )
+      .await
+    } else {
+      match if_clause.else_part {
+        Some(ElsePart::Elif(if_clause)) => {
+          execute_if_clause(*if_clause, state, stdin, stdout, stderr).await
+        }
+        Some(ElsePart::Else(list)) => {
+          execute_sequential_list(
+            list,
+            state,
+            stdin,
+            stdout,
+            stderr,
+            AsyncCommandBehavior::Yield,
+          )
+          .await
+        }
+        // the exit code is zero when no branch was taken
+        None => return ExecuteResult::Continue(0, changes, async_handles),
+      }
+    };
+    match branch_result {
+      ExecuteResult::Exit(code, sub_handles) => {
+        async_handles.extend(sub_handles);
+        ExecuteResult::Exit(code, async_handles)
+      }
+      ExecuteResult::Continue(exit_code, sub_changes, sub_handles) => {
+        changes.extend(sub_changes);
+        async_handles.extend(sub_handles);
+        ExecuteResult::Continue(exit_code, changes, async_handles)
+      }
+      ExecuteResult::LoopControl(control, sub_changes, sub_handles) => {
+        changes.extend(sub_changes);
+        async_handles.extend(sub_handles);
+        ExecuteResult::LoopControl(control, changes, async_handles)
+      }
+    }
+  }
+  .boxed()
+}
+
+async fn execute_for_clause(
+  for_clause: ForClause,
+  mut state: ShellState,
+  stdin: ShellPipeReader,
+  stdout: ShellPipeWriter,
+  mut stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  let mut changes = Vec::new();
+  let words = evaluate_args(
+    for_clause.wordlist,
+    &mut state,
+    &mut changes,
+    stdin.clone(),
+    stderr.clone(),
+  )
+  .await;
+  let words = match words {
+    Ok(words) => words,
+    Err(err) => {
+      return evaluation_error_result(err, &mut stderr);
+    }
+  };
+  state.increment_loop_depth();
+  let token = state.token();
+  let mut exit_code = 0;
+  let mut async_handles = Vec::new();
+  for word in words {
+    if token.is_cancelled() {
+      return ExecuteResult::Exit(CANCELLATION_EXIT_CODE, async_handles);
+    }
+
+    let change = EnvChange::SetShellVar(for_clause.var_name.clone(), word);
+    state.apply_change(&change);
+    changes.push(change);
+
+    let result = execute_sequential_list(
+      for_clause.body.clone(),
+      state.clone(),
+      stdin.clone(),
+      stdout.clone(),
+      stderr.clone(),
+      AsyncCommandBehavior::Yield,
+    )
+    .await;
+    match apply_loop_result(
+      result,
+      &mut state,
+      &mut changes,
+      &mut async_handles,
+    ) {
+      LoopFlow::Completed(code) => exit_code = code,
+      LoopFlow::Continue => exit_code = 0,
+      LoopFlow::Break => {
+        exit_code = 0;
+        break;
+      }
+      LoopFlow::Return(result) => return result,
+    }
+  }
+  ExecuteResult::Continue(exit_code, changes, async_handles)
+}
+
+async fn execute_while_clause(
+  while_clause: WhileClause,
+  mut state: ShellState,
   stdin: ShellPipeReader,
   stdout: ShellPipeWriter,
   stderr: ShellPipeWriter,
 ) -> ExecuteResult {
-  let args =
-    evaluate_args(command.args, &state, stdin.clone(), stderr.clone()).await;
-  let mut state = state.clone();
-  for env_var in command.env_vars {
-    state.apply_env_var(
-      &env_var.name,
-      &evaluate_string_or_word(
-        env_var.value,
-        &state,
+  state.increment_loop_depth();
+  let token = state.token();
+  let mut exit_code = 0;
+  let mut changes = Vec::new();
+  let mut async_handles = Vec::new();
+  loop {
+    // give other tasks a chance to run in case this loop never awaits
+    tokio::task::yield_now().await;
+    if token.is_cancelled() {
+      return ExecuteResult::Exit(CANCELLATION_EXIT_CODE, async_handles);
+    }
+
+    let result = execute_sequential_list(
+      while_clause.condition.clone(),
+      state.clone(),
+      stdin.clone(),
+      stdout.clone(),
+      stderr.clone(),
+      AsyncCommandBehavior::Yield,
+    )
+    .await;
+    let condition_exit_code = match apply_loop_result(
+      result,
+      &mut state,
+      &mut changes,
+      &mut async_handles,
+    ) {
+      LoopFlow::Completed(code) => code,
+      LoopFlow::Continue => continue,
+      LoopFlow::Break => {
+        exit_code = 0;
+        break;
+      }
+      LoopFlow::Return(result) => return result,
+    };
+    if (condition_exit_code == 0) == while_clause.is_until {
+      break;
+    }
+
+    let result = execute_sequential_list(
+      while_clause.body.clone(),
+      state.clone(),
+      stdin.clone(),
+      stdout.clone(),
+      stderr.clone(),
+      AsyncCommandBehavior::Yield,
+    )
+    .await;
+    match apply_loop_result(
+      result,
+      &mut state,
+      &mut changes,
+      &mut async_handles,
+    ) {
+      LoopFlow::Completed(code) => exit_code = code,
+      LoopFlow::Continue => exit_code = 0,
+      LoopFlow::Break => {
+        exit_code = 0;
+        break;
+      }
+      LoopFlow::Return(result) => return result,
+    }
+  }
+  ExecuteResult::Continue(exit_code, changes, async_handles)
+}
+
+async fn execute_case_clause(
+  case_clause: CaseClause,
+  mut state: ShellState,
+  stdin: ShellPipeReader,
+  stdout: ShellPipeWriter,
+  mut stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  let mut changes = Vec::new();
+  let word = evaluate_string_or_word(
+    case_clause.word,
+    &mut state,
+    &mut changes,
+    stdin.clone(),
+    stderr.clone(),
+  )
+  .await;
+  let word = match word {
+    Ok(word) => word,
+    Err(err) => {
+      return evaluation_error_result(err, &mut stderr);
+    }
+  };
+  for item in case_clause.items {
+    for pattern in item.patterns {
+      let pattern = evaluate_pattern(
+        pattern.into_parts(),
+        &mut state,
+        &mut changes,
         stdin.clone(),
         stderr.clone(),
       )
-      .await,
-    );
+      .await;
+      let pattern = match pattern {
+        Ok(pattern) => pattern,
+        Err(err) => {
+          return evaluation_error_result(err, &mut stderr);
+        }
+      };
+      if pattern.matches(&word) {
+        return execute_sequential_list(
+          item.body,
+          state,
+          stdin,
+          stdout,
+          stderr,
+          AsyncCommandBehavior::Yield,
+        )
+        .await
+        .with_prior_changes(changes);
+      }
+    }
   }
-  execute_command_args(args, state, stdin, stdout, stderr).await
+  // no pattern matched
+  ExecuteResult::Continue(0, changes, Vec::new())
+}
+
+/// How a loop should proceed after executing its condition or body.
+enum LoopFlow {
+  /// Execution completed with the provided exit code.
+  Completed(i32),
+  /// Skip to the next iteration.
+  Continue,
+  /// Stop looping.
+  Break,
+  /// Stop looping and provide this result to the parent.
+  Return(ExecuteResult),
+}
+
+fn apply_loop_result(
+  result: ExecuteResult,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
+  async_handles: &mut Vec<JoinHandle<i32>>,
+) -> LoopFlow {
+  match result {
+    ExecuteResult::Exit(code, sub_handles) => {
+      async_handles.extend(sub_handles);
+      LoopFlow::Return(ExecuteResult::Exit(code, std::mem::take(async_handles)))
+    }
+    ExecuteResult::Continue(code, sub_changes, sub_handles) => {
+      state.apply_changes(&sub_changes);
+      changes.extend(sub_changes);
+      async_handles.extend(sub_handles);
+      LoopFlow::Completed(code)
+    }
+    ExecuteResult::LoopControl(control, sub_changes, sub_handles) => {
+      state.apply_changes(&sub_changes);
+      changes.extend(sub_changes);
+      async_handles.extend(sub_handles);
+      // controlling an outer loop requires stopping this one
+      let outer_control = match control {
+        LoopControl::Break(1) => return LoopFlow::Break,
+        LoopControl::Continue(1) => return LoopFlow::Continue,
+        LoopControl::Break(count) => LoopControl::Break(count - 1),
+        LoopControl::Continue(count) => LoopControl::Continue(count - 1),
+      };
+      LoopFlow::Return(ExecuteResult::LoopControl(
+        outer_control,
+        std::mem::take(changes),
+        std::mem::take(async_handles),
+      ))
+    }
+  }
+}
+
+async fn execute_simple_command(
+  command: SimpleCommand,
+  mut state: ShellState,
+  stdin: ShellPipeReader,
+  stdout: ShellPipeWriter,
+  mut stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  let mut changes = Vec::new();
+  let args = evaluate_args(
+    command.args,
+    &mut state,
+    &mut changes,
+    stdin.clone(),
+    stderr.clone(),
+  )
+  .await;
+  let args = match args {
+    Ok(args) => args,
+    Err(err) => {
+      return evaluation_error_result(err, &mut stderr);
+    }
+  };
+  for env_var in command.env_vars {
+    let value = evaluate_string_or_word(
+      env_var.value,
+      &mut state,
+      &mut changes,
+      stdin.clone(),
+      stderr.clone(),
+    )
+    .await;
+    match value {
+      Ok(value) => state.apply_env_var(&env_var.name, &value),
+      Err(err) => {
+        return evaluation_error_result(err, &mut stderr);
+      }
+    }
+  }
+  execute_command_args(args, state, stdin, stdout, stderr)
+    .await
+    .with_prior_changes(changes)
 }
 
 fn execute_command_args(
   mut args: Vec<String>,
   state: ShellState,
   stdin: ShellPipeReader,
-  mut stdout: ShellPipeWriter,
+  stdout: ShellPipeWriter,
   mut stderr: ShellPipeWriter,
 ) -> FutureExecuteResult {
   macro_rules! execute_with_cancellation {
@@ -550,7 +1108,9 @@ fn execute_command_args(
     if token.is_cancelled() {
       return ExecuteResult::for_cancellation();
     }
-    if let Some(stripped_name) = command_name.strip_prefix('!') {
+    if let Some(body) = state.get_function(&command_name).cloned() {
+      execute_function(body, args, state, stdin, stdout, stderr).await
+    } else if let Some(stripped_name) = command_name.strip_prefix('!') {
       let _ = stderr.write_line(
         &format!(concat!(
           "History expansion is not supported:\n",
@@ -566,11 +1126,14 @@ fn execute_command_args(
       cd_command(&cwd, args, stderr)
     } else if command_name == "exit" {
       exit_command(args, stderr)
+    } else if command_name == "break" {
+      break_command(args, state.loop_depth(), stderr)
+    } else if command_name == "continue" {
+      continue_command(args, state.loop_depth(), stderr)
     } else if command_name == "pwd" {
       pwd_command(state.cwd(), args, stdout, stderr)
     } else if command_name == "echo" {
-      let _ = stdout.write_line(&args.join(" "));
-      ExecuteResult::from_exit_code(0)
+      echo_command(args, stdout)
     } else if command_name == "true" {
       // ignores additional arguments
       ExecuteResult::from_exit_code(0)
@@ -664,6 +1227,28 @@ fn execute_command_args(
   }.boxed()
 }
 
+async fn execute_function(
+  body: Arc<SequentialList>,
+  args: Vec<String>,
+  mut state: ShellState,
+  stdin: ShellPipeReader,
+  stdout: ShellPipeWriter,
+  stderr: ShellPipeWriter,
+) -> ExecuteResult {
+  // the positional parameters are scoped to the call and
+  // are not part of the changes provided to the caller
+  state.set_args(args);
+  execute_sequential_list(
+    body.as_ref().clone(),
+    state,
+    stdin,
+    stdout,
+    stderr,
+    AsyncCommandBehavior::Yield,
+  )
+  .await
+}
+
 fn evaluate_export_command(args: Vec<String>) -> ExecuteResult {
   let mut changes = Vec::new();
   for arg in args {
@@ -728,8 +1313,7 @@ fn resolve_command_path(
     let uc_command_name = command_name.to_uppercase();
     let path_ext = state
       .get_var("PATHEXT")
-      .map(|s| s.as_str())
-      .unwrap_or(".EXE;.CMD;.BAT;.COM");
+      .unwrap_or(Cow::Borrowed(".EXE;.CMD;.BAT;.COM"));
     let command_exts = path_ext
       .split(';')
       .map(|s| s.trim().to_uppercase())
@@ -774,60 +1358,225 @@ fn resolve_command_path(
 
 async fn evaluate_args(
   args: Vec<StringOrWord>,
-  state: &ShellState,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
   stdin: ShellPipeReader,
   stderr: ShellPipeWriter,
-) -> Vec<String> {
+) -> Result<Vec<String>> {
   let mut result = Vec::new();
   for arg in args {
     match arg {
-      StringOrWord::Word(parts) => {
+      StringOrWord::Word { parts, .. }
+        if parts
+          .iter()
+          .any(|part| matches!(part, StringPart::Glob { .. })) =>
+      {
+        let paths =
+          evaluate_glob(parts, state, changes, stdin.clone(), stderr.clone())
+            .await?;
+        result.extend(paths);
+      }
+      StringOrWord::Word { parts, .. } => {
         // todo(dsherret): maybe we should have this work like sh and I believe
         // reparse then continually re-evaluate until there's only strings left.
-        let text =
-          evaluate_string_parts(parts, state, stdin.clone(), stderr.clone())
-            .await;
+        let text = evaluate_string_parts(
+          parts,
+          state,
+          changes,
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await?;
         result.extend(text);
       }
-      StringOrWord::String(parts) => {
-        result.push(
-          evaluate_string_parts(parts, state, stdin.clone(), stderr.clone())
-            .await
-            .join(" "),
-        );
+      StringOrWord::String { parts, .. } => {
+        let fields = evaluate_quoted_string_parts(
+          parts,
+          state,
+          changes,
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await?;
+        result.extend(fields);
+      }
+    }
+  }
+  Ok(result)
+}
+
+/// Expands a word with unquoted glob characters to the matching paths.
+/// A glob that doesn't match any paths is an error rather than being
+/// passed through as-is.
+async fn evaluate_glob(
+  parts: Vec<StringPart>,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
+  stdin: ShellPipeReader,
+  stderr: ShellPipeWriter,
+) -> Result<Vec<String>> {
+  let mut glob = Glob::new();
+  let mut text = String::new();
+  for part in parts {
+    match part {
+      StringPart::Text {
+        value: part_text, ..
+      } => {
+        glob.push_literal(&part_text);
+        text.push_str(&part_text);
+      }
+      StringPart::Glob {
+        value: part_text, ..
+      } => {
+        glob.push_glob(&part_text);
+        text.push_str(&part_text);
+      }
+      StringPart::Tilde { .. } => {
+        let home_dir = state.home_dir();
+        glob.push_literal(&home_dir);
+        text.push_str(&home_dir);
+      }
+      // substituted text is matched literally
+      StringPart::Variable { name, .. } => {
+        if let Some(value) = state.get_var(&name) {
+          glob.push_literal(&value);
+          text.push_str(&value);
+        }
+      }
+      StringPart::ParameterExpansion(expansion) => {
+        let value = evaluate_parameter_expansion(
+          expansion,
+          state,
+          changes,
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await?;
+        if let Some(value) = value {
+          glob.push_literal(&value);
+          text.push_str(&value);
+        }
+      }
+      StringPart::Arithmetic { expr, .. } => {
+        let value = evaluate_arithmetic(&expr, state)?.to_string();
+        glob.push_literal(&value);
+        text.push_str(&value);
+      }
+      StringPart::Command { list, .. } => {
+        let part_text = evaluate_command_substitution(
+          list,
+          &state.with_child_token(),
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await;
+        glob.push_literal(&part_text);
+        text.push_str(&part_text);
       }
     }
   }
-  result
+  let paths = glob.expand(state.cwd());
+  if paths.is_empty() {
+    bail!("glob: no matches found '{}'", text);
+  }
+  Ok(paths)
 }
 
 async fn evaluate_string_or_word(
   string_or_word: StringOrWord,
-  state: &ShellState,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
   stdin: ShellPipeReader,
   stderr: ShellPipeWriter,
-) -> String {
-  evaluate_string_parts(string_or_word.into_parts(), state, stdin, stderr)
-    .await
-    .join(" ")
+) -> Result<String> {
+  let words = match string_or_word {
+    StringOrWord::Word { parts, .. } => {
+      evaluate_string_parts(parts, state, changes, stdin, stderr).await?
+    }
+    StringOrWord::String { parts, .. } => {
+      evaluate_quoted_string_parts(parts, state, changes, stdin, stderr).await?
+    }
+  };
+  Ok(words.join(" "))
 }
 
+/// Evaluates the parts of an unquoted word, splitting
+/// the substituted text into separate words.
 async fn evaluate_string_parts(
   parts: Vec<StringPart>,
-  state: &ShellState,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
   stdin: ShellPipeReader,
   stderr: ShellPipeWriter,
-) -> Vec<String> {
+) -> Result<Vec<String>> {
+  evaluate_string_parts_inner(parts, false, state, changes, stdin, stderr).await
+}
+
+/// Evaluates the parts of a quoted string without splitting the
+/// substituted text, except `"$@"` expands to a separate word for
+/// each positional parameter (or none when there are none).
+async fn evaluate_quoted_string_parts(
+  parts: Vec<StringPart>,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
+  stdin: ShellPipeReader,
+  stderr: ShellPipeWriter,
+) -> Result<Vec<String>> {
+  evaluate_string_parts_inner(parts, true, state, changes, stdin, stderr).await
+}
+
+async fn evaluate_string_parts_inner(
+  parts: Vec<StringPart>,
+  is_quoted: bool,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
+  stdin: ShellPipeReader,
+  stderr: ShellPipeWriter,
+) -> Result<Vec<String>> {
   let mut result = Vec::new();
   let mut current_text = String::new();
+  let mut has_empty_args_expansion = false;
   for part in parts {
     let evaluation_result_text = match part {
-      StringPart::Text(text) => {
+      part if is_quoted && is_positional_args(&part) => {
+        match state.args().split_last() {
+          Some((last_arg, args)) => {
+            for arg in args {
+              current_text.push_str(arg);
+              result.push(std::mem::take(&mut current_text));
+            }
+            current_text.push_str(last_arg);
+          }
+          None => has_empty_args_expansion = true,
+        }
+        None
+      }
+      StringPart::Text { value: text, .. }
+      | StringPart::Glob { value: text, .. } => {
         current_text.push_str(&text);
         None
       }
-      StringPart::Variable(name) => state.get_var(&name).map(|v| v.to_string()),
-      StringPart::Command(list) => Some(
+      StringPart::Tilde { .. } => {
+        current_text.push_str(&state.home_dir());
+        None
+      }
+      StringPart::Variable { name, .. } => {
+        state.get_var(&name).map(|v| v.into_owned())
+      }
+      StringPart::ParameterExpansion(expansion) => {
+        evaluate_parameter_expansion(
+          expansion,
+          state,
+          changes,
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await?
+      }
+      StringPart::Arithmetic { expr, .. } => {
+        Some(evaluate_arithmetic(&expr, state)?.to_string())
+      }
+      StringPart::Command { list, .. } => Some(
         evaluate_command_substitution(
           list,
           // contain cancellation to the command substitution
@@ -843,8 +1592,13 @@ async fn evaluate_string_parts(
     // For now we do a very basic string split on whitespace, but in the future
     // we should continue to improve this functionality.
     if let Some(text) = evaluation_result_text {
+      if is_quoted {
+        current_text.push_str(&text);
+        continue;
+      }
+
       let mut parts = text
-        .split(' ')
+        .split([' ', '\t', '\n'])
         .map(|p| p.trim())
         .filter(|p| !p.is_empty())
         .collect::<Vec<_>>();
@@ -866,10 +1620,233 @@ async fn evaluate_string_parts(
       }
     }
   }
-  if !current_text.is_empty() {
+  if is_quoted {
+    // a quoted string is a word even when empty, unless it's only
+    // the expansion of `"$@"` without any positional parameters
+    if !current_text.is_empty()
+      || !result.is_empty()
+      || !has_empty_args_expansion
+    {
+      result.push(current_text);
+    }
+  } else if !current_text.is_empty() {
     result.push(current_text);
   }
-  result
+  Ok(result)
+}
+
+/// Gets if the part expands to all the positional parameters (`$@`).
+fn is_positional_args(part: &StringPart) -> bool {
+  match part {
+    StringPart::Variable { name, .. } => name == "@",
+    StringPart::ParameterExpansion(expansion) => {
+      expansion.name == "@" && expansion.op.is_none()
+    }
+    _ => false,
+  }
+}
+
+/// Evaluates a braced parameter expansion, returning `None` when
+/// there is no text to substitute.
+fn evaluate_parameter_expansion<'a>(
+  expansion: ParameterExpansion,
+  state: &'a mut ShellState,
+  changes: &'a mut Vec<EnvChange>,
+  stdin: ShellPipeReader,
+  stderr: ShellPipeWriter,
+) -> BoxFuture<'a, Result<Option<String>>> {
+  // requires boxed async because of recursive async
+  async move {
+    let name = expansion.name;
+    let value = state.get_var(&name).map(|v| v.into_owned());
+    let is_set = |check_null: bool| match &value {
+      Some(value) => !check_null || !value.is_empty(),
+      None => false,
+    };
+    Ok(match expansion.op {
+      None => value,
+      Some(ParameterExpansionOp::Length) => {
+        Some(value.map(|v| v.chars().count()).unwrap_or(0).to_string())
+      }
+      Some(ParameterExpansionOp::UseDefault { check_null, word }) => {
+        if is_set(check_null) {
+          value
+        } else {
+          Some(
+            evaluate_string_parts(word, state, changes, stdin, stderr)
+              .await?
+              .join(" "),
+          )
+        }
+      }
+      Some(ParameterExpansionOp::AssignDefault { check_null, word }) => {
+        if is_set(check_null) {
+          value
+        } else {
+          // positional and special parameters can't be assigned
+          if !name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            bail!("{}: cannot assign in this way", name);
+          }
+          let text = evaluate_string_parts(word, state, changes, stdin, stderr)
+            .await?
+            .join(" ");
+          let change = EnvChange::SetShellVar(name, text.clone());
+          state.apply_change(&change);
+          changes.push(change);
+          Some(text)
+        }
+      }
+      Some(ParameterExpansionOp::UseAlternative { check_null, word }) => {
+        if is_set(check_null) {
+          Some(
+            evaluate_string_parts(word, state, changes, stdin, stderr)
+              .await?
+              .join(" "),
+          )
+        } else {
+          None
+        }
+      }
+      Some(ParameterExpansionOp::ErrorIfUnset { check_null, word }) => {
+        if is_set(check_null) {
+          value
+        } else {
+          let message =
+            evaluate_string_parts(word, state, changes, stdin, stderr)
+              .await?
+              .join(" ");
+          let message = if !message.is_empty() {
+            format!("{}: {}", name, message)
+          } else if check_null {
+            format!("{}: parameter null or not set", name)
+          } else {
+            format!("{}: parameter not set", name)
+          };
+          return Err(UnsetParameterError(message).into());
+        }
+      }
+      Some(ParameterExpansionOp::RemovePrefix { longest, pattern }) => {
+        let value = value.unwrap_or_default();
+        let pattern =
+          evaluate_pattern(pattern, state, changes, stdin, stderr).await?;
+        let mut indexes = char_boundaries(&value);
+        if longest {
+          indexes.reverse();
+        }
+        let index = indexes
+          .into_iter()
+          .find(|index| pattern.matches(&value[..*index]));
+        Some(match index {
+          Some(index) => value[index..].to_string(),
+          None => value,
+        })
+      }
+      Some(ParameterExpansionOp::RemoveSuffix { longest, pattern }) => {
+        let value = value.unwrap_or_default();
+        let pattern =
+          evaluate_pattern(pattern, state, changes, stdin, stderr).await?;
+        let mut indexes = char_boundaries(&value);
+        if !longest {
+          indexes.reverse();
+        }
+        let index = indexes
+          .into_iter()
+          .find(|index| pattern.matches(&value[*index..]));
+        Some(match index {
+          Some(index) => value[..index].to_string(),
+          None => value,
+        })
+      }
+    })
+  }
+  .boxed()
+}
+
+/// Error for a `${VAR:?message}` expansion of an unset parameter,
+/// which exits the shell.
+#[derive(Debug)]
+struct UnsetParameterError(String);
+
+impl std::fmt::Display for UnsetParameterError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for UnsetParameterError {}
+
+/// Writes an error from evaluating a word and gets the result
+/// of the command that was being evaluated.
+fn evaluation_error_result(
+  err: anyhow::Error,
+  stderr: &mut ShellPipeWriter,
+) -> ExecuteResult {
+  let _ = stderr.write_line(&err.to_string());
+  if err.is::<UnsetParameterError>() {
+    ExecuteResult::Exit(1, Vec::new())
+  } else {
+    ExecuteResult::from_exit_code(1)
+  }
+}
+
+/// Gets the byte indexes of the character boundaries in the text
+/// from the start to the end of the text.
+fn char_boundaries(text: &str) -> Vec<usize> {
+  text
+    .char_indices()
+    .map(|(index, _)| index)
+    .chain(std::iter::once(text.len()))
+    .collect()
+}
+
+async fn evaluate_pattern(
+  parts: Vec<StringPart>,
+  state: &mut ShellState,
+  changes: &mut Vec<EnvChange>,
+  stdin: ShellPipeReader,
+  stderr: ShellPipeWriter,
+) -> Result<Pattern> {
+  let mut result = Pattern::new();
+  for part in parts {
+    match part {
+      StringPart::Text { value: text, .. } => result.push_literal(&text),
+      StringPart::Glob { value: glob, .. } => result.push_glob(&glob),
+      StringPart::Tilde { .. } => result.push_literal(&state.home_dir()),
+      // substituted text is matched literally
+      StringPart::Variable { name, .. } => {
+        if let Some(value) = state.get_var(&name) {
+          result.push_literal(&value);
+        }
+      }
+      StringPart::ParameterExpansion(expansion) => {
+        let value = evaluate_parameter_expansion(
+          expansion,
+          state,
+          changes,
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await?;
+        if let Some(value) = value {
+          result.push_literal(&value);
+        }
+      }
+      StringPart::Arithmetic { expr, .. } => {
+        result.push_literal(&evaluate_arithmetic(&expr, state)?.to_string())
+      }
+      StringPart::Command { list, .. } => {
+        let text = evaluate_command_substitution(
+          list,
+          &state.with_child_token(),
+          stdin.clone(),
+          stderr.clone(),
+        )
+        .await;
+        result.push_literal(&text);
+      }
+    }
+  }
+  Ok(result)
 }
 
 async fn evaluate_command_substitution(
@@ -890,8 +1867,8 @@ async fn evaluate_command_substitution(
   })
   .await;
 
-  // Remove the trailing newline and then replace inner newlines with a space
-  // This seems to be what sh does, but I'm not entirely sure:
+  // Remove the trailing newline. The inner newlines are kept for when
+  // it's quoted and otherwise separate words like spaces:
   //
   // > echo $(echo 1 && echo -e "\n2\n")
   // 1 2
@@ -899,8 +1876,7 @@ async fn evaluate_command_substitution(
     .strip_suffix("\r\n")
     .or_else(|| text.strip_suffix('\n'))
     .unwrap_or(&text)
-    .replace("\r\n", " ")
-    .replace('\n', " ")
+    .replace("\r\n", "\n")
 }
 
 async fn execute_with_stdout_as_text(
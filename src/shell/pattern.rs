@@ -0,0 +1,207 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+/// A shell pattern (ex. `*.ts` or `[!a-c]?`) that text can be matched against.
+///
+/// Patterns are built up from literal text, which is always matched exactly,
+/// and glob text, which may contain the special pattern characters.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Pattern {
+  tokens: Vec<PatternToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternToken {
+  /// Matches the provided character.
+  Char(char),
+  /// `?` - Matches any single character.
+  AnyChar,
+  /// `*` - Matches any string, including the empty string.
+  AnyString,
+  /// `[...]` - Matches a single character in the bracket expression.
+  Bracket(BracketExpr),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct BracketExpr {
+  negated: bool,
+  ranges: Vec<(char, char)>,
+}
+
+impl BracketExpr {
+  fn matches(&self, c: char) -> bool {
+    let is_in_ranges = self
+      .ranges
+      .iter()
+      .any(|(start, end)| *start <= c && c <= *end);
+    is_in_ranges != self.negated
+  }
+}
+
+impl Pattern {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Adds text that should be matched exactly.
+  pub fn push_literal(&mut self, text: &str) {
+    self.tokens.extend(text.chars().map(PatternToken::Char));
+  }
+
+  /// Adds text where `*`, `?`, and `[...]` have special meaning.
+  pub fn push_glob(&mut self, text: &str) {
+    let chars = text.chars().collect::<Vec<_>>();
+    let mut i = 0;
+    while i < chars.len() {
+      match chars[i] {
+        '*' => self.tokens.push(PatternToken::AnyString),
+        '?' => self.tokens.push(PatternToken::AnyChar),
+        '[' => match parse_bracket_expr(&chars[i + 1..]) {
+          Some((expr, len)) => {
+            self.tokens.push(PatternToken::Bracket(expr));
+            i += len;
+          }
+          // an unclosed bracket is treated as a regular character
+          None => self.tokens.push(PatternToken::Char('[')),
+        },
+        '\\' if i + 1 < chars.len() => {
+          i += 1;
+          self.tokens.push(PatternToken::Char(chars[i]));
+        }
+        c => self.tokens.push(PatternToken::Char(c)),
+      }
+      i += 1;
+    }
+  }
+
+  /// Gets if the pattern matches the entire provided text.
+  pub fn matches(&self, text: &str) -> bool {
+    let chars = text.chars().collect::<Vec<_>>();
+    matches_tokens(&self.tokens, &chars)
+  }
+}
+
+/// Parses the bracket expression found after a `[`, returning
+/// the expression and the number of characters consumed including
+/// the closing `]`.
+fn parse_bracket_expr(chars: &[char]) -> Option<(BracketExpr, usize)> {
+  let mut i = 0;
+  let negated = matches!(chars.first(), Some('!' | '^'));
+  if negated {
+    i += 1;
+  }
+  let mut ranges = Vec::new();
+  let start_index = i;
+  while i < chars.len() {
+    let c = chars[i];
+    // a `]` at the start of the expression is a regular character
+    if c == ']' && i > start_index {
+      return Some((BracketExpr { negated, ranges }, i + 1));
+    }
+    let is_range = chars.get(i + 1) == Some(&'-')
+      && chars.get(i + 2).map(|c| *c != ']').unwrap_or(false);
+    if is_range {
+      ranges.push((c, chars[i + 2]));
+      i += 3;
+    } else {
+      ranges.push((c, c));
+      i += 1;
+    }
+  }
+  None
+}
+
+fn matches_tokens(tokens: &[PatternToken], chars: &[char]) -> bool {
+  // iterative wildcard matching that backtracks to the last `*`
+  let mut token_index = 0;
+  let mut char_index = 0;
+  let mut last_star: Option<(usize, usize)> = None;
+  while char_index < chars.len() {
+    let c = chars[char_index];
+    match tokens.get(token_index) {
+      Some(PatternToken::AnyString) => {
+        last_star = Some((token_index, char_index));
+        token_index += 1;
+        continue;
+      }
+      Some(PatternToken::AnyChar) => {
+        token_index += 1;
+        char_index += 1;
+        continue;
+      }
+      Some(PatternToken::Char(expected)) if *expected == c => {
+        token_index += 1;
+        char_index += 1;
+        continue;
+      }
+      Some(PatternToken::Bracket(expr)) if expr.matches(c) => {
+        token_index += 1;
+        char_index += 1;
+        continue;
+      }
+      _ => {}
+    }
+    match last_star {
+      Some((star_token_index, star_char_index)) => {
+        // have the last `*` consume one more character and try again
+        last_star = Some((star_token_index, star_char_index + 1));
+        token_index = star_token_index + 1;
+        char_index = star_char_index + 1;
+      }
+      None => return false,
+    }
+  }
+  tokens[token_index..]
+    .iter()
+    .all(|token| *token == PatternToken::AnyString)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn glob(text: &str) -> Pattern {
+    let mut pattern = Pattern::new();
+    pattern.push_glob(text);
+    pattern
+  }
+
+  #[test]
+  fn matches_globs() {
+    assert!(glob("*").matches(""));
+    assert!(glob("*").matches("anything"));
+    assert!(glob("*.ts").matches("mod.ts"));
+    assert!(!glob("*.ts").matches("mod.js"));
+    assert!(glob("a*b*c").matches("aXbYbZc"));
+    assert!(!glob("a*b*c").matches("aXbYbZ"));
+    assert!(glob("?").matches("a"));
+    assert!(!glob("?").matches(""));
+    assert!(!glob("?").matches("ab"));
+    assert!(glob("a?c").matches("abc"));
+    assert!(glob("[abc]").matches("b"));
+    assert!(!glob("[abc]").matches("d"));
+    assert!(glob("[a-c]x").matches("bx"));
+    assert!(!glob("[!a-c]x").matches("bx"));
+    assert!(glob("[^a-c]x").matches("dx"));
+    assert!(glob("[]]").matches("]"));
+    assert!(glob("[a-]").matches("-"));
+    assert!(glob("[").matches("["));
+    assert!(glob("[ab").matches("[ab"));
+    assert!(glob("\\*").matches("*"));
+    assert!(!glob("\\*").matches("a"));
+    assert!(glob("linux|darwin").matches("linux|darwin"));
+  }
+
+  #[test]
+  fn matches_literals() {
+    let mut pattern = Pattern::new();
+    pattern.push_literal("*.");
+    pattern.push_glob("*");
+    assert!(pattern.matches("*.ts"));
+    assert!(!pattern.matches("mod.ts"));
+
+    let mut pattern = Pattern::new();
+    pattern.push_literal("[a]?");
+    assert!(pattern.matches("[a]?"));
+    assert!(!pattern.matches("ab"));
+  }
+}
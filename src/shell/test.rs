@@ -122,407 +122,1410 @@ pub async fn boolean_logic() {
     .await;
 }
 
+#[tokio::test]
+pub async fn if_clause() {
+  TestBuilder::new()
+    .command("if true; then echo 1; else echo 2; fi")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("if false; then echo 1; else echo 2; fi")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(
+      "if false; then echo 1; elif echo 2 && false; then echo 3; elif true; then echo 4; fi",
+    )
+    .assert_stdout("2\n4\n")
+    .run()
+    .await;
+
+  // no branch taken
+  TestBuilder::new()
+    .command("if false; then echo 1; fi && echo ok")
+    .assert_stdout("ok\n")
+    .run()
+    .await;
+
+  // exit code of the executed branch
+  TestBuilder::new()
+    .command("if true; then false; fi || echo failed")
+    .assert_stdout("failed\n")
+    .run()
+    .await;
+
+  // changes apply to the current shell
+  TestBuilder::new()
+    .command("if VAR=1; then export OTHER=2; fi; echo $VAR $OTHER")
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("if true; then exit 5; fi; echo 1")
+    .assert_exit_code(5)
+    .run()
+    .await;
+
+  // redirects
+  TestBuilder::new()
+    .command("if true; then echo 1; echo 2; fi > file.txt")
+    .assert_file_equals("file.txt", "1\n2\n")
+    .run()
+    .await;
+
+  // reserved words as arguments
+  TestBuilder::new()
+    .command("if echo if then; then echo fi; fi")
+    .assert_stdout("if then\nfi\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn for_clause() {
+  TestBuilder::new()
+    .command("for i in 1 2 3; do echo $i; done")
+    .assert_stdout("1\n2\n3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"for i in $(echo a b) "c d"; do echo "[$i]"; done"#)
+    .assert_stdout("[a]\n[b]\n[c d]\n")
+    .run()
+    .await;
+
+  // no words
+  TestBuilder::new()
+    .command("for i in; do echo $i; done && echo done")
+    .assert_stdout("done\n")
+    .run()
+    .await;
+
+  // the variable and other changes are kept after the loop
+  TestBuilder::new()
+    .command("for i in 1 2; do export LAST=$i; done; echo $i $LAST")
+    .assert_stdout("2 2\n")
+    .run()
+    .await;
+
+  // exit code of the last iteration
+  TestBuilder::new()
+    .command("for i in 1 2; do false; done || echo failed")
+    .assert_stdout("failed\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("for i in 1 2 3; do echo $i; exit 4; done")
+    .assert_stdout("1\n")
+    .assert_exit_code(4)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("mkdir dist && cd dist && for f in a b; do echo $f > $f.txt; done")
+    .assert_file_equals("dist/a.txt", "a\n")
+    .assert_file_equals("dist/b.txt", "b\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn while_clause() {
+  TestBuilder::new()
+    .ensure_temp_dir()
+    .command("while ! cat file.txt; do echo waiting && echo 1 > file.txt; done")
+    .assert_stdout("waiting\n1\n")
+    .assert_stderr(&format!("cat: file.txt: {}\n", no_such_file_error_text()))
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .ensure_temp_dir()
+    .command("until cat file.txt; do echo waiting && echo 1 > file.txt; done")
+    .assert_stdout("waiting\n1\n")
+    .assert_stderr(&format!("cat: file.txt: {}\n", no_such_file_error_text()))
+    .run()
+    .await;
+
+  // condition never succeeds
+  TestBuilder::new()
+    .command("while false; do echo 1; done && echo 2")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("while true; do exit 3; done; echo 1")
+    .assert_exit_code(3)
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn break_continue() {
+  TestBuilder::new()
+    .command("while true; do echo 1; break; echo 2; done; echo 3")
+    .assert_stdout("1\n3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("for i in 1 2 3; do echo $i; continue; echo no; done")
+    .assert_stdout("1\n2\n3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("for i in 1 2 3; do echo $i && break; done && echo done")
+    .assert_stdout("1\ndone\n")
+    .run()
+    .await;
+
+  // nested loops
+  TestBuilder::new()
+    .command(
+      "for i in 1 2; do for j in a b; do echo $i$j; break 2; done; done; echo 3",
+    )
+    .assert_stdout("1a\n3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(
+      "for i in 1 2; do for j in a b; do echo $i$j; continue 2; echo no; done; echo no; done",
+    )
+    .assert_stdout("1a\n2a\n")
+    .run()
+    .await;
+
+  // count greater than the number of loops
+  TestBuilder::new()
+    .command("for i in 1 2; do for j in a b; do break 5; done; done; echo 1")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  // changes before a break are kept
+  TestBuilder::new()
+    .command("while true; do VAR=1; break; done; echo $VAR")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  // subshells and pipe sequences can't control outer loops
+  TestBuilder::new()
+    .command("for i in 1 2; do (break); echo $i; done")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("for i in 1 2; do break | echo $i; done")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  // outside a loop
+  TestBuilder::new()
+    .command("break; echo 1")
+    .assert_stdout("1\n")
+    .assert_stderr(
+      "break: only meaningful in a `for', `while', or `until' loop\n",
+    )
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn case_clause() {
+  TestBuilder::new()
+    .command(
+      "case $OS in linux|darwin) echo unix;; windows) echo windows;; esac",
+    )
+    .env_var("OS", "darwin")
+    .assert_stdout("unix\n")
+    .run()
+    .await;
+
+  // default arm
+  TestBuilder::new()
+    .command("case $MODE in release) echo 1;; *) echo 2;; esac")
+    .env_var("MODE", "debug")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  // only the first matching arm runs
+  TestBuilder::new()
+    .command("case abc in a*) echo 1;; *c) echo 2;; esac")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("case a.ts in (*.js) echo js;; (*.[jt]s) echo ts; esac")
+    .assert_stdout("ts\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("case ab in ?) echo 1;; ??) echo 2;; esac")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  // quoted and substituted pattern characters are matched literally
+  TestBuilder::new()
+    .command("case abc in \"a*\") echo 1;; $VAR) echo 2;; a\\*) echo 3;; *) echo 4;; esac")
+    .env_var("VAR", "*")
+    .assert_stdout("4\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("case '*' in $VAR) echo 1;; esac")
+    .env_var("VAR", "*")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  // no match
+  TestBuilder::new()
+    .command("false; case a in b) echo 1;; esac && echo 2")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  // exit code and changes from the arm are kept
+  TestBuilder::new()
+    .command("case a in a) VAR=1 && false;; esac || echo $VAR")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  // break within a case in a loop
+  TestBuilder::new()
+    .command("for i in 1 2 3; do case $i in 2) break;; esac; echo $i; done")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn special_parameters() {
+  TestBuilder::new()
+    .command(
+      "echo $?; false; echo $?; (exit 3) || echo $?; true && echo $?; false; true & echo $?",
+    )
+    .assert_stdout("0\n1\n3\n0\n0\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("if (exit 2); then echo yes; else echo $?; fi")
+    .assert_stdout("2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("echo $$")
+    .assert_stdout(&format!("{}\n", std::process::id()))
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .args(&["--release", "a b"])
+    .command(r#"echo $# $1 "$2" $3 && echo $@ && echo "$*""#)
+    .assert_stdout("2 --release a b\n--release a b\n--release a b\n")
+    .run()
+    .await;
+
+  // only a single digit is taken for an unbraced positional parameter
+  TestBuilder::new()
+    .args(&["a", "b", "c", "d", "e", "f", "g", "h", "i", "j"])
+    .command(r#"echo $1abc $10 ${10} "$1-x""#)
+    .assert_stdout("aabc a0 j a-x\n")
+    .run()
+    .await;
+
+  // a quoted $@ expands to a word per positional parameter
+  TestBuilder::new()
+    .args(&["x y", "z"])
+    .command(
+      r#"count() { echo $#; }; count "$@"; count "$*"; count $@; for a in "$@"; do echo "[$a]"; done"#,
+    )
+    .assert_stdout("2\n1\n3\n[x y]\n[z]\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"count() { echo $#; }; count "$@"; count "a$@" "$*""#)
+    .assert_stdout("0\n2\n")
+    .run()
+    .await;
+
+  // functions have their own positional parameters
+  TestBuilder::new()
+    .args(&["script"])
+    .command("func() { echo $1; }; func call; echo $1")
+    .assert_stdout("call\nscript\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn functions() {
+  TestBuilder::new()
+    .command("greet() { echo Hello $1!; }; greet World && greet there")
+    .assert_stdout("Hello World!\nHello there!\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("count() { echo $# $@; }; count; count a b c; echo $# $1")
+    .assert_stdout("0\n3 a b c\n0\n")
+    .run()
+    .await;
+
+  // positional parameters are scoped to the call
+  TestBuilder::new()
+    .command(
+      "inner() { echo inner $1; }; outer() { inner b; echo outer $1; }; outer a",
+    )
+    .assert_stdout("inner b\nouter a\n")
+    .run()
+    .await;
+
+  // changes within the function are kept
+  TestBuilder::new()
+    .command("setup() { export VAR=1 && cd sub; }; setup; echo $VAR; pwd")
+    .directory("sub")
+    .assert_stdout("1\n$TEMP_DIR/sub\n")
+    .run()
+    .await;
+
+  // functions take precedence over builtins and can be redefined
+  TestBuilder::new()
+    .command("echo() { pwd; }; echo 1; echo() { exit 2; }; echo; pwd")
+    .assert_stdout("$TEMP_DIR\n")
+    .assert_exit_code(2)
+    .ensure_temp_dir()
+    .run()
+    .await;
+
+  // exit code of the last command
+  TestBuilder::new()
+    .command("fail() { true && false; }; fail || echo failed")
+    .assert_stdout("failed\n")
+    .run()
+    .await;
+
+  // recursion
+  TestBuilder::new()
+    .command(
+      "countdown() { echo $1; case $1 in 3) countdown 2;; 2) countdown 1;; esac; }; countdown 3",
+    )
+    .assert_stdout("3\n2\n1\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn groups() {
+  // groups keep changes
+  TestBuilder::new()
+    .command("{ cd sub && export VAR=1; } && echo $VAR && pwd")
+    .directory("sub")
+    .assert_stdout("1\n$TEMP_DIR/sub\n")
+    .run()
+    .await;
+
+  // subshells don't
+  TestBuilder::new()
+    .command("(cd sub && export VAR=1 && VAR2=2); echo $VAR$VAR2 && pwd")
+    .directory("sub")
+    .assert_stdout("\n$TEMP_DIR\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("{ cd sub && echo 1; echo 2; } > log.txt; pwd")
+    .directory("sub")
+    .assert_stdout("$TEMP_DIR/sub\n")
+    .assert_file_equals("log.txt", "1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("{ echo 1; false; } || echo 2")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("{ exit 3; }; echo 1")
+    .assert_exit_code(3)
+    .run()
+    .await;
+
+  // groups in a pipe sequence don't change the parent
+  TestBuilder::new()
+    .command("{ VAR=1; echo 1; } | cat; echo $VAR 2")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+}
+
 #[tokio::test]
 pub async fn exit() {
   TestBuilder::new()
-    .command("exit 1")
-    .assert_exit_code(1)
+    .command("exit 1")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("exit 5")
+    .assert_exit_code(5)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("exit 258 && echo 1")
+    .assert_exit_code(2)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("(exit 0) && echo 1")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("(exit 1) && echo 1")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("echo 1 && (exit 1)")
+    .assert_stdout("1\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("exit ; echo 2")
+    .assert_exit_code(1)
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn async_commands() {
+  TestBuilder::new()
+    .command("sleep 0.1 && echo 2 & echo 1")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("(sleep 0.1 && echo 2 &) ; echo 1")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("(sleep 0.1 && echo 2) & echo 1")
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(
+      "$(sleep 0.1 && echo 1 & $(sleep 0.2 && echo 2 & echo echo) & echo echo)",
+    )
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("exit 1 & exit 0")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // should not output because the `exit 1` will cancel the sleep
+  TestBuilder::new()
+    .command("sleep 5 && echo 1 & exit 1")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // should fail when async command exits
+  TestBuilder::new()
+    .command("exit 1 & exit 0")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // should fail when async command fails and cancel any running command
+  TestBuilder::new()
+    .command("deno eval 'Deno.exit(1)' & sleep 5 && echo 2 & echo 1")
+    .assert_stdout("1\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // should cancel running command
+  TestBuilder::new()
+    .command("sleep 10 & sleep 0.5 && deno eval 'Deno.exit(2)' & deno eval 'console.log(1); setTimeout(() => { console.log(3) }, 10_000);'")
+    .assert_stdout("1\n")
+    .assert_exit_code(2)
+    .run()
+    .await;
+
+  // should be able to opt out by doing an `|| exit 0`
+  TestBuilder::new()
+    .command("deno eval 'Deno.exit(1)' || exit 0 & echo 1")
+    .assert_stdout("1\n")
+    .assert_exit_code(0)
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn command_substition() {
+  TestBuilder::new()
+    .command("echo $(echo 1)")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("echo $(echo 1 && echo 2)")
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+
+  // async inside subshell should wait
+  TestBuilder::new()
+    .command("$(sleep 0.1 && echo 1 & echo echo) 2")
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+  TestBuilder::new()
+    .command("$(sleep 0.1 && echo 1 && exit 5 &) ; echo 2")
+    .assert_stdout("2\n")
+    .assert_stderr("1: command not found\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn shell_variables() {
+  TestBuilder::new()
+    .command(r#"echo $VAR && VAR=1 && echo $VAR && deno eval 'console.log(Deno.env.get("VAR"))'"#)
+    .assert_stdout("\n1\nundefined\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"VAR=1 && echo $VAR$VAR"#)
+    .assert_stdout("11\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"VAR=1 && echo Test$VAR && echo $(echo "Test: $VAR") ; echo CommandSub$($VAR); echo $ ; echo \$VAR"#)
+    .assert_stdout("Test1\nTest: 1\nCommandSub\n$\n$VAR\n")
+    .assert_stderr("1: command not found\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn env_variables() {
+  TestBuilder::new()
+    .command(r#"echo $VAR && export VAR=1 && echo $VAR && deno eval 'console.log(Deno.env.get("VAR"))'"#)
+    .assert_stdout("\n1\n1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"export VAR=1 VAR2=testing VAR3="test this out" && echo $VAR $VAR2 $VAR3"#)
+    .assert_stdout("1 testing test this out\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn sequential_lists() {
+  TestBuilder::new()
+    .command(r#"echo 1 ; sleep 0.1 && echo 4 & echo 2 ; echo 3;"#)
+    .assert_stdout("1\n2\n3\n4\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn pipeline() {
+  TestBuilder::new()
+    .command(r#"echo 1 | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo 1 | echo 2 && echo 3"#)
+    .assert_stdout("2\n3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo $(sleep 0.1 && echo 2 & echo 1) | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo 2 | echo 1 | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"deno eval 'console.log(1); console.error(2);' | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1\n")
+    .assert_stderr("2\n")
+    .run()
+    .await;
+
+  // stdout and stderr pipeline
+
+  TestBuilder::new()
+    .command(r#"deno eval 'console.log(1); console.error(2);' |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    // add bit of a delay while outputting stdout so that it doesn't race with stderr
+    .command(r#"deno eval 'console.log(1); console.error(2);' | deno eval --unstable 'setTimeout(async () => { await Deno.stdin.readable.pipeTo(Deno.stderr.writable) }, 10)' |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stderr.writable)'"#)
+    // still outputs 2 because the first command didn't pipe stderr
+    .assert_stderr("2\n1\n")
+    .run()
+    .await;
+
+  // |& pipeline should still pipe stdout
+  TestBuilder::new()
+    .command(r#"echo 1 |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
+    .assert_stdout("1\n")
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn negated() {
+  TestBuilder::new()
+    .command(r#"! echo 1 && echo 2"#)
+    .assert_stdout("1\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! echo 1 || echo 2"#)
+    .assert_stdout("1\n2\n")
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! (echo 1 | echo 2 && echo 3) || echo 4"#)
+    .assert_stdout("2\n3\n4\n")
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! echo 1 | echo 2 && echo 3"#)
+    .assert_stdout("2\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! (exit 5) && echo 1"#)
+    .assert_stdout("1\n")
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! exit 5 && echo 1"#)
+    .assert_exit_code(5)
+    .run()
+    .await;
+  TestBuilder::new()
+    .command(r#"! echo 1 && echo 2 &"#)
+    .assert_stdout("1\n")
+    // differing behaviour to shells, where this async command will actually fail
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // test no spaces
+  TestBuilder::new()
+    .command(r#"!echo 1 && echo 2"#)
+    .assert_stderr("History expansion is not supported:\n  !echo\n  ~\n\nPerhaps you meant to add a space after the exclamation point to negate the command?\n  ! echo\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+}
+
+#[tokio::test]
+pub async fn redirects() {
+  TestBuilder::new()
+    .command(r#"echo 5 6 7 > test.txt"#)
+    .assert_file_equals("test.txt", "5 6 7\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo 1 2 3 && echo 1 > test.txt"#)
+    .assert_stdout("1 2 3\n")
+    .assert_file_equals("test.txt", "1\n")
+    .run()
+    .await;
+
+  // subdir
+  TestBuilder::new()
+    .command(r#"mkdir subdir && cd subdir && echo 1 2 3 > test.txt"#)
+    .assert_file_equals("subdir/test.txt", "1 2 3\n")
+    .run()
+    .await;
+
+  // absolute path
+  TestBuilder::new()
+    .command(r#"echo 1 2 3 > "$PWD/test.txt""#)
+    .assert_file_equals("test.txt", "1 2 3\n")
+    .run()
+    .await;
+
+  // stdout
+  TestBuilder::new()
+    .command(r#"deno eval 'console.log(1); console.error(5)' 1> test.txt"#)
+    .assert_stderr("5\n")
+    .assert_file_equals("test.txt", "1\n")
+    .run()
+    .await;
+
+  // stderr
+  TestBuilder::new()
+    .command(r#"deno eval 'console.log(1); console.error(5)' 2> test.txt"#)
+    .assert_stdout("1\n")
+    .assert_file_equals("test.txt", "5\n")
+    .run()
+    .await;
+
+  // invalid fd
+  TestBuilder::new()
+    .command(r#"echo 2 3> test.txt"#)
+    .ensure_temp_dir()
+    .assert_stderr(
+      "only redirecting to stdout (1) and stderr (2) is supported\n",
+    )
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // /dev/null
+  TestBuilder::new()
+    .command(r#"deno eval 'console.log(1); console.error(5)' 2> /dev/null"#)
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  // appending
+  TestBuilder::new()
+    .command(r#"echo 1 > test.txt && echo 2 >> test.txt"#)
+    .assert_file_equals("test.txt", "1\n2\n")
+    .run()
+    .await;
+
+  // &> and &>> redirect
+  TestBuilder::new()
+    .command(
+      concat!(
+        "deno eval 'console.log(1); setTimeout(() => console.error(23), 10)' &> file.txt &&",
+        "deno eval 'console.log(456); setTimeout(() => console.error(789), 10)' &>> file.txt"
+      )
+    )
+    .assert_file_equals("file.txt", "1\n23\n456\n789\n")
+    .run()
+    .await;
+
+  // multiple arguments after re-direct
+  TestBuilder::new()
+    .command(r#"export TwoArgs=testing\ this && echo 1 > $TwoArgs"#)
+    .assert_stderr(concat!(
+      "redirect path must be 1 argument, but found 2 ",
+      "(testing this). Did you mean to quote it (ex. \"testing this\")?\n"
+    ))
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // zero arguments after re-direct
+  TestBuilder::new()
+    .command(r#"echo 1 > $EMPTY"#)
+    .assert_stderr("redirect path must be 1 argument, but found 0\n")
+    .assert_exit_code(1)
+    .run()
+    .await;
+
+  // input
+  TestBuilder::new()
+    .command(r#"cat < input.txt && cat 0<input.txt"#)
+    .file("input.txt", "1\n2\n")
+    .assert_stdout("1\n2\n1\n2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"cd sub && cat - < input.txt"#)
+    .directory("sub")
+    .file("sub/input.txt", "1\n")
+    .assert_stdout("1\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"xargs echo < args.txt"#)
+    .file("args.txt", "1 2\n3\n")
+    .assert_stdout("1 2 3\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)' < input.txt"#)
+    .file("input.txt", "1\n")
+    .assert_stdout("1\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command("exit 5")
-    .assert_exit_code(5)
+    .command(r#"cat < /dev/null && echo 1"#)
+    .stdin("2")
+    .assert_stdout("1\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command("exit 258 && echo 1")
-    .assert_exit_code(2)
+    .command(r#"cat < missing.txt"#)
+    .ensure_temp_dir()
+    .assert_stderr(&format!(
+      "error opening file for redirect ($TEMP_DIR{}missing.txt). {}\n",
+      FOLDER_SEPERATOR,
+      no_such_file_error_text()
+    ))
+    .assert_exit_code(1)
     .run()
     .await;
 
+  // duplicating file descriptors
   TestBuilder::new()
-    .command("(exit 0) && echo 1")
-    .assert_stdout("1\n")
+    .command(r#"echo 1 1>&2"#)
+    .assert_stderr("1\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command("(exit 1) && echo 1")
+    .command(r#"cat file.txt 2>&1"#)
+    .ensure_temp_dir()
+    .assert_stdout(&format!("cat: file.txt: {}\n", no_such_file_error_text()))
     .assert_exit_code(1)
     .run()
     .await;
 
   TestBuilder::new()
-    .command("echo 1 && (exit 1)")
-    .assert_stdout("1\n")
+    .command(r#"deno eval 'console.log(1); console.error(5)' 2>&1"#)
+    .assert_stdout("1\n5\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo 1 3>&1"#)
+    .assert_stderr(
+      "only redirecting to stdout (1) and stderr (2) is supported\n",
+    )
     .assert_exit_code(1)
     .run()
     .await;
 
   TestBuilder::new()
-    .command("exit ; echo 2")
+    .command(r#"echo 1 >&3"#)
+    .assert_stderr(
+      "only redirecting to stdout (1) and stderr (2) is supported\n",
+    )
     .assert_exit_code(1)
     .run()
     .await;
-}
 
-#[tokio::test]
-pub async fn async_commands() {
+  // closing file descriptors
   TestBuilder::new()
-    .command("sleep 0.1 && echo 2 & echo 1")
-    .assert_stdout("1\n2\n")
+    .command(r#"echo 1 >&- && cat file.txt 2>&-"#)
+    .ensure_temp_dir()
+    .assert_exit_code(1)
     .run()
     .await;
 
   TestBuilder::new()
-    .command("(sleep 0.1 && echo 2 &) ; echo 1")
-    .assert_stdout("1\n2\n")
+    .command(r#"cat <&- && echo 1"#)
+    .stdin("2")
+    .assert_stdout("1\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command("(sleep 0.1 && echo 2) & echo 1")
-    .assert_stdout("1\n2\n")
+    .command(r#"cat 1< input.txt"#)
+    .file("input.txt", "1\n")
+    .assert_stderr("only redirecting from a file to stdin (0) is supported\n")
+    .assert_exit_code(1)
     .run()
     .await;
 
+  // multiple redirects are applied left to right
   TestBuilder::new()
-    .command(
-      "$(sleep 0.1 && echo 1 & $(sleep 0.2 && echo 2 & echo echo) & echo echo)",
+    .command(r#"cat missing.txt 2> err.txt > out.txt; cat < out.txt"#)
+    .ensure_temp_dir()
+    .assert_file_equals(
+      "err.txt",
+      &format!("cat: missing.txt: {}\n", no_such_file_error_text()),
     )
-    .assert_stdout("1 2\n")
+    .assert_file_equals("out.txt", "")
     .run()
     .await;
 
   TestBuilder::new()
-    .command("exit 1 & exit 0")
+    .command(r#"cat missing.txt > file.txt 2>&1"#)
+    .ensure_temp_dir()
+    .assert_file_equals(
+      "file.txt",
+      &format!("cat: missing.txt: {}\n", no_such_file_error_text()),
+    )
     .assert_exit_code(1)
     .run()
     .await;
 
-  // should not output because the `exit 1` will cancel the sleep
   TestBuilder::new()
-    .command("sleep 5 && echo 1 & exit 1")
+    .command(r#"cat missing.txt 2>&1 > file.txt"#)
+    .ensure_temp_dir()
+    .assert_stdout(&format!(
+      "cat: missing.txt: {}\n",
+      no_such_file_error_text()
+    ))
+    .assert_file_equals("file.txt", "")
     .assert_exit_code(1)
     .run()
     .await;
 
-  // should fail when async command exits
   TestBuilder::new()
-    .command("exit 1 & exit 0")
-    .assert_exit_code(1)
+    .command(r#"cat < input.txt > output.txt"#)
+    .file("input.txt", "1\n")
+    .assert_file_equals("output.txt", "1\n")
     .run()
     .await;
+}
 
-  // should fail when async command fails and cancel any running command
+#[tokio::test]
+pub async fn comments() {
   TestBuilder::new()
-    .command("deno eval 'Deno.exit(1)' & sleep 5 && echo 2 & echo 1")
-    .assert_stdout("1\n")
-    .assert_exit_code(1)
+    .command("# build the project\necho 1 # first; echo 2\necho a#b '#c' && # next\n echo 3")
+    .assert_stdout("1\na#b #c\n3\n")
     .run()
     .await;
 
-  // should cancel running command
   TestBuilder::new()
-    .command("sleep 10 & sleep 0.5 && deno eval 'Deno.exit(2)' & deno eval 'console.log(1); setTimeout(() => { console.log(3) }, 10_000);'")
-    .assert_stdout("1\n")
-    .assert_exit_code(2)
+    .command("cat <<EOF # comment\n# not a comment\nEOF")
+    .assert_stdout("# not a comment\n")
     .run()
     .await;
+}
 
-  // should be able to opt out by doing an `|| exit 0`
+#[tokio::test]
+pub async fn multi_line_scripts() {
   TestBuilder::new()
-    .command("deno eval 'Deno.exit(1)' || exit 0 & echo 1")
-    .assert_stdout("1\n")
-    .assert_exit_code(0)
+    .command(
+      r#"
+NAME=world
+echo hello \
+  $NAME &&
+  echo "a \
+b"
+
+for i in 1 2
+do
+  echo $i |
+    cat
+done
+
+case $NAME in
+  world)
+    echo matched
+    ;;
+  *) echo other ;;
+esac
+"#,
+    )
+    .assert_stdout("hello world\na b\n1\n2\nmatched\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command("cat <<A\na\nA\ncat <<B\nb\nB\necho done")
+    .assert_stdout("a\nb\ndone\n")
     .run()
     .await;
 }
 
 #[tokio::test]
-pub async fn command_substition() {
+pub async fn here_docs() {
   TestBuilder::new()
-    .command("echo $(echo 1)")
-    .assert_stdout("1\n")
+    .command("cat <<EOF\nhello $NAME\n  $(echo 1) \\$NAME\nEOF\n")
+    .env_var("NAME", "world")
+    .assert_stdout("hello world\n  1 $NAME\n")
     .run()
     .await;
 
+  // the body isn't split into words
   TestBuilder::new()
-    .command("echo $(echo 1 && echo 2)")
-    .assert_stdout("1 2\n")
+    .command("V='a   b'\ncat <<EOF\n$V ${V} $(echo x; echo y)\nEOF\n")
+    .assert_stdout("a   b a   b x\ny\n")
     .run()
     .await;
 
-  // async inside subshell should wait
   TestBuilder::new()
-    .command("$(sleep 0.1 && echo 1 & echo echo) 2")
-    .assert_stdout("1 2\n")
+    .command(r#"echo "$(echo x; echo y)" $(echo x; echo y)"#)
+    .assert_stdout("x\ny x y\n")
     .run()
     .await;
+
+  // the bodies follow the line in order
   TestBuilder::new()
-    .command("$(sleep 0.1 && echo 1 && exit 5 &) ; echo 2")
-    .assert_stdout("2\n")
-    .assert_stderr("1: command not found\n")
+    .command("cat <<A; cat <<B && echo 3\n1\nA\n2\nB\necho 4")
+    .assert_stdout("1\n2\n3\n4\n")
     .run()
     .await;
-}
 
-#[tokio::test]
-pub async fn shell_variables() {
   TestBuilder::new()
-    .command(r#"echo $VAR && VAR=1 && echo $VAR && deno eval 'console.log(Deno.env.get("VAR"))'"#)
-    .assert_stdout("\n1\nundefined\n")
+    .command("cat <<'EOF'\nhello $NAME\nEOF")
+    .env_var("NAME", "world")
+    .assert_stdout("hello $NAME\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command(r#"VAR=1 && echo $VAR$VAR"#)
-    .assert_stdout("11\n")
+    .command("cat <<-EOF\n\t\thello\n\tEOF")
+    .assert_stdout("hello\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command(r#"VAR=1 && echo Test$VAR && echo $(echo "Test: $VAR") ; echo CommandSub$($VAR); echo $ ; echo \$VAR"#)
-    .assert_stdout("Test1\nTest: 1\nCommandSub\n$\n$VAR\n")
-    .assert_stderr("1: command not found\n")
+    .command("cat <<EOF > file.txt\n1\n2\nEOF")
+    .ensure_temp_dir()
+    .assert_file_equals("file.txt", "1\n2\n")
     .run()
     .await;
-}
 
-#[tokio::test]
-pub async fn env_variables() {
+  // larger than the pipe's buffer
+  let text = "a".repeat(100_000);
   TestBuilder::new()
-    .command(r#"echo $VAR && export VAR=1 && echo $VAR && deno eval 'console.log(Deno.env.get("VAR"))'"#)
-    .assert_stdout("\n1\n1\n")
+    .command(&format!("cat <<EOF > file.txt\n{}\nEOF", text))
+    .ensure_temp_dir()
+    .assert_file_equals("file.txt", &format!("{}\n", text))
     .run()
     .await;
 
+  // here-strings
   TestBuilder::new()
-    .command(r#"export VAR=1 VAR2=testing VAR3="test this out" && echo $VAR $VAR2 $VAR3"#)
-    .assert_stdout("1 testing test this out\n")
+    .command(r#"cat <<< "hello $NAME""#)
+    .env_var("NAME", "world")
+    .assert_stdout("hello world\n")
     .run()
     .await;
-}
 
-#[tokio::test]
-pub async fn sequential_lists() {
   TestBuilder::new()
-    .command(r#"echo 1 ; sleep 0.1 && echo 4 & echo 2 ; echo 3;"#)
-    .assert_stdout("1\n2\n3\n4\n")
+    .command(r#"cat <<< $EMPTY"#)
+    .assert_stdout("\n")
     .run()
     .await;
 }
 
 #[tokio::test]
-pub async fn pipeline() {
+pub async fn glob() {
   TestBuilder::new()
-    .command(r#"echo 1 | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1\n")
+    .directory("src/sub")
+    .file("b.ts", "")
+    .file("a.ts", "")
+    .file(".hidden.ts", "")
+    .file("c.js", "")
+    .file("src/d.json", "")
+    .file("src/sub/e.json", "")
+    .command("echo *.ts && echo ?.js && echo [ab].ts && echo src/**/*.json")
+    .assert_stdout("a.ts b.ts\nc.js\na.ts b.ts\nsrc/d.json src/sub/e.json\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command(r#"echo 1 | echo 2 && echo 3"#)
-    .assert_stdout("2\n3\n")
+    .directory("dist")
+    .file("dist/a.js", "")
+    .file("dist/b.js", "")
+    .file("dist/c.d.ts", "")
+    .command("rm dist/*.js")
+    .assert_not_exists("dist/a.js")
+    .assert_not_exists("dist/b.js")
+    .assert_exists("dist/c.d.ts")
     .run()
     .await;
 
   TestBuilder::new()
-    .command(r#"echo $(sleep 0.1 && echo 2 & echo 1) | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1 2\n")
+    .directory("dist")
+    .file("dist/a.js", "")
+    .command("DIR=dist && for f in $DIR/*.js; do echo $f; done")
+    .assert_stdout("dist/a.js\n")
     .run()
     .await;
 
+  // quoted and escaped glob characters are not expanded
   TestBuilder::new()
-    .command(r#"echo 2 | echo 1 | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1\n")
+    .file("a.ts", "")
+    .command(r#"echo "*.ts" '*.ts' \*.ts"#)
+    .assert_stdout("*.ts *.ts *.ts\n")
     .run()
     .await;
 
+  // no matches
   TestBuilder::new()
-    .command(r#"deno eval 'console.log(1); console.error(2);' | deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1\n")
-    .assert_stderr("2\n")
+    .command("echo *.ts && echo 1")
+    .ensure_temp_dir()
+    .assert_stderr("glob: no matches found '*.ts'\n")
+    .assert_exit_code(1)
     .run()
     .await;
+}
 
-  // stdout and stderr pipeline
-
+#[tokio::test]
+pub async fn tilde_expansion() {
   TestBuilder::new()
-    .command(r#"deno eval 'console.log(1); console.error(2);' |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1\n2\n")
+    .env_var("HOME", "/home/user")
+    .command(r#"echo ~ ~/projects "~" '~/a' a~ ~other \~ \~/b"#)
+    .assert_stdout("/home/user /home/user/projects ~ ~/a a~ ~other ~ ~/b\n")
     .run()
     .await;
 
   TestBuilder::new()
-    // add bit of a delay while outputting stdout so that it doesn't race with stderr
-    .command(r#"deno eval 'console.log(1); console.error(2);' | deno eval --unstable 'setTimeout(async () => { await Deno.stdin.readable.pipeTo(Deno.stderr.writable) }, 10)' |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stderr.writable)'"#)
-    // still outputs 2 because the first command didn't pipe stderr
-    .assert_stderr("2\n1\n")
+    .env_var("HOME", "/home/user")
+    .command(r#"DIR=~/projects && echo $DIR"#)
+    .assert_stdout("/home/user/projects\n")
     .run()
     .await;
 
-  // |& pipeline should still pipe stdout
   TestBuilder::new()
-    .command(r#"echo 1 |& deno eval 'await Deno.stdin.readable.pipeTo(Deno.stdout.writable)'"#)
-    .assert_stdout("1\n")
+    .directory("sub")
+    .command(r#"export HOME=$PWD && cd ~/sub && pwd"#)
+    .assert_stdout(&format!("$TEMP_DIR{}sub\n", FOLDER_SEPERATOR))
     .run()
     .await;
 }
 
 #[tokio::test]
-pub async fn negated() {
+pub async fn parameter_expansion() {
   TestBuilder::new()
-    .command(r#"! echo 1 && echo 2"#)
-    .assert_stdout("1\n")
-    .assert_exit_code(1)
+    .env_var("VAR", "value")
+    .env_var("EMPTY", "")
+    .command(r#"echo ${VAR} ${UNSET} ${#VAR} ${#UNSET} "${VAR}s""#)
+    .assert_stdout("value 5 0 values\n")
     .run()
     .await;
+
   TestBuilder::new()
-    .command(r#"! echo 1 || echo 2"#)
-    .assert_stdout("1\n2\n")
+    .env_var("VAR", "value")
+    .command(
+      r#"EMPTY="" && echo ${VAR:-a} ${UNSET:-b} ${EMPTY:-c} ${EMPTY-d} "${UNSET:-e f}""#,
+    )
+    .assert_stdout("value b c e f\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .env_var("VAR", "value")
+    .command(r#"EMPTY="" && echo ${VAR:+a} ${UNSET:+b} ${EMPTY:+c} ${EMPTY+d}"#)
+    .assert_stdout("a d\n")
     .run()
     .await;
+
   TestBuilder::new()
-    .command(r#"! (echo 1 | echo 2 && echo 3) || echo 4"#)
-    .assert_stdout("2\n3\n4\n")
+    .command(r#"echo ${PORT:=8000} && echo $PORT && echo ${PORT:=9000}"#)
+    .assert_stdout("8000\n8000\n8000\n")
     .run()
     .await;
+
   TestBuilder::new()
-    .command(r#"! echo 1 | echo 2 && echo 3"#)
-    .assert_stdout("2\n")
+    .command(r#"echo ${1:=value}"#)
+    .assert_stderr("1: cannot assign in this way\n")
     .assert_exit_code(1)
     .run()
     .await;
+
   TestBuilder::new()
-    .command(r#"! (exit 5) && echo 1"#)
-    .assert_stdout("1\n")
+    .env_var("VAR", "value")
+    .command(r#"echo ${VAR:?} && echo ${UNSET:?must be set} && echo 1"#)
+    .assert_stdout("value\n")
+    .assert_stderr("UNSET: must be set\n")
+    .assert_exit_code(1)
     .run()
     .await;
+
   TestBuilder::new()
-    .command(r#"! exit 5 && echo 1"#)
-    .assert_exit_code(5)
+    .command(r#"EMPTY="" && echo ${EMPTY?} && echo ${EMPTY:?}"#)
+    .assert_stdout("\n")
+    .assert_stderr("EMPTY: parameter null or not set\n")
+    .assert_exit_code(1)
     .run()
     .await;
+
+  // the script exits instead of continuing to the next command
   TestBuilder::new()
-    .command(r#"! echo 1 && echo 2 &"#)
-    .assert_stdout("1\n")
-    // differing behaviour to shells, where this async command will actually fail
+    .command(r#"echo ${X:?boom}; echo after"#)
+    .assert_stderr("X: boom\n")
     .assert_exit_code(1)
     .run()
     .await;
 
-  // test no spaces
   TestBuilder::new()
-    .command(r#"!echo 1 && echo 2"#)
-    .assert_stderr("History expansion is not supported:\n  !echo\n  ~\n\nPerhaps you meant to add a space after the exclamation point to negate the command?\n  ! echo\n")
+    .command(r#"echo ${X?} || echo after"#)
+    .assert_stderr("X: parameter not set\n")
     .assert_exit_code(1)
     .run()
     .await;
-}
 
-#[tokio::test]
-pub async fn redirects() {
+  // only the subshell exits
   TestBuilder::new()
-    .command(r#"echo 5 6 7 > test.txt"#)
-    .assert_file_equals("test.txt", "5 6 7\n")
+    .command(r#"(echo ${X:?}); echo after"#)
+    .assert_stdout("after\n")
+    .assert_stderr("X: parameter null or not set\n")
     .run()
     .await;
 
   TestBuilder::new()
-    .command(r#"echo 1 2 3 && echo 1 > test.txt"#)
-    .assert_stdout("1 2 3\n")
-    .assert_file_equals("test.txt", "1\n")
+    .env_var("FILE", "src/dir/mod.test.ts")
+    .command(r#"echo ${FILE#*/} ${FILE##*/} ${FILE%.*} ${FILE%%.*} ${FILE#x}"#)
+    .assert_stdout(
+      "dir/mod.test.ts mod.test.ts src/dir/mod.test src/dir/mod src/dir/mod.test.ts\n",
+    )
     .run()
     .await;
 
-  // subdir
   TestBuilder::new()
-    .command(r#"mkdir subdir && cd subdir && echo 1 2 3 > test.txt"#)
-    .assert_file_equals("subdir/test.txt", "1 2 3\n")
+    .env_var("FILE", "a*b")
+    .command(r#"echo ${FILE#"a*"} ${FILE%[b]}"#)
+    .assert_stdout("b a*\n")
     .run()
     .await;
+}
 
-  // absolute path
+#[tokio::test]
+pub async fn ansi_c_quoting_and_echo() {
   TestBuilder::new()
-    .command(r#"echo 1 2 3 > "$PWD/test.txt""#)
-    .assert_file_equals("test.txt", "1 2 3\n")
+    .command(r#"echo $'a\tb' "$'c'" && echo -n 1 && echo -n 2"#)
+    .assert_stdout("a\tb $'c'\n12")
     .run()
     .await;
 
-  // stdout
   TestBuilder::new()
-    .command(r#"deno eval 'console.log(1); console.error(5)' 1> test.txt"#)
-    .assert_stderr("5\n")
-    .assert_file_equals("test.txt", "1\n")
+    .command(r#"echo -e 'a\tb\x41' && echo -E 'a\tb' && echo 'a\tb' -n"#)
+    .assert_stdout("a\tbA\na\\tb\na\\tb -n\n")
     .run()
     .await;
 
-  // stderr
   TestBuilder::new()
-    .command(r#"deno eval 'console.log(1); console.error(5)' 2> test.txt"#)
-    .assert_stdout("1\n")
-    .assert_file_equals("test.txt", "5\n")
+    .command(r#"echo -e "a\cb" && echo c"#)
+    .assert_stdout("ac\n")
     .run()
     .await;
+}
 
-  // invalid fd
+#[tokio::test]
+pub async fn backticks() {
   TestBuilder::new()
-    .command(r#"echo 2 3> test.txt"#)
-    .ensure_temp_dir()
-    .assert_stderr(
-      "only redirecting to stdout (1) and stderr (2) is supported\n",
-    )
-    .assert_exit_code(1)
+    .command(r#"echo `echo 1` "`echo 2 3`" a`echo b`c"#)
+    .assert_stdout("1 2 3 abc\n")
     .run()
     .await;
 
-  // /dev/null
   TestBuilder::new()
-    .command(r#"deno eval 'console.log(1); console.error(5)' 2> /dev/null"#)
-    .assert_stdout("1\n")
+    .env_var("VAR", "value")
+    .command(r#"echo `echo \$VAR \`echo nested\``"#)
+    .assert_stdout("value nested\n")
     .run()
     .await;
+}
 
-  // appending
+#[tokio::test]
+pub async fn arithmetic_expansion() {
   TestBuilder::new()
-    .command(r#"echo 1 > test.txt && echo 2 >> test.txt"#)
-    .assert_file_equals("test.txt", "1\n2\n")
+    .command(r#"echo $((1 + 2 * 3)) $(((1 + 2) * 3)) $((7 / 2)) $((-7 % 3))"#)
+    .assert_stdout("7 9 3 -1\n")
     .run()
     .await;
 
-  // &> and &>> redirect
   TestBuilder::new()
     .command(
-      concat!(
-        "deno eval 'console.log(1); setTimeout(() => console.error(23), 10)' &> file.txt &&",
-        "deno eval 'console.log(456); setTimeout(() => console.error(789), 10)' &>> file.txt"
-      )
+      r#"echo $((1 < 2)) $((2 <= 1)) $((1 == 1 && 2 != 2)) $((0 || 3)) $((!0))"#,
     )
-    .assert_file_equals("file.txt", "1\n23\n456\n789\n")
+    .assert_stdout("1 0 0 1 1\n")
     .run()
     .await;
 
-  // multiple arguments after re-direct
   TestBuilder::new()
-    .command(r#"export TwoArgs=testing\ this && echo 1 > $TwoArgs"#)
-    .assert_stderr(concat!(
-      "redirect path must be 1 argument, but found 2 ",
-      "(testing this). Did you mean to quote it (ex. \"testing this\")?\n"
-    ))
+    .env_var("COUNT", "4")
+    .env_var("EMPTY", "")
+    .command(
+      r#"NEXT=$((COUNT + 1)) && echo $NEXT $(($NEXT * 2)) "$((UNSET + EMPTY))""#,
+    )
+    .assert_stdout("5 10 0\n")
+    .run()
+    .await;
+
+  // a subshell within a command substitution
+  TestBuilder::new()
+    .command(r#"echo $((echo 1) && echo 2)"#)
+    .assert_stdout("1 2\n")
+    .run()
+    .await;
+
+  TestBuilder::new()
+    .command(r#"echo $((1 / 0)) && echo 1"#)
+    .assert_stderr("arithmetic: division by zero\n")
     .assert_exit_code(1)
     .run()
     .await;
 
-  // zero arguments after re-direct
   TestBuilder::new()
-    .command(r#"echo 1 > $EMPTY"#)
-    .assert_stderr("redirect path must be 1 argument, but found 0\n")
+    .env_var("VAR", "text")
+    .command(r#"echo $((VAR + 1))"#)
+    .assert_stderr("arithmetic: VAR: expected integer, but found 'text'\n")
     .assert_exit_code(1)
     .run()
     .await;
+
+  TestBuilder::new()
+    .command(r#"echo $((0 && 1 / 0)) $((1 || 1 / 0))"#)
+    .assert_stdout("0 1\n")
+    .run()
+    .await;
 }
 
 #[tokio::test]
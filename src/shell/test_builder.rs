@@ -43,6 +43,7 @@ pub struct TestBuilder {
   // it is much much faster to lazily create this
   temp_dir: Option<TempDir>,
   env_vars: HashMap<String, String>,
+  args: Vec<String>,
   command: String,
   stdin: Vec<u8>,
   expected_exit_code: i32,
@@ -71,6 +72,7 @@ impl TestBuilder {
     Self {
       temp_dir: None,
       env_vars,
+      args: Vec::new(),
       command: String::new(),
       stdin: Vec::new(),
       expected_exit_code: 0,
@@ -113,6 +115,11 @@ impl TestBuilder {
     self
   }
 
+  pub fn args(&mut self, args: &[&str]) -> &mut Self {
+    self.args = args.iter().map(|arg| arg.to_string()).collect();
+    self
+  }
+
   pub fn file(&mut self, path: &str, text: &str) -> &mut Self {
     let temp_dir = self.get_temp_dir();
     fs::write(temp_dir.cwd.join(path), text).unwrap();
@@ -180,6 +187,7 @@ impl TestBuilder {
       list,
       self.env_vars.clone(),
       &cwd,
+      self.args.clone(),
       stdin,
       stdout,
       stderr,
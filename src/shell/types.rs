@@ -6,12 +6,14 @@ use std::io::Read;
 use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::Result;
 use futures::future::BoxFuture;
 use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 
+use crate::parser::SequentialList;
 use crate::shell::fs_util;
 
 #[derive(Clone)]
@@ -22,9 +24,17 @@ pub struct ShellState {
   /// Variables that should be evaluated within the shell and
   /// not passed down to any sub commands.
   shell_vars: HashMap<String, String>,
+  /// Functions defined within the shell.
+  functions: HashMap<String, Arc<SequentialList>>,
+  /// Positional parameters (ex. `$1`) of the executing script or function.
+  args: Vec<String>,
+  /// Exit code of the last executed command (`$?`).
+  last_exit_code: i32,
   cwd: PathBuf,
   /// Token to cancel execution.
   token: CancellationToken,
+  /// Number of loops the current command is executing within.
+  loop_depth: u32,
 }
 
 impl ShellState {
@@ -32,8 +42,12 @@ impl ShellState {
     let mut result = Self {
       env_vars: Default::default(),
       shell_vars: Default::default(),
+      functions: Default::default(),
+      args: Vec::new(),
+      last_exit_code: 0,
       cwd: PathBuf::new(),
       token: CancellationToken::default(),
+      loop_depth: 0,
     };
     // ensure the data is normalized
     for (name, value) in env_vars {
@@ -51,7 +65,23 @@ impl ShellState {
     &self.env_vars
   }
 
-  pub fn get_var(&self, name: &str) -> Option<&String> {
+  pub fn get_var(&self, name: &str) -> Option<Cow<str>> {
+    match name {
+      "#" => return Some(Cow::Owned(self.args.len().to_string())),
+      "@" | "*" => return Some(Cow::Owned(self.args.join(" "))),
+      "?" => return Some(Cow::Owned(self.last_exit_code.to_string())),
+      "$" => return Some(Cow::Owned(std::process::id().to_string())),
+      _ => {}
+    }
+    if let Ok(index) = name.parse::<usize>() {
+      if index > 0 {
+        return self
+          .args
+          .get(index - 1)
+          .map(|arg| Cow::Borrowed(arg.as_str()));
+      }
+    }
+
     let name = if cfg!(windows) {
       Cow::Owned(name.to_uppercase())
     } else {
@@ -61,6 +91,45 @@ impl ShellState {
       .env_vars
       .get(name.as_ref())
       .or_else(|| self.shell_vars.get(name.as_ref()))
+      .map(|value| Cow::Borrowed(value.as_str()))
+  }
+
+  /// Gets the home directory from `HOME` (or `USERPROFILE` on Windows),
+  /// falling back to the home directory of the current process and
+  /// then to `~` when it can't be resolved.
+  pub fn home_dir(&self) -> Cow<str> {
+    if let Some(home_dir) = self.get_var("HOME") {
+      return home_dir;
+    }
+    if cfg!(windows) {
+      if let Some(home_dir) = self.get_var("USERPROFILE") {
+        return home_dir;
+      }
+    }
+    #[allow(deprecated)]
+    let process_home_dir = std::env::home_dir();
+    match process_home_dir {
+      Some(home_dir) => Cow::Owned(home_dir.display().to_string()),
+      None => Cow::Borrowed("~"),
+    }
+  }
+
+  /// Gets the positional parameters (ex. `$1`) of
+  /// the executing script or function.
+  pub fn args(&self) -> &[String] {
+    &self.args
+  }
+
+  pub fn get_function(&self, name: &str) -> Option<&Arc<SequentialList>> {
+    self.functions.get(name)
+  }
+
+  pub fn set_args(&mut self, args: Vec<String>) {
+    self.args = args;
+  }
+
+  pub fn set_last_exit_code(&mut self, exit_code: i32) {
+    self.last_exit_code = exit_code;
   }
 
   pub fn set_cwd(&mut self, cwd: &Path) {
@@ -90,6 +159,9 @@ impl ShellState {
       EnvChange::Cd(new_dir) => {
         self.cwd = new_dir.clone();
       }
+      EnvChange::SetFunction(name, body) => {
+        self.functions.insert(name.to_string(), body.clone());
+      }
     }
   }
 
@@ -127,6 +199,14 @@ impl ShellState {
     state.token = self.token.child_token();
     state
   }
+
+  pub fn loop_depth(&self) -> u32 {
+    self.loop_depth
+  }
+
+  pub fn increment_loop_depth(&mut self) {
+    self.loop_depth += 1;
+  }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -136,6 +216,8 @@ pub enum EnvChange {
   // `ENV_VAR=VALUE`
   SetShellVar(String, String),
   Cd(PathBuf),
+  // `name() { list; }`
+  SetFunction(String, Arc<SequentialList>),
 }
 
 pub type FutureExecuteResult = BoxFuture<'static, ExecuteResult>;
@@ -144,10 +226,20 @@ pub type FutureExecuteResult = BoxFuture<'static, ExecuteResult>;
 // SIGINT (2) + 128
 pub const CANCELLATION_EXIT_CODE: i32 = 130;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopControl {
+  /// `break n`
+  Break(u32),
+  /// `continue n`
+  Continue(u32),
+}
+
 #[derive(Debug)]
 pub enum ExecuteResult {
   Exit(i32, Vec<JoinHandle<i32>>),
   Continue(i32, Vec<EnvChange>, Vec<JoinHandle<i32>>),
+  /// Signal to an enclosing loop to break or continue.
+  LoopControl(LoopControl, Vec<EnvChange>, Vec<JoinHandle<i32>>),
 }
 
 impl ExecuteResult {
@@ -163,20 +255,42 @@ impl ExecuteResult {
     match self {
       ExecuteResult::Exit(code, handles) => (code, handles),
       ExecuteResult::Continue(code, _, handles) => (code, handles),
+      ExecuteResult::LoopControl(_, _, handles) => (0, handles),
     }
   }
 
   pub fn into_handles(self) -> Vec<JoinHandle<i32>> {
     self.into_exit_code_and_handles().1
   }
+
+  /// Adds changes that occurred before the execution of this result.
+  pub fn with_prior_changes(self, mut changes: Vec<EnvChange>) -> Self {
+    match self {
+      ExecuteResult::Exit(_, _) => self,
+      ExecuteResult::Continue(code, result_changes, handles) => {
+        changes.extend(result_changes);
+        ExecuteResult::Continue(code, changes, handles)
+      }
+      ExecuteResult::LoopControl(control, result_changes, handles) => {
+        changes.extend(result_changes);
+        ExecuteResult::LoopControl(control, changes, handles)
+      }
+    }
+  }
 }
 
 /// Reader side of a pipe.
-pub struct ShellPipeReader(os_pipe::PipeReader);
+pub enum ShellPipeReader {
+  OsPipe(os_pipe::PipeReader),
+  StdFile(std::fs::File),
+}
 
 impl Clone for ShellPipeReader {
   fn clone(&self) -> Self {
-    Self(self.0.try_clone().unwrap())
+    match self {
+      Self::OsPipe(pipe) => Self::OsPipe(pipe.try_clone().unwrap()),
+      Self::StdFile(file) => Self::StdFile(file.try_clone().unwrap()),
+    }
   }
 }
 
@@ -186,18 +300,39 @@ impl ShellPipeReader {
   }
 
   pub fn from_raw(reader: os_pipe::PipeReader) -> Self {
-    Self(reader)
+    Self::OsPipe(reader)
+  }
+
+  pub fn from_std(std_file: std::fs::File) -> Self {
+    Self::StdFile(std_file)
+  }
+
+  /// Creates a reader that provides the specified text.
+  pub fn from_text(text: String) -> Self {
+    let (reader, mut writer) = pipe();
+    // write on another thread so that text larger than the
+    // pipe's buffer doesn't block until it's read
+    std::thread::spawn(move || {
+      let _ = writer.write(text.as_bytes());
+    });
+    reader
   }
 
   pub fn into_stdio(self) -> std::process::Stdio {
-    self.0.into()
+    match self {
+      Self::OsPipe(pipe) => pipe.into(),
+      Self::StdFile(file) => file.into(),
+    }
   }
 
   /// Pipe everything to the specified writer
   pub fn pipe_to(mut self, writer: &mut dyn Write) -> Result<()> {
     loop {
       let mut buffer = [0; 512]; // todo: what is an appropriate buffer size?
-      let size = self.0.read(&mut buffer)?;
+      let size = match &mut self {
+        Self::OsPipe(pipe) => pipe.read(&mut buffer)?,
+        Self::StdFile(file) => file.read(&mut buffer)?,
+      };
       if size == 0 {
         break;
       }
@@ -283,5 +418,8 @@ impl ShellPipeWriter {
 /// Used to communicate between commands.
 pub fn pipe() -> (ShellPipeReader, ShellPipeWriter) {
   let (reader, writer) = os_pipe::pipe().unwrap();
-  (ShellPipeReader(reader), ShellPipeWriter::OsPipe(writer))
+  (
+    ShellPipeReader::OsPipe(reader),
+    ShellPipeWriter::OsPipe(writer),
+  )
 }
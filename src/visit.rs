@@ -0,0 +1,917 @@
+// Copyright 2018-2022 the Deno authors. All rights reserved. MIT license.
+
+use crate::parser::*;
+
+/// Visits the nodes of the parser AST.
+///
+/// Each method walks into the children of its node by default, so an
+/// implementation only needs to override the methods for the nodes it's
+/// interested in. An overridden method can call the matching `walk_`
+/// function to continue into the node's children.
+pub trait Visit {
+  fn visit_sequential_list(&mut self, list: &SequentialList) {
+    walk_sequential_list(self, list);
+  }
+
+  fn visit_sequential_list_item(&mut self, item: &SequentialListItem) {
+    walk_sequential_list_item(self, item);
+  }
+
+  fn visit_sequence(&mut self, sequence: &Sequence) {
+    walk_sequence(self, sequence);
+  }
+
+  fn visit_function_definition(&mut self, definition: &FunctionDefinition) {
+    walk_function_definition(self, definition);
+  }
+
+  fn visit_pipeline(&mut self, pipeline: &Pipeline) {
+    walk_pipeline(self, pipeline);
+  }
+
+  fn visit_pipeline_inner(&mut self, inner: &PipelineInner) {
+    walk_pipeline_inner(self, inner);
+  }
+
+  fn visit_boolean_list(&mut self, list: &BooleanList) {
+    walk_boolean_list(self, list);
+  }
+
+  fn visit_pipe_sequence(&mut self, sequence: &PipeSequence) {
+    walk_pipe_sequence(self, sequence);
+  }
+
+  fn visit_command(&mut self, command: &Command) {
+    walk_command(self, command);
+  }
+
+  fn visit_command_inner(&mut self, inner: &CommandInner) {
+    walk_command_inner(self, inner);
+  }
+
+  fn visit_if_clause(&mut self, clause: &IfClause) {
+    walk_if_clause(self, clause);
+  }
+
+  fn visit_else_part(&mut self, else_part: &ElsePart) {
+    walk_else_part(self, else_part);
+  }
+
+  fn visit_for_clause(&mut self, clause: &ForClause) {
+    walk_for_clause(self, clause);
+  }
+
+  fn visit_while_clause(&mut self, clause: &WhileClause) {
+    walk_while_clause(self, clause);
+  }
+
+  fn visit_case_clause(&mut self, clause: &CaseClause) {
+    walk_case_clause(self, clause);
+  }
+
+  fn visit_case_item(&mut self, item: &CaseItem) {
+    walk_case_item(self, item);
+  }
+
+  fn visit_simple_command(&mut self, command: &SimpleCommand) {
+    walk_simple_command(self, command);
+  }
+
+  fn visit_env_var(&mut self, env_var: &EnvVar) {
+    walk_env_var(self, env_var);
+  }
+
+  fn visit_string_or_word(&mut self, value: &StringOrWord) {
+    walk_string_or_word(self, value);
+  }
+
+  fn visit_string_part(&mut self, part: &StringPart) {
+    walk_string_part(self, part);
+  }
+
+  fn visit_parameter_expansion(&mut self, expansion: &ParameterExpansion) {
+    walk_parameter_expansion(self, expansion);
+  }
+
+  fn visit_parameter_expansion_op(&mut self, op: &ParameterExpansionOp) {
+    walk_parameter_expansion_op(self, op);
+  }
+
+  fn visit_arithmetic_expr(&mut self, expr: &ArithmeticExpr) {
+    walk_arithmetic_expr(self, expr);
+  }
+
+  fn visit_redirect(&mut self, redirect: &Redirect) {
+    walk_redirect(self, redirect);
+  }
+
+  fn visit_io_file(&mut self, io_file: &IoFile) {
+    walk_io_file(self, io_file);
+  }
+
+  fn visit_here_doc(&mut self, here_doc: &HereDoc) {
+    walk_here_doc(self, here_doc);
+  }
+
+  /// Called with the span of each node before visiting its children.
+  fn visit_span(&mut self, _span: &Span) {}
+}
+
+pub fn walk_sequential_list<V: Visit + ?Sized>(
+  visitor: &mut V,
+  list: &SequentialList,
+) {
+  visitor.visit_span(&list.span);
+  for item in &list.items {
+    visitor.visit_sequential_list_item(item);
+  }
+}
+
+pub fn walk_sequential_list_item<V: Visit + ?Sized>(
+  visitor: &mut V,
+  item: &SequentialListItem,
+) {
+  visitor.visit_span(&item.span);
+  visitor.visit_sequence(&item.sequence);
+}
+
+pub fn walk_sequence<V: Visit + ?Sized>(visitor: &mut V, sequence: &Sequence) {
+  match sequence {
+    Sequence::ShellVar(env_var) => visitor.visit_env_var(env_var),
+    Sequence::Pipeline(pipeline) => visitor.visit_pipeline(pipeline),
+    Sequence::BooleanList(list) => visitor.visit_boolean_list(list),
+    Sequence::FunctionDefinition(definition) => {
+      visitor.visit_function_definition(definition)
+    }
+  }
+}
+
+pub fn walk_function_definition<V: Visit + ?Sized>(
+  visitor: &mut V,
+  definition: &FunctionDefinition,
+) {
+  visitor.visit_span(&definition.span);
+  visitor.visit_sequential_list(&definition.body);
+}
+
+pub fn walk_pipeline<V: Visit + ?Sized>(visitor: &mut V, pipeline: &Pipeline) {
+  visitor.visit_span(&pipeline.span);
+  visitor.visit_pipeline_inner(&pipeline.inner);
+}
+
+pub fn walk_pipeline_inner<V: Visit + ?Sized>(
+  visitor: &mut V,
+  inner: &PipelineInner,
+) {
+  match inner {
+    PipelineInner::Command(command) => visitor.visit_command(command),
+    PipelineInner::PipeSequence(sequence) => {
+      visitor.visit_pipe_sequence(sequence)
+    }
+  }
+}
+
+pub fn walk_boolean_list<V: Visit + ?Sized>(
+  visitor: &mut V,
+  list: &BooleanList,
+) {
+  visitor.visit_span(&list.span);
+  visitor.visit_sequence(&list.current);
+  visitor.visit_sequence(&list.next);
+}
+
+pub fn walk_pipe_sequence<V: Visit + ?Sized>(
+  visitor: &mut V,
+  sequence: &PipeSequence,
+) {
+  visitor.visit_span(&sequence.span);
+  visitor.visit_command(&sequence.current);
+  visitor.visit_pipeline_inner(&sequence.next);
+}
+
+pub fn walk_command<V: Visit + ?Sized>(visitor: &mut V, command: &Command) {
+  visitor.visit_span(&command.span);
+  visitor.visit_command_inner(&command.inner);
+  for redirect in &command.redirects {
+    visitor.visit_redirect(redirect);
+  }
+}
+
+pub fn walk_command_inner<V: Visit + ?Sized>(
+  visitor: &mut V,
+  inner: &CommandInner,
+) {
+  match inner {
+    CommandInner::Simple(command) => visitor.visit_simple_command(command),
+    CommandInner::Subshell(list) | CommandInner::Group(list) => {
+      visitor.visit_sequential_list(list)
+    }
+    CommandInner::If(clause) => visitor.visit_if_clause(clause),
+    CommandInner::For(clause) => visitor.visit_for_clause(clause),
+    CommandInner::While(clause) => visitor.visit_while_clause(clause),
+    CommandInner::Case(clause) => visitor.visit_case_clause(clause),
+  }
+}
+
+pub fn walk_if_clause<V: Visit + ?Sized>(visitor: &mut V, clause: &IfClause) {
+  visitor.visit_span(&clause.span);
+  visitor.visit_sequential_list(&clause.condition);
+  visitor.visit_sequential_list(&clause.body);
+  if let Some(else_part) = &clause.else_part {
+    visitor.visit_else_part(else_part);
+  }
+}
+
+pub fn walk_else_part<V: Visit + ?Sized>(
+  visitor: &mut V,
+  else_part: &ElsePart,
+) {
+  match else_part {
+    ElsePart::Elif(clause) => visitor.visit_if_clause(clause),
+    ElsePart::Else(list) => visitor.visit_sequential_list(list),
+  }
+}
+
+pub fn walk_for_clause<V: Visit + ?Sized>(visitor: &mut V, clause: &ForClause) {
+  visitor.visit_span(&clause.span);
+  for word in &clause.wordlist {
+    visitor.visit_string_or_word(word);
+  }
+  visitor.visit_sequential_list(&clause.body);
+}
+
+pub fn walk_while_clause<V: Visit + ?Sized>(
+  visitor: &mut V,
+  clause: &WhileClause,
+) {
+  visitor.visit_span(&clause.span);
+  visitor.visit_sequential_list(&clause.condition);
+  visitor.visit_sequential_list(&clause.body);
+}
+
+pub fn walk_case_clause<V: Visit + ?Sized>(
+  visitor: &mut V,
+  clause: &CaseClause,
+) {
+  visitor.visit_span(&clause.span);
+  visitor.visit_string_or_word(&clause.word);
+  for item in &clause.items {
+    visitor.visit_case_item(item);
+  }
+}
+
+pub fn walk_case_item<V: Visit + ?Sized>(visitor: &mut V, item: &CaseItem) {
+  visitor.visit_span(&item.span);
+  for pattern in &item.patterns {
+    visitor.visit_string_or_word(pattern);
+  }
+  visitor.visit_sequential_list(&item.body);
+}
+
+pub fn walk_simple_command<V: Visit + ?Sized>(
+  visitor: &mut V,
+  command: &SimpleCommand,
+) {
+  visitor.visit_span(&command.span);
+  for env_var in &command.env_vars {
+    visitor.visit_env_var(env_var);
+  }
+  for arg in &command.args {
+    visitor.visit_string_or_word(arg);
+  }
+}
+
+pub fn walk_env_var<V: Visit + ?Sized>(visitor: &mut V, env_var: &EnvVar) {
+  visitor.visit_span(&env_var.span);
+  visitor.visit_string_or_word(&env_var.value);
+}
+
+pub fn walk_string_or_word<V: Visit + ?Sized>(
+  visitor: &mut V,
+  value: &StringOrWord,
+) {
+  match value {
+    StringOrWord::Word { parts, span }
+    | StringOrWord::String { parts, span } => {
+      visitor.visit_span(span);
+      for part in parts {
+        visitor.visit_string_part(part);
+      }
+    }
+  }
+}
+
+pub fn walk_string_part<V: Visit + ?Sized>(visitor: &mut V, part: &StringPart) {
+  match part {
+    StringPart::Text { span, .. }
+    | StringPart::Variable { span, .. }
+    | StringPart::Glob { span, .. }
+    | StringPart::Tilde { span } => visitor.visit_span(span),
+    StringPart::Command { list, span } => {
+      visitor.visit_span(span);
+      visitor.visit_sequential_list(list);
+    }
+    StringPart::ParameterExpansion(expansion) => {
+      visitor.visit_parameter_expansion(expansion)
+    }
+    StringPart::Arithmetic { expr, span } => {
+      visitor.visit_span(span);
+      visitor.visit_arithmetic_expr(expr);
+    }
+  }
+}
+
+pub fn walk_parameter_expansion<V: Visit + ?Sized>(
+  visitor: &mut V,
+  expansion: &ParameterExpansion,
+) {
+  visitor.visit_span(&expansion.span);
+  if let Some(op) = &expansion.op {
+    visitor.visit_parameter_expansion_op(op);
+  }
+}
+
+pub fn walk_parameter_expansion_op<V: Visit + ?Sized>(
+  visitor: &mut V,
+  op: &ParameterExpansionOp,
+) {
+  match op {
+    ParameterExpansionOp::Length => {}
+    ParameterExpansionOp::UseDefault { word, .. }
+    | ParameterExpansionOp::AssignDefault { word, .. }
+    | ParameterExpansionOp::UseAlternative { word, .. }
+    | ParameterExpansionOp::ErrorIfUnset { word, .. }
+    | ParameterExpansionOp::RemovePrefix { pattern: word, .. }
+    | ParameterExpansionOp::RemoveSuffix { pattern: word, .. } => {
+      for part in word {
+        visitor.visit_string_part(part);
+      }
+    }
+  }
+}
+
+pub fn walk_arithmetic_expr<V: Visit + ?Sized>(
+  visitor: &mut V,
+  expr: &ArithmeticExpr,
+) {
+  match expr {
+    ArithmeticExpr::Number { span, .. }
+    | ArithmeticExpr::Variable { span, .. } => visitor.visit_span(span),
+    ArithmeticExpr::Parentheses { expr, span } => {
+      visitor.visit_span(span);
+      visitor.visit_arithmetic_expr(expr);
+    }
+    ArithmeticExpr::Unary { operand, span, .. } => {
+      visitor.visit_span(span);
+      visitor.visit_arithmetic_expr(operand);
+    }
+    ArithmeticExpr::Binary {
+      left, right, span, ..
+    } => {
+      visitor.visit_span(span);
+      visitor.visit_arithmetic_expr(left);
+      visitor.visit_arithmetic_expr(right);
+    }
+  }
+}
+
+pub fn walk_redirect<V: Visit + ?Sized>(visitor: &mut V, redirect: &Redirect) {
+  visitor.visit_span(&redirect.span);
+  visitor.visit_io_file(&redirect.io_file);
+}
+
+pub fn walk_io_file<V: Visit + ?Sized>(visitor: &mut V, io_file: &IoFile) {
+  match io_file {
+    IoFile::Word(word) => visitor.visit_string_or_word(word),
+    IoFile::HereDoc(here_doc) => visitor.visit_here_doc(here_doc),
+    IoFile::Fd(_) | IoFile::Close => {}
+  }
+}
+
+pub fn walk_here_doc<V: Visit + ?Sized>(visitor: &mut V, here_doc: &HereDoc) {
+  visitor.visit_span(&here_doc.span);
+  for part in here_doc.body.iter().flatten() {
+    visitor.visit_string_part(part);
+  }
+}
+
+/// Visits the nodes of the parser AST mutably (ex. to rewrite words).
+///
+/// See `Visit` for how to override the methods.
+pub trait VisitMut {
+  fn visit_sequential_list_mut(&mut self, list: &mut SequentialList) {
+    walk_sequential_list_mut(self, list);
+  }
+
+  fn visit_sequential_list_item_mut(&mut self, item: &mut SequentialListItem) {
+    walk_sequential_list_item_mut(self, item);
+  }
+
+  fn visit_sequence_mut(&mut self, sequence: &mut Sequence) {
+    walk_sequence_mut(self, sequence);
+  }
+
+  fn visit_function_definition_mut(
+    &mut self,
+    definition: &mut FunctionDefinition,
+  ) {
+    walk_function_definition_mut(self, definition);
+  }
+
+  fn visit_pipeline_mut(&mut self, pipeline: &mut Pipeline) {
+    walk_pipeline_mut(self, pipeline);
+  }
+
+  fn visit_pipeline_inner_mut(&mut self, inner: &mut PipelineInner) {
+    walk_pipeline_inner_mut(self, inner);
+  }
+
+  fn visit_boolean_list_mut(&mut self, list: &mut BooleanList) {
+    walk_boolean_list_mut(self, list);
+  }
+
+  fn visit_pipe_sequence_mut(&mut self, sequence: &mut PipeSequence) {
+    walk_pipe_sequence_mut(self, sequence);
+  }
+
+  fn visit_command_mut(&mut self, command: &mut Command) {
+    walk_command_mut(self, command);
+  }
+
+  fn visit_command_inner_mut(&mut self, inner: &mut CommandInner) {
+    walk_command_inner_mut(self, inner);
+  }
+
+  fn visit_if_clause_mut(&mut self, clause: &mut IfClause) {
+    walk_if_clause_mut(self, clause);
+  }
+
+  fn visit_else_part_mut(&mut self, else_part: &mut ElsePart) {
+    walk_else_part_mut(self, else_part);
+  }
+
+  fn visit_for_clause_mut(&mut self, clause: &mut ForClause) {
+    walk_for_clause_mut(self, clause);
+  }
+
+  fn visit_while_clause_mut(&mut self, clause: &mut WhileClause) {
+    walk_while_clause_mut(self, clause);
+  }
+
+  fn visit_case_clause_mut(&mut self, clause: &mut CaseClause) {
+    walk_case_clause_mut(self, clause);
+  }
+
+  fn visit_case_item_mut(&mut self, item: &mut CaseItem) {
+    walk_case_item_mut(self, item);
+  }
+
+  fn visit_simple_command_mut(&mut self, command: &mut SimpleCommand) {
+    walk_simple_command_mut(self, command);
+  }
+
+  fn visit_env_var_mut(&mut self, env_var: &mut EnvVar) {
+    walk_env_var_mut(self, env_var);
+  }
+
+  fn visit_string_or_word_mut(&mut self, value: &mut StringOrWord) {
+    walk_string_or_word_mut(self, value);
+  }
+
+  fn visit_string_part_mut(&mut self, part: &mut StringPart) {
+    walk_string_part_mut(self, part);
+  }
+
+  fn visit_parameter_expansion_mut(
+    &mut self,
+    expansion: &mut ParameterExpansion,
+  ) {
+    walk_parameter_expansion_mut(self, expansion);
+  }
+
+  fn visit_parameter_expansion_op_mut(
+    &mut self,
+    op: &mut ParameterExpansionOp,
+  ) {
+    walk_parameter_expansion_op_mut(self, op);
+  }
+
+  fn visit_arithmetic_expr_mut(&mut self, expr: &mut ArithmeticExpr) {
+    walk_arithmetic_expr_mut(self, expr);
+  }
+
+  fn visit_redirect_mut(&mut self, redirect: &mut Redirect) {
+    walk_redirect_mut(self, redirect);
+  }
+
+  fn visit_io_file_mut(&mut self, io_file: &mut IoFile) {
+    walk_io_file_mut(self, io_file);
+  }
+
+  fn visit_here_doc_mut(&mut self, here_doc: &mut HereDoc) {
+    walk_here_doc_mut(self, here_doc);
+  }
+
+  /// Called with the span of each node before visiting its children.
+  fn visit_span_mut(&mut self, _span: &mut Span) {}
+}
+
+pub fn walk_sequential_list_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  list: &mut SequentialList,
+) {
+  visitor.visit_span_mut(&mut list.span);
+  for item in &mut list.items {
+    visitor.visit_sequential_list_item_mut(item);
+  }
+}
+
+pub fn walk_sequential_list_item_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  item: &mut SequentialListItem,
+) {
+  visitor.visit_span_mut(&mut item.span);
+  visitor.visit_sequence_mut(&mut item.sequence);
+}
+
+pub fn walk_sequence_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  sequence: &mut Sequence,
+) {
+  match sequence {
+    Sequence::ShellVar(env_var) => visitor.visit_env_var_mut(env_var),
+    Sequence::Pipeline(pipeline) => visitor.visit_pipeline_mut(pipeline),
+    Sequence::BooleanList(list) => visitor.visit_boolean_list_mut(list),
+    Sequence::FunctionDefinition(definition) => {
+      visitor.visit_function_definition_mut(definition)
+    }
+  }
+}
+
+pub fn walk_function_definition_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  definition: &mut FunctionDefinition,
+) {
+  visitor.visit_span_mut(&mut definition.span);
+  visitor.visit_sequential_list_mut(&mut definition.body);
+}
+
+pub fn walk_pipeline_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  pipeline: &mut Pipeline,
+) {
+  visitor.visit_span_mut(&mut pipeline.span);
+  visitor.visit_pipeline_inner_mut(&mut pipeline.inner);
+}
+
+pub fn walk_pipeline_inner_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  inner: &mut PipelineInner,
+) {
+  match inner {
+    PipelineInner::Command(command) => visitor.visit_command_mut(command),
+    PipelineInner::PipeSequence(sequence) => {
+      visitor.visit_pipe_sequence_mut(sequence)
+    }
+  }
+}
+
+pub fn walk_boolean_list_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  list: &mut BooleanList,
+) {
+  visitor.visit_span_mut(&mut list.span);
+  visitor.visit_sequence_mut(&mut list.current);
+  visitor.visit_sequence_mut(&mut list.next);
+}
+
+pub fn walk_pipe_sequence_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  sequence: &mut PipeSequence,
+) {
+  visitor.visit_span_mut(&mut sequence.span);
+  visitor.visit_command_mut(&mut sequence.current);
+  visitor.visit_pipeline_inner_mut(&mut sequence.next);
+}
+
+pub fn walk_command_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  command: &mut Command,
+) {
+  visitor.visit_span_mut(&mut command.span);
+  visitor.visit_command_inner_mut(&mut command.inner);
+  for redirect in &mut command.redirects {
+    visitor.visit_redirect_mut(redirect);
+  }
+}
+
+pub fn walk_command_inner_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  inner: &mut CommandInner,
+) {
+  match inner {
+    CommandInner::Simple(command) => visitor.visit_simple_command_mut(command),
+    CommandInner::Subshell(list) | CommandInner::Group(list) => {
+      visitor.visit_sequential_list_mut(list)
+    }
+    CommandInner::If(clause) => visitor.visit_if_clause_mut(clause),
+    CommandInner::For(clause) => visitor.visit_for_clause_mut(clause),
+    CommandInner::While(clause) => visitor.visit_while_clause_mut(clause),
+    CommandInner::Case(clause) => visitor.visit_case_clause_mut(clause),
+  }
+}
+
+pub fn walk_if_clause_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  clause: &mut IfClause,
+) {
+  visitor.visit_span_mut(&mut clause.span);
+  visitor.visit_sequential_list_mut(&mut clause.condition);
+  visitor.visit_sequential_list_mut(&mut clause.body);
+  if let Some(else_part) = &mut clause.else_part {
+    visitor.visit_else_part_mut(else_part);
+  }
+}
+
+pub fn walk_else_part_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  else_part: &mut ElsePart,
+) {
+  match else_part {
+    ElsePart::Elif(clause) => visitor.visit_if_clause_mut(clause),
+    ElsePart::Else(list) => visitor.visit_sequential_list_mut(list),
+  }
+}
+
+pub fn walk_for_clause_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  clause: &mut ForClause,
+) {
+  visitor.visit_span_mut(&mut clause.span);
+  for word in &mut clause.wordlist {
+    visitor.visit_string_or_word_mut(word);
+  }
+  visitor.visit_sequential_list_mut(&mut clause.body);
+}
+
+pub fn walk_while_clause_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  clause: &mut WhileClause,
+) {
+  visitor.visit_span_mut(&mut clause.span);
+  visitor.visit_sequential_list_mut(&mut clause.condition);
+  visitor.visit_sequential_list_mut(&mut clause.body);
+}
+
+pub fn walk_case_clause_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  clause: &mut CaseClause,
+) {
+  visitor.visit_span_mut(&mut clause.span);
+  visitor.visit_string_or_word_mut(&mut clause.word);
+  for item in &mut clause.items {
+    visitor.visit_case_item_mut(item);
+  }
+}
+
+pub fn walk_case_item_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  item: &mut CaseItem,
+) {
+  visitor.visit_span_mut(&mut item.span);
+  for pattern in &mut item.patterns {
+    visitor.visit_string_or_word_mut(pattern);
+  }
+  visitor.visit_sequential_list_mut(&mut item.body);
+}
+
+pub fn walk_simple_command_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  command: &mut SimpleCommand,
+) {
+  visitor.visit_span_mut(&mut command.span);
+  for env_var in &mut command.env_vars {
+    visitor.visit_env_var_mut(env_var);
+  }
+  for arg in &mut command.args {
+    visitor.visit_string_or_word_mut(arg);
+  }
+}
+
+pub fn walk_env_var_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  env_var: &mut EnvVar,
+) {
+  visitor.visit_span_mut(&mut env_var.span);
+  visitor.visit_string_or_word_mut(&mut env_var.value);
+}
+
+pub fn walk_string_or_word_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  value: &mut StringOrWord,
+) {
+  match value {
+    StringOrWord::Word { parts, span }
+    | StringOrWord::String { parts, span } => {
+      visitor.visit_span_mut(span);
+      for part in parts {
+        visitor.visit_string_part_mut(part);
+      }
+    }
+  }
+}
+
+pub fn walk_string_part_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  part: &mut StringPart,
+) {
+  match part {
+    StringPart::Text { span, .. }
+    | StringPart::Variable { span, .. }
+    | StringPart::Glob { span, .. }
+    | StringPart::Tilde { span } => visitor.visit_span_mut(span),
+    StringPart::Command { list, span } => {
+      visitor.visit_span_mut(span);
+      visitor.visit_sequential_list_mut(list);
+    }
+    StringPart::ParameterExpansion(expansion) => {
+      visitor.visit_parameter_expansion_mut(expansion)
+    }
+    StringPart::Arithmetic { expr, span } => {
+      visitor.visit_span_mut(span);
+      visitor.visit_arithmetic_expr_mut(expr);
+    }
+  }
+}
+
+pub fn walk_parameter_expansion_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  expansion: &mut ParameterExpansion,
+) {
+  visitor.visit_span_mut(&mut expansion.span);
+  if let Some(op) = &mut expansion.op {
+    visitor.visit_parameter_expansion_op_mut(op);
+  }
+}
+
+pub fn walk_parameter_expansion_op_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  op: &mut ParameterExpansionOp,
+) {
+  match op {
+    ParameterExpansionOp::Length => {}
+    ParameterExpansionOp::UseDefault { word, .. }
+    | ParameterExpansionOp::AssignDefault { word, .. }
+    | ParameterExpansionOp::UseAlternative { word, .. }
+    | ParameterExpansionOp::ErrorIfUnset { word, .. }
+    | ParameterExpansionOp::RemovePrefix { pattern: word, .. }
+    | ParameterExpansionOp::RemoveSuffix { pattern: word, .. } => {
+      for part in word {
+        visitor.visit_string_part_mut(part);
+      }
+    }
+  }
+}
+
+pub fn walk_arithmetic_expr_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  expr: &mut ArithmeticExpr,
+) {
+  match expr {
+    ArithmeticExpr::Number { span, .. }
+    | ArithmeticExpr::Variable { span, .. } => visitor.visit_span_mut(span),
+    ArithmeticExpr::Parentheses { expr, span } => {
+      visitor.visit_span_mut(span);
+      visitor.visit_arithmetic_expr_mut(expr);
+    }
+    ArithmeticExpr::Unary { operand, span, .. } => {
+      visitor.visit_span_mut(span);
+      visitor.visit_arithmetic_expr_mut(operand);
+    }
+    ArithmeticExpr::Binary {
+      left, right, span, ..
+    } => {
+      visitor.visit_span_mut(span);
+      visitor.visit_arithmetic_expr_mut(left);
+      visitor.visit_arithmetic_expr_mut(right);
+    }
+  }
+}
+
+pub fn walk_redirect_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  redirect: &mut Redirect,
+) {
+  visitor.visit_span_mut(&mut redirect.span);
+  visitor.visit_io_file_mut(&mut redirect.io_file);
+}
+
+pub fn walk_io_file_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  io_file: &mut IoFile,
+) {
+  match io_file {
+    IoFile::Word(word) => visitor.visit_string_or_word_mut(word),
+    IoFile::HereDoc(here_doc) => visitor.visit_here_doc_mut(here_doc),
+    IoFile::Fd(_) | IoFile::Close => {}
+  }
+}
+
+pub fn walk_here_doc_mut<V: VisitMut + ?Sized>(
+  visitor: &mut V,
+  here_doc: &mut HereDoc,
+) {
+  visitor.visit_span_mut(&mut here_doc.span);
+  for part in here_doc.body.iter_mut().flatten() {
+    visitor.visit_string_part_mut(part);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::printer::print;
+  use pretty_assertions::assert_eq;
+
+  #[test]
+  fn test_visit() {
+    #[derive(Default)]
+    struct CommandNames(Vec<String>);
+
+    impl Visit for CommandNames {
+      fn visit_simple_command(&mut self, command: &SimpleCommand) {
+        if let [StringPart::Text { value, .. }] =
+          command.args[0].parts().as_slice()
+        {
+          self.0.push(value.clone());
+        }
+        walk_simple_command(self, command);
+      }
+    }
+
+    let list = parse(concat!(
+      "A=1 deno run $(git rev-parse HEAD) && (echo 1 | cat) ||",
+      " if test -f x; then rm x; fi",
+    ))
+    .unwrap();
+    let mut names = CommandNames::default();
+    names.visit_sequential_list(&list);
+    assert_eq!(names.0, vec!["deno", "git", "echo", "cat", "test", "rm"]);
+
+    #[derive(Default)]
+    struct VariableNames(Vec<String>);
+
+    impl Visit for VariableNames {
+      fn visit_string_part(&mut self, part: &StringPart) {
+        if let StringPart::Variable { name, .. } = part {
+          self.0.push(name.clone());
+        }
+        walk_string_part(self, part);
+      }
+
+      fn visit_parameter_expansion(&mut self, expansion: &ParameterExpansion) {
+        self.0.push(expansion.name.clone());
+        walk_parameter_expansion(self, expansion);
+      }
+
+      fn visit_arithmetic_expr(&mut self, expr: &ArithmeticExpr) {
+        if let ArithmeticExpr::Variable { name, .. } = expr {
+          self.0.push(name.clone());
+        }
+        walk_arithmetic_expr(self, expr);
+      }
+    }
+
+    let list = parse("echo $A \"${B:-$C}\" $((D + 1)) <<EOF\n$E\nEOF").unwrap();
+    let mut names = VariableNames::default();
+    names.visit_sequential_list(&list);
+    assert_eq!(names.0, vec!["A", "B", "C", "D", "E"]);
+  }
+
+  #[test]
+  fn test_visit_mut() {
+    struct RenameCommand;
+
+    impl VisitMut for RenameCommand {
+      fn visit_simple_command_mut(&mut self, command: &mut SimpleCommand) {
+        // the parsed word has a span, so only its text is compared
+        let is_npm = match command.args[0].parts().as_slice() {
+          [StringPart::Text { value, .. }] => value == "npm",
+          _ => false,
+        };
+        if is_npm {
+          command.args[0] = StringOrWord::new_word("pnpm");
+        }
+        walk_simple_command_mut(self, command);
+      }
+    }
+
+    let mut list =
+      parse("npm install && echo $(npm -v) 'npm' | { npm test; }").unwrap();
+    RenameCommand.visit_sequential_list_mut(&mut list);
+    assert_eq!(
+      print(&list),
+      "pnpm install && echo $(pnpm -v) 'npm' | { pnpm test; }",
+    );
+  }
+}